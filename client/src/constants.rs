@@ -9,6 +9,8 @@ pub const BASE_SPECULAR_HIGHLIGHT: f32 = 0.;
 
 // increase render distance if we build the project in release mode
 pub const DEFAULT_CHUNK_RENDER_DISTANCE_RADIUS: u32 = if cfg!(debug_assertions) { 2 } else { 4 };
+pub const DEFAULT_CHUNK_VERTICAL_RENDER_DISTANCE_RADIUS: u32 =
+    if cfg!(debug_assertions) { 2 } else { 4 };
 
 pub const CELESTIAL_SIZE: f32 = 10.;
 pub const CELESTIAL_DISTANCE: f32 = 50.; // Low value for testing ; will be increased later
@@ -16,6 +18,8 @@ pub const DAY_DURATION: f32 = 60.;
 
 pub const MAX_INVENTORY_SLOTS: u32 = 4 * 9;
 pub const MAX_HOTBAR_SLOTS: u32 = 9;
+/// Crafting slots come after the inventory slots, numbered row by row in a 3×3 grid
+pub const CRAFTING_SLOTS_START: u32 = MAX_INVENTORY_SLOTS;
 
 pub const HOTBAR_CELL_SIZE: f32 = 50.;
 pub const HOTBAR_PADDING: f32 = 5.;
@@ -4,7 +4,7 @@ use crate::ui::hud::chat::{render_chat, setup_chat};
 use bevy::prelude::*;
 use bevy_atmosphere::prelude::*;
 use inventory::Inventory;
-use shared::messages::PlayerSpawnEvent;
+use shared::messages::{CraftResponse, DroppedItemsUpdate, ItemPickedUp, PlayerSpawnEvent};
 
 use crate::world::time::ClientTime;
 use crate::world::ClientWorldMap;
@@ -30,14 +30,14 @@ use crate::camera::*;
 use crate::input::*;
 use crate::player::*;
 use crate::ui::hud::inventory::*;
-use shared::world::{BlockId, ItemId, WorldSeed};
+use shared::world::{BlockId, ItemId, WorldHeight, WorldSeed};
 
 use crate::menus::loading::load_loading_screen;
 use crate::network::{
-    establish_authenticated_connection_to_server, init_server_connection,
+    apply_received_chunks, establish_authenticated_connection_to_server, init_server_connection,
     launch_local_server_system, network_failure_handler, poll_network_messages,
     send_player_position_to_server, terminate_server_connection, upload_player_inputs_system,
-    CurrentPlayerProfile, TargetServer, TargetServerState,
+    CurrentPlayerProfile, PendingChunkPayloads, TargetServer, TargetServerState,
 };
 use crate::{DisplayQuality, GameState, Volume};
 
@@ -84,13 +84,21 @@ pub fn game_plugin(app: &mut App) {
         .insert_resource(AtlasHandles::<BlockId>::default())
         .insert_resource(AtlasHandles::<ItemId>::default())
         .insert_resource(RenderDistance { ..default() })
+        .insert_resource(WorldHeight::default())
         .insert_resource(UIMode::Closed)
         .insert_resource(ViewMode::FirstPerson)
         .insert_resource(DebugOptions::default())
         .insert_resource(Inventory::new())
+        .insert_resource(CraftingGrid::default())
+        .insert_resource(BlockBreaking::default())
+        .insert_resource(DroppedItems::default())
         .insert_resource(CurrentPlayerProfile::new())
+        .insert_resource(PendingChunkPayloads::default())
         .add_event::<WorldRenderRequestUpdateEvent>()
         .add_event::<PlayerSpawnEvent>()
+        .add_event::<CraftResponse>()
+        .add_event::<DroppedItemsUpdate>()
+        .add_event::<ItemPickedUp>()
         .add_systems(
             OnEnter(GameState::PreGameLoading),
             (
@@ -129,14 +137,14 @@ pub fn game_plugin(app: &mut App) {
         )
         .add_systems(
             OnEnter(GameState::Game),
-            (print_settings, setup_chunk_ghost),
+            (print_settings, setup_chunk_ghost, setup_crack_overlay),
         )
         .add_systems(
             Update,
             (
                 render_pause_menu,
                 render_chat,
-                render_inventory_hotbar,
+                (render_inventory_hotbar, update_crafting).chain(),
                 set_ui_mode,
             )
                 .run_if(in_state(GameState::Game)),
@@ -146,7 +154,16 @@ pub fn game_plugin(app: &mut App) {
             (
                 render_distance_update_system,
                 player_movement_system,
-                (handle_block_interactions, camera_control_system).chain(),
+                (
+                    handle_block_interactions,
+                    handle_block_breaking,
+                    camera_control_system,
+                )
+                    .chain(),
+                update_crack_overlay,
+                (update_dropped_items, animate_dropped_items).chain(),
+                pick_up_dropped_items,
+                handle_item_dropping,
                 fps_text_update_system,
                 coords_text_update_system,
                 total_blocks_text_update_system,
@@ -169,6 +186,7 @@ pub fn game_plugin(app: &mut App) {
             Update,
             (
                 poll_network_messages,
+                apply_received_chunks,
                 network_failure_handler,
                 upload_player_inputs_system,
                 send_player_position_to_server,
@@ -182,8 +200,18 @@ pub fn game_plugin(app: &mut App) {
         );
 }
 
-fn clear_resources(mut world_map: ResMut<ClientWorldMap>) {
+fn clear_resources(
+    mut world_map: ResMut<ClientWorldMap>,
+    mut pending_chunks: ResMut<PendingChunkPayloads>,
+    mut dropped_items: ResMut<DroppedItems>,
+    (mut inventory, mut crafting_grid): (ResMut<Inventory>, ResMut<CraftingGrid>),
+) {
     world_map.map = HashMap::new();
+    pending_chunks.tasks.clear();
+    *dropped_items = DroppedItems::default();
+    // The server of the next game sends the items the player holds there
+    *inventory = Inventory::new();
+    *crafting_grid = CraftingGrid::default();
     world_map.total_blocks_count = 0;
     world_map.total_chunks_count = 0;
     world_map.name = "".into();
@@ -24,4 +24,5 @@ pub enum GameAction {
     RenderDistancePlus,
     ReloadChunks,
     DebugGetBlock,
+    DropItem,
 }
@@ -72,12 +72,20 @@ pub fn get_bindings(game_folder_path: &String) -> KeyMap {
 
     // Try to get & serialize existing binds
     if let Ok(content) = fs::read_to_string(binds_path.as_path()) {
-        if let Ok(key_map) = from_str::<KeyMap>(&content) {
+        if let Ok(mut key_map) = from_str::<KeyMap>(&content) {
+            // Actions added since the binds were saved get their default keys
+            for (action, keys) in default_bindings().map {
+                key_map.map.entry(action).or_insert(keys);
+            }
             return key_map;
         }
     }
 
     // If binds cannot be loaded, get default ones
+    default_bindings()
+}
+
+fn default_bindings() -> KeyMap {
     KeyMap {
         map: {
             let mut map = BTreeMap::new();
@@ -112,6 +120,7 @@ pub fn get_bindings(game_folder_path: &String) -> KeyMap {
             map.insert(GameAction::RenderDistancePlus, vec![KeyCode::KeyP]);
             map.insert(GameAction::ReloadChunks, vec![KeyCode::KeyR]);
             map.insert(GameAction::DebugGetBlock, vec![KeyCode::KeyI]);
+            map.insert(GameAction::DropItem, vec![KeyCode::KeyQ]);
             map
         },
     }
@@ -21,8 +21,10 @@ use input::{data::GameAction, keyboard::get_bindings};
 use menus::settings::{DisplayQuality, Volume};
 use menus::solo::SelectedWorld;
 use serde::{Deserialize, Serialize};
+use shared::world::{BiomeRegistry, BlockRegistry, ItemRegistry, RecipeRegistry};
 use shared::GameFolderPaths;
 use std::collections::BTreeMap;
+use std::path::Path;
 use ui::menus::{self, splash};
 
 #[derive(Parser, Debug)]
@@ -134,6 +136,11 @@ fn main() {
             }),
     );
 
+    let assets_folder = Path::new(&game_folder_paths.assets_folder_path);
+    let blocks = BlockRegistry::load(assets_folder);
+    let items = ItemRegistry::load(assets_folder, &blocks)
+        .unwrap_or_else(|e| panic!("Error loading items: {}", e));
+
     app.add_event::<LoadWorldEvent>();
     network::add_base_netcode(&mut app);
     app.insert_resource(DisplayQuality::Medium)
@@ -145,6 +152,11 @@ fn main() {
         .insert_resource(TexturePath {
             path: texture_path.to_string(),
         })
+        .insert_resource(BiomeRegistry::load(assets_folder, &blocks))
+        // Replaced by the blocks, items and recipes of the server once connected
+        .insert_resource(RecipeRegistry::load(assets_folder, &items))
+        .insert_resource(items)
+        .insert_resource(blocks)
         .insert_resource(game_folder_paths)
         .init_state::<GameState>()
         .enable_state_scoped_entities::<GameState>()
@@ -1,8 +1,11 @@
-use bevy::{math::IVec3, prelude::ResMut};
+use bevy::{
+    math::{IVec3, Vec3},
+    prelude::ResMut,
+};
 use bevy_renet::renet::{DefaultChannel, RenetClient};
 use bincode::Options;
-use shared::messages::{ChatMessage, ClientToServerMessage, SaveWorldRequest};
-use shared::world::BlockData;
+use shared::messages::{ChatMessage, ClientToServerMessage, CraftRequest, SaveWorldRequest};
+use shared::world::{BlockData, ItemStack};
 
 pub enum NetworkAction {
     ChatMessage(String),
@@ -10,12 +13,25 @@ pub enum NetworkAction {
         requested_chunks: Vec<IVec3>,
         player_chunk_pos: IVec3,
         render_distance: u32,
+        vertical_render_distance: u32,
     },
     SaveWorldRequest,
     BlockInteraction {
         position: IVec3,
         block_type: Option<BlockData>, // None = suppression, Some = ajout
     },
+    BlockBreakingStart {
+        position: IVec3,
+        tool: Option<ItemStack>,
+    },
+    CraftRequest(CraftRequest),
+    DropItem {
+        stack: ItemStack,
+        direction: Vec3,
+    },
+    PickUpItem {
+        id: u64,
+    },
 }
 
 pub fn send_network_action(client: &mut ResMut<RenetClient>, action: NetworkAction) {
@@ -39,12 +55,14 @@ pub fn send_network_action(client: &mut ResMut<RenetClient>, action: NetworkActi
             requested_chunks,
             player_chunk_pos,
             render_distance,
+            vertical_render_distance,
         } => {
             let input_message = bincode::options()
                 .serialize(&ClientToServerMessage::WorldUpdateRequest {
                     player_chunk_position: player_chunk_pos,
                     requested_chunks,
                     render_distance,
+                    vertical_render_distance,
                 })
                 .unwrap();
 
@@ -73,5 +91,33 @@ pub fn send_network_action(client: &mut ResMut<RenetClient>, action: NetworkActi
 
             client.send_message(DefaultChannel::ReliableOrdered, message);
         }
+        NetworkAction::BlockBreakingStart { position, tool } => {
+            let message = bincode::options()
+                .serialize(&ClientToServerMessage::BlockBreakingStart { position, tool })
+                .unwrap();
+
+            client.send_message(DefaultChannel::ReliableOrdered, message);
+        }
+        NetworkAction::CraftRequest(request) => {
+            let message = bincode::options()
+                .serialize(&ClientToServerMessage::CraftRequest(request))
+                .unwrap();
+
+            client.send_message(DefaultChannel::ReliableOrdered, message);
+        }
+        NetworkAction::DropItem { stack, direction } => {
+            let message = bincode::options()
+                .serialize(&ClientToServerMessage::DropItem { stack, direction })
+                .unwrap();
+
+            client.send_message(DefaultChannel::ReliableOrdered, message);
+        }
+        NetworkAction::PickUpItem { id } => {
+            let message = bincode::options()
+                .serialize(&ClientToServerMessage::PickUpItem { id })
+                .unwrap();
+
+            client.send_message(DefaultChannel::ReliableOrdered, message);
+        }
     }
 }
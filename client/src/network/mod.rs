@@ -12,4 +12,4 @@ pub use cleanup::*;
 pub use inputs::*;
 pub use player::*;
 pub use setup::*;
-pub use world::request_world_update;
+pub use world::{apply_received_chunks, request_world_update, PendingChunkPayloads};
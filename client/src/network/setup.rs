@@ -4,12 +4,11 @@ use rand::Rng;
 use shared::{get_shared_renet_config, GameServerConfig};
 
 use crate::menus::solo::SelectedWorld;
-use crate::network::world::update_world_from_network;
+use crate::network::world::{update_world_from_network, PendingChunkPayloads, ServerEventWriters};
 use crate::network::{update_cached_chat_state, CachedChatConversation};
+use crate::player::inventory::Inventory;
 use crate::player::{CurrentPlayerMarker, Player};
-use crate::world::render_distance::RenderDistance;
 use crate::world::time::ClientTime;
-use crate::world::WorldRenderRequestUpdateEvent;
 use bevy_renet::renet::transport::{
     ClientAuthentication, NetcodeClientTransport, NetcodeTransportError,
 };
@@ -22,7 +21,7 @@ use shared::messages::{
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::{net::UdpSocket, thread, time::SystemTime};
 
-use crate::world::ClientWorldMap;
+use shared::world::WorldHeight;
 use shared::GameFolderPaths;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -95,6 +94,8 @@ pub fn launch_local_server_system(
         debug!("Obtained UDP socket: {}", addr);
 
         let world_name_clone = world_name.clone();
+        let world_preset = selected_world.preset.clone();
+        let generator_settings = selected_world.settings.clone();
         let game_folder_path = paths.clone().game_folder_path;
         //
         thread::spawn(move || {
@@ -103,6 +104,9 @@ pub fn launch_local_server_system(
                 GameServerConfig {
                     world_name: world_name_clone,
                     is_solo: true,
+                    world_preset,
+                    generator_settings,
+                    chunk_compression: Default::default(),
                 },
                 game_folder_path,
             );
@@ -131,23 +135,19 @@ fn poll_reliable_ordered_messages(
 
 fn poll_reliable_unordered_messages(
     client: &mut ResMut<RenetClient>,
-    world: &mut ResMut<ClientWorldMap>,
     client_time: ResMut<ClientTime>,
-    ev_render: &mut EventWriter<WorldRenderRequestUpdateEvent>,
     players: &mut Query<(&mut Transform, &Player), With<Player>>,
     current_player_entity: Query<Entity, With<CurrentPlayerMarker>>,
-    render_distance: Res<RenderDistance>,
-    ev_spawn: &mut EventWriter<PlayerSpawnEvent>,
+    events: &mut ServerEventWriters,
+    pending_chunks: &mut ResMut<PendingChunkPayloads>,
 ) {
     update_world_from_network(
         client,
-        world,
         client_time,
-        ev_render,
         players,
         current_player_entity,
-        render_distance,
-        ev_spawn,
+        events,
+        pending_chunks,
     );
 }
 
@@ -155,23 +155,19 @@ pub fn poll_network_messages(
     mut client: ResMut<RenetClient>,
     mut chat_state: ResMut<CachedChatConversation>,
     client_time: ResMut<ClientTime>,
-    mut world: ResMut<ClientWorldMap>,
-    mut ev_render: EventWriter<WorldRenderRequestUpdateEvent>,
     mut players: Query<(&mut Transform, &Player), With<Player>>,
     current_player_entity: Query<Entity, With<CurrentPlayerMarker>>,
-    render_distance: Res<RenderDistance>,
-    mut ev_spawn: EventWriter<PlayerSpawnEvent>,
+    mut events: ServerEventWriters,
+    mut pending_chunks: ResMut<PendingChunkPayloads>,
 ) {
     poll_reliable_ordered_messages(&mut client, &mut chat_state);
     poll_reliable_unordered_messages(
         &mut client,
-        &mut world,
         client_time,
-        &mut ev_render,
         &mut players,
         current_player_entity,
-        render_distance,
-        &mut ev_spawn,
+        &mut events,
+        &mut pending_chunks,
     );
 }
 
@@ -222,7 +218,10 @@ pub fn establish_authenticated_connection_to_server(
     mut client: ResMut<RenetClient>,
     mut target: ResMut<TargetServer>,
     current_profile: Res<CurrentPlayerProfile>,
+    mut world_height: ResMut<WorldHeight>,
+    mut inventory: ResMut<Inventory>,
     mut ev_spawn: EventWriter<PlayerSpawnEvent>,
+    mut commands: Commands,
 ) {
     if target.session_token.is_some() {
         info!(
@@ -254,6 +253,16 @@ pub fn establish_authenticated_connection_to_server(
             target.username = Some(message.username);
             target.session_token = Some(message.session_token);
             target.state = TargetServerState::ConnectionEstablished;
+            *world_height = message.world_height;
+            // The server remembers the items of the player from their last visit
+            *inventory = Inventory::new();
+            for stack in message.inventory {
+                inventory.add_item_to_inventory(stack, &message.items);
+            }
+            // Block and item IDs sent by the server refer to its own blocks and items
+            commands.insert_resource(message.blocks);
+            commands.insert_resource(message.items);
+            commands.insert_resource(message.recipes);
             ev_spawn.send(message.spawn_event);
             info!("Connected! {:?}", target);
         }
@@ -2,13 +2,18 @@ use crate::{
     player::{CurrentPlayerMarker, Player},
     world::ClientChunk,
 };
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
 use bevy_renet::renet::{DefaultChannel, RenetClient};
 use bincode::Options;
 use shared::{
-    messages::{PlayerSpawnEvent, ServerToClientMessage},
-    world::{block_to_chunk_coord, chunk_in_radius},
+    messages::{
+        CraftResponse, DroppedItemsUpdate, ItemPickedUp, PlayerSpawnEvent, ServerToClientMessage,
+    },
+    world::{block_to_chunk_coord, chunk_in_radius, ServerChunk},
 };
+use std::collections::{HashMap, VecDeque};
 
 use crate::world::ClientWorldMap;
 
@@ -18,26 +23,26 @@ use crate::world::WorldRenderRequestUpdateEvent;
 
 use super::api::send_network_action;
 
+/// Events sent to the systems handling the messages of the server
+#[derive(SystemParam)]
+pub struct ServerEventWriters<'w> {
+    spawn: EventWriter<'w, PlayerSpawnEvent>,
+    craft: EventWriter<'w, CraftResponse>,
+    dropped_items: EventWriter<'w, DroppedItemsUpdate>,
+    item_picked_up: EventWriter<'w, ItemPickedUp>,
+}
+
 pub fn update_world_from_network(
     client: &mut ResMut<RenetClient>,
-    world: &mut ResMut<ClientWorldMap>,
     mut client_time: ResMut<ClientTime>,
-    ev_render: &mut EventWriter<WorldRenderRequestUpdateEvent>,
     players: &mut Query<(&mut Transform, &Player), With<Player>>,
     current_player_entity: Query<Entity, With<CurrentPlayerMarker>>,
-    render_distance: Res<RenderDistance>,
-    ev_spawn: &mut EventWriter<PlayerSpawnEvent>,
+    events: &mut ServerEventWriters,
+    pending_chunks: &mut ResMut<PendingChunkPayloads>,
 ) {
-    let (player_pos, current_player) = players.get(current_player_entity.single()).unwrap();
+    let (_, current_player) = players.get(current_player_entity.single()).unwrap();
     let current_player_id = current_player.id;
 
-    let player_pos = IVec3::new(
-        block_to_chunk_coord(player_pos.translation.x as i32),
-        0,
-        block_to_chunk_coord(player_pos.translation.z as i32),
-    );
-    let r = render_distance.distance as i32;
-
     while let Some(bytes) = client.receive_message(DefaultChannel::ReliableUnordered) {
         let msg = bincode::options()
             .deserialize::<ServerToClientMessage>(&bytes)
@@ -45,33 +50,11 @@ pub fn update_world_from_network(
 
         match msg {
             ServerToClientMessage::WorldUpdate(world_update) => {
-                debug!(
-                    "Received world update, {} chunks received",
-                    world_update.new_map.len()
-                );
-
-                trace!("Chunks positions : {:?}", world_update.new_map.keys());
-
-                for (pos, chunk) in world_update.new_map {
-                    // If the chunk is not in render distance range or is empty, do not consider it
-                    if !chunk_in_radius(&player_pos, &pos, r) || chunk.map.is_empty() {
-                        continue;
-                    }
-
-                    let chunk = ClientChunk {
-                        map: chunk.map,
-                        entity: {
-                            if let Some(c) = world.map.get(&pos) {
-                                c.entity
-                            } else {
-                                None
-                            }
-                        },
-                    };
-
-                    world.map.insert(pos, chunk);
-                    ev_render.send(WorldRenderRequestUpdateEvent::ChunkToReload(pos));
-                }
+                // Decompressing and deserializing chunks can take a while, so it is done off the main thread
+                let payload = world_update.new_map;
+                pending_chunks
+                    .tasks
+                    .push_back(AsyncComputeTaskPool::get().spawn(async move { payload.decode() }));
 
                 debug!("Player pos {:?}", world_update.player_positions);
 
@@ -93,13 +76,93 @@ pub fn update_world_from_network(
             }
             ServerToClientMessage::PlayerSpawn(spawn_event) => {
                 info!("Received SINGLE spawn event {:?}", spawn_event);
-                ev_spawn.send(spawn_event);
+                events.spawn.send(spawn_event);
+            }
+            ServerToClientMessage::CraftResponse(response) => {
+                events.craft.send(response);
+            }
+            ServerToClientMessage::DroppedItems(update) => {
+                events.dropped_items.send(update);
+            }
+            ServerToClientMessage::ItemPickedUp(picked_up) => {
+                events.item_picked_up.send(picked_up);
             }
             _ => {}
         }
     }
 }
 
+/// Chunks received from the server, waiting to be decoded
+#[derive(Resource, Default)]
+pub struct PendingChunkPayloads {
+    pub tasks: VecDeque<Task<ChunkDecodingResult>>,
+}
+
+type ChunkDecodingResult =
+    Result<HashMap<IVec3, ServerChunk>, Box<dyn std::error::Error + Send + Sync>>;
+
+pub fn apply_received_chunks(
+    mut pending_chunks: ResMut<PendingChunkPayloads>,
+    mut world: ResMut<ClientWorldMap>,
+    mut ev_render: EventWriter<WorldRenderRequestUpdateEvent>,
+    player: Query<&Transform, With<CurrentPlayerMarker>>,
+    render_distance: Res<RenderDistance>,
+) {
+    let Ok(player_pos) = player.get_single() else {
+        return;
+    };
+    let player_pos = IVec3::new(
+        block_to_chunk_coord(player_pos.translation.x as i32),
+        block_to_chunk_coord(player_pos.translation.y as i32),
+        block_to_chunk_coord(player_pos.translation.z as i32),
+    );
+    let r = render_distance.distance as i32;
+    let vertical_r = render_distance.vertical_distance as i32;
+
+    // Updates are applied in the order they were received, so that a newer version
+    // of a chunk is never overwritten by an older one
+    while let Some(task) = pending_chunks.tasks.front_mut() {
+        let Some(result) = block_on(future::poll_once(task)) else {
+            break;
+        };
+        pending_chunks.tasks.pop_front();
+
+        let new_map = match result {
+            Ok(new_map) => new_map,
+            Err(e) => {
+                error!("Failed to decode chunks from the server: {}", e);
+                continue;
+            }
+        };
+
+        debug!("Received world update, {} chunks received", new_map.len());
+
+        trace!("Chunks positions : {:?}", new_map.keys());
+
+        for (pos, chunk) in new_map {
+            // If the chunk is not in render distance range or is empty, do not consider it
+            if !chunk_in_radius(&player_pos, &pos, r, vertical_r) || chunk.map.is_empty() {
+                continue;
+            }
+
+            let chunk = ClientChunk {
+                map: chunk.map,
+                tints: chunk.tints,
+                entity: {
+                    if let Some(c) = world.map.get(&pos) {
+                        c.entity
+                    } else {
+                        None
+                    }
+                },
+            };
+
+            world.map.insert(pos, chunk);
+            ev_render.send(WorldRenderRequestUpdateEvent::ChunkToReload(pos));
+        }
+    }
+}
+
 pub fn request_world_update(
     client: &mut ResMut<RenetClient>,
     requested_chunks: Vec<IVec3>,
@@ -112,6 +175,7 @@ pub fn request_world_update(
             requested_chunks,
             player_chunk_pos,
             render_distance: render_distance.distance,
+            vertical_render_distance: render_distance.vertical_distance,
         },
     );
 }
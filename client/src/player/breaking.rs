@@ -0,0 +1,267 @@
+use crate::camera::BlockRaycastSet;
+use crate::constants::{CUBE_SIZE, INTERACTION_DISTANCE};
+use crate::network::api::{send_network_action, NetworkAction};
+use crate::player::interactions::targeted_block;
+use crate::player::inventory::Inventory;
+use crate::ui::hud::hotbar::Hotbar;
+use crate::ui::hud::UIMode;
+use crate::world::{ClientWorldMap, WorldRenderRequestUpdateEvent};
+use crate::GameState;
+use bevy::math::NormedVectorSpace;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use bevy_mod_raycast::prelude::RaycastSource;
+use bevy_renet::renet::RenetClient;
+use shared::world::{BlockRegistry, ItemRegistry, ItemStack};
+
+use super::CurrentPlayerMarker;
+
+/// Number of crack textures shown while a block is being broken
+const CRACK_STAGES: usize = 10;
+
+/// Block the player is breaking by holding the left mouse button
+#[derive(Resource, Debug, Default)]
+pub struct BlockBreaking {
+    position: Option<IVec3>,
+    tool: Option<ItemStack>,
+    /// Seconds spent breaking the block
+    progress: f32,
+    /// Seconds needed to break the block with the tool
+    required: f32,
+}
+
+impl BlockBreaking {
+    /// Block being broken, with the part of the breaking time already spent
+    pub fn progress(&self) -> Option<(IVec3, f32)> {
+        self.position
+            .map(|position| (position, (self.progress / self.required).min(1.)))
+    }
+}
+
+/// Cube drawn over the block being broken, with a crack texture for each breaking stage
+#[derive(Component)]
+pub struct CrackOverlay {
+    stages: Vec<Handle<StandardMaterial>>,
+}
+
+pub fn handle_block_breaking(
+    (player, raycast_source, hotbar): (
+        Query<&Transform, With<CurrentPlayerMarker>>,
+        Query<&RaycastSource<BlockRaycastSet>>,
+        Query<&Hotbar>,
+    ),
+    (mut world_map, mut inventory, mut breaking, mut client): (
+        ResMut<ClientWorldMap>,
+        ResMut<Inventory>,
+        ResMut<BlockBreaking>,
+        ResMut<RenetClient>,
+    ),
+    (mouse_input, ui_mode, time, blocks, items): (
+        Res<ButtonInput<MouseButton>>,
+        Res<UIMode>,
+        Res<Time>,
+        Res<BlockRegistry>,
+        Res<ItemRegistry>,
+    ),
+    mut ev_render: EventWriter<WorldRenderRequestUpdateEvent>,
+) {
+    if *ui_mode == UIMode::Opened || !mouse_input.pressed(MouseButton::Left) {
+        *breaking = BlockBreaking::default();
+        return;
+    }
+
+    // Block targeted by the player, if close enough
+    let target = raycast_source
+        .single()
+        .intersections()
+        .first()
+        .filter(|(_, intersection)| {
+            (intersection.position() - player.single().translation).norm() < INTERACTION_DISTANCE
+        })
+        .map(|(_, intersection)| targeted_block(intersection))
+        .and_then(|position| {
+            world_map
+                .get_block_by_coordinates(&position)
+                .map(|block| (position, block.id))
+        });
+
+    let slot = hotbar.single().selected;
+    let tool = inventory
+        .inner
+        .get(&slot)
+        .copied()
+        .filter(|stack| items.get(stack.item_id).tool().is_some());
+    let tool_definition = tool.map(|tool| items.get(tool.item_id));
+
+    // Liquids and unbreakable blocks are never broken
+    let Some((position, required)) = target.and_then(|(position, block)| {
+        let definition = blocks.get(block);
+        if definition.is_liquid() {
+            return None;
+        }
+        definition
+            .breaking_time(tool_definition)
+            .map(|required| (position, required))
+    }) else {
+        *breaking = BlockBreaking::default();
+        return;
+    };
+
+    // Breaking starts over when the player looks at another block or switches tools,
+    // even for one of the same kind since the server wears out the one it was told about
+    if breaking.position != Some(position) || breaking.tool != tool {
+        *breaking = BlockBreaking {
+            position: Some(position),
+            tool,
+            progress: 0.,
+            required,
+        };
+        send_network_action(
+            &mut client,
+            NetworkAction::BlockBreakingStart { position, tool },
+        );
+    }
+
+    breaking.progress += time.delta_seconds();
+    if breaking.progress < required {
+        return;
+    }
+    *breaking = BlockBreaking::default();
+
+    // Both halves of doors are broken together
+    let other_half = world_map
+        .get_block_by_coordinates(&position)
+        .and_then(|block| {
+            blocks
+                .get(block.id)
+                .other_half(block, position)
+                .filter(|other| {
+                    world_map
+                        .get_block_by_coordinates(other)
+                        .is_some_and(|other| other.id == block.id)
+                })
+        });
+    if let Some(other_half) = other_half {
+        world_map.remove_block_by_coordinates(&other_half);
+        ev_render.send(WorldRenderRequestUpdateEvent::BlockToReload(other_half));
+    }
+
+    // Its items are dropped by the server
+    world_map.remove_block_by_coordinates(&position);
+
+    // Blocks broken instantly do not wear tools out
+    if tool.is_some() && required > 0. {
+        inventory.wear_tool(slot);
+    }
+
+    ev_render.send(WorldRenderRequestUpdateEvent::BlockToReload(position));
+
+    // The server checks that the block was broken for long enough
+    send_network_action(
+        &mut client,
+        NetworkAction::BlockInteraction {
+            position,
+            block_type: None,
+        },
+    );
+}
+
+pub fn setup_crack_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let stages = (0..CRACK_STAGES)
+        .map(|stage| {
+            materials.add(StandardMaterial {
+                base_color_texture: Some(images.add(crack_image(stage))),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    commands.spawn((
+        StateScoped(GameState::Game),
+        NotShadowCaster,
+        PbrBundle {
+            // Slightly larger than blocks, to be drawn over their faces
+            mesh: meshes.add(Cuboid::from_length(CUBE_SIZE * 1.005)),
+            material: stages[0].clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        CrackOverlay { stages },
+    ));
+}
+
+pub fn update_crack_overlay(
+    mut overlay: Query<(
+        &CrackOverlay,
+        &mut Handle<StandardMaterial>,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+    breaking: Res<BlockBreaking>,
+) {
+    let (overlay, mut material, mut transform, mut visibility) = overlay.single_mut();
+    let Some((position, progress)) = breaking.progress() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let stage = ((progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1);
+    *material = overlay.stages[stage].clone();
+    transform.translation = position.as_vec3() + Vec3::splat(CUBE_SIZE / 2.);
+    *visibility = Visibility::Visible;
+}
+
+/// Transparent texture with cracks growing with the breaking stage
+fn crack_image(stage: usize) -> Image {
+    const SIZE: usize = 16;
+    let mut data = vec![0u8; SIZE * SIZE * 4];
+
+    // Cracks are random walks from the center, always the same ones and longer at each stage :
+    // a small linear congruential generator is enough
+    let mut seed: u32 = 0x2545_f491;
+    let mut random = |max: u32| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (seed >> 16) % max
+    };
+    let length = 2 + stage * SIZE / 2 / CRACK_STAGES;
+    for _ in 0..6 {
+        let (mut x, mut y) = (SIZE as i32 / 2, SIZE as i32 / 2);
+        let (dx, dy) = [(1, 0), (-1, 0), (0, 1), (0, -1)][random(4) as usize];
+        for step in 0..SIZE {
+            if step < length && (0..SIZE as i32).contains(&x) && (0..SIZE as i32).contains(&y) {
+                let pixel = (y as usize * SIZE + x as usize) * 4;
+                data[pixel..pixel + 4].copy_from_slice(&[20, 20, 20, 200]);
+            }
+            // Mostly straight, with some turns
+            match random(4) {
+                0 => (x, y) = (x + dy, y + dx),
+                1 => (x, y) = (x - dy, y - dx),
+                _ => (x, y) = (x + dx, y + dy),
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: SIZE as u32,
+            height: SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
@@ -11,27 +11,40 @@ use crate::world::{ClientWorldMap, WorldRenderRequestUpdateEvent};
 use crate::KeyMap;
 use bevy::prelude::*;
 use bevy_renet::renet::RenetClient;
-use shared::world::{block_to_chunk_coord, chunk_in_radius};
+use shared::world::{block_to_chunk_coord, chunk_in_radius, BlockRegistry, WorldHeight};
 
 use super::CurrentPlayerMarker;
 use crate::world::FirstChunkReceived;
 
-fn is_block_at_position(position: Vec3, world_map: &ClientWorldMap) -> bool {
-    if let Some(block) = world_map.get_block_by_coordinates(&IVec3::new(
-        position.x.floor() as i32,
-        position.y.floor() as i32,
-        position.z.floor() as i32,
-    )) {
-        block.id.has_hitbox()
+fn is_block_at_position(
+    position: Vec3,
+    world_map: &ClientWorldMap,
+    blocks: &BlockRegistry,
+) -> bool {
+    // Only the collision boxes of the block count, such as the lower half of slabs
+    let floor = position.floor();
+    if let Some(block) = world_map.get_block_by_coordinates(&floor.as_ivec3()) {
+        blocks.get(block.id).collides(block, position - floor)
     } else {
         false
     }
 }
 
+fn is_liquid_at_position(
+    position: Vec3,
+    world_map: &ClientWorldMap,
+    blocks: &BlockRegistry,
+) -> bool {
+    world_map
+        .get_block_by_coordinates(&position.floor().as_ivec3())
+        .is_some_and(|block| blocks.get(block.id).is_liquid())
+}
+
 fn check_player_collision(
     player_position: Vec3,
     player: &Player,
     world_map: &ClientWorldMap,
+    blocks: &BlockRegistry,
 ) -> bool {
     // Vérification de la collision avec les pieds et la tête du joueur
     let foot_position = Vec3::new(
@@ -56,7 +69,7 @@ fn check_player_collision(
     // Vérifier la collision au niveau des pieds
     for offset in &offsets {
         let check_pos = foot_position + *offset;
-        if is_block_at_position(check_pos, world_map) {
+        if is_block_at_position(check_pos, world_map, blocks) {
             return true;
         }
     }
@@ -64,7 +77,7 @@ fn check_player_collision(
     // Vérifier la collision au niveau de la tête
     for offset in &offsets {
         let check_pos = head_position + *offset;
-        if is_block_at_position(check_pos, world_map) {
+        if is_block_at_position(check_pos, world_map, blocks) {
             return true;
         }
     }
@@ -88,7 +101,7 @@ pub fn player_movement_system(
         Res<KeyMap>,
         ResMut<Assets<StandardMaterial>>,
         ResMut<ClientWorldMap>,
-        Res<RenderDistance>,
+        (Res<RenderDistance>, Res<WorldHeight>, Res<BlockRegistry>),
         ResMut<ViewMode>,
         ResMut<DebugOptions>,
         ResMut<RenetClient>,
@@ -106,7 +119,7 @@ pub fn player_movement_system(
         key_map,
         mut materials,
         mut world_map,
-        render_distance,
+        (render_distance, world_height, blocks),
         mut view_mode,
         mut debug_options,
         mut client,
@@ -143,12 +156,17 @@ pub fn player_movement_system(
     // If player changed chunks between this frame and the previous
     if player_chunk != *previous_player_chunk {
         let r = render_distance.distance as i32;
+        let vertical_r = render_distance.vertical_distance as i32;
         let mut requested_chunks: Vec<IVec3> = Vec::new();
 
         for x in -r..=r {
             for z in -r..=r {
-                for y in 0..=8 {
-                    let chunk_pos = IVec3::new(player_chunk.x + x, y, player_chunk.z + z);
+                for y in -vertical_r..=vertical_r {
+                    // Nothing is ever generated above or below the limits of the world
+                    if !world_height.contains_chunk(player_chunk.y + y) {
+                        continue;
+                    }
+                    let chunk_pos = player_chunk + IVec3::new(x, y, z);
                     let chunk = world_map.map.get(&chunk_pos);
 
                     if chunk.is_none() {
@@ -161,7 +179,7 @@ pub fn player_movement_system(
         // Only retain chunks in the render radius
         world_map.map.retain(|pos, chunk| {
             // If chunk is empty, or not in render radius
-            if !chunk_in_radius(&player_chunk, pos, r) || chunk.map.is_empty() {
+            if !chunk_in_radius(&player_chunk, pos, r, vertical_r) || chunk.map.is_empty() {
                 // Remove chunk, and delete its associated entity if it exists
                 if let Some(entity) = chunk.entity {
                     commands.entity(entity).despawn_recursive();
@@ -214,9 +232,24 @@ pub fn player_movement_system(
         }
     }
 
-    let speed = if player.is_flying { 15.0 } else { 5.0 };
+    // Swimming applies when the body of the player is in a liquid
+    let feet_position = player_transform.translation - Vec3::Y * (player.height / 2.0);
+    let feet_in_liquid =
+        !player.is_flying && is_liquid_at_position(feet_position, &world_map, &blocks);
+    let body_in_liquid = !player.is_flying
+        && is_liquid_at_position(player_transform.translation, &world_map, &blocks);
+
+    let speed = if player.is_flying {
+        15.0
+    } else if body_in_liquid {
+        3.0
+    } else {
+        5.0
+    };
 
     let jump_velocity = 10.0;
+    let swim_velocity = 3.0;
+    let sink_velocity = 2.0;
 
     // flying mode
     if player.is_flying && *ui_mode == UIMode::Closed {
@@ -261,7 +294,7 @@ pub fn player_movement_system(
         let new_pos_x = player_transform.translation
             + Vec3::new(direction.x, 0.0, 0.0) * speed * time.delta_seconds();
 
-        if player.is_flying || !check_player_collision(new_pos_x, &player, &world_map) {
+        if player.is_flying || !check_player_collision(new_pos_x, &player, &world_map, &blocks) {
             player_transform.translation.x = new_pos_x.x;
         }
 
@@ -269,14 +302,28 @@ pub fn player_movement_system(
         let new_pos_z = player_transform.translation
             + Vec3::new(0.0, 0.0, direction.z) * speed * time.delta_seconds();
 
-        if player.is_flying || !check_player_collision(new_pos_z, &player, &world_map) {
+        if player.is_flying || !check_player_collision(new_pos_z, &player, &world_map, &blocks) {
             player_transform.translation.z = new_pos_z.z;
         }
     }
 
     // Handle jumping (if on the ground) and gravity, only if not flying
     if !player.is_flying {
-        if player.on_ground && is_action_pressed(GameAction::Jump, &keyboard_input, &key_map) {
+        let jump_pressed = is_action_pressed(GameAction::Jump, &keyboard_input, &key_map);
+        if body_in_liquid {
+            // Swim up while jump is pressed, otherwise slowly sink
+            if jump_pressed {
+                player.vertical_velocity = swim_velocity;
+            } else {
+                player.vertical_velocity = (player.vertical_velocity
+                    + GRAVITY * 0.2 * time.delta_seconds())
+                .max(-sink_velocity);
+            }
+        } else if feet_in_liquid && jump_pressed {
+            // At the surface, jumping allows getting out of the water onto the shore
+            player.vertical_velocity = jump_velocity;
+            player.on_ground = false;
+        } else if player.on_ground && jump_pressed {
             // Player can jump only when grounded
             player.vertical_velocity = jump_velocity;
             player.on_ground = false;
@@ -301,6 +348,7 @@ pub fn player_movement_system(
         ),
         &player,
         &world_map,
+        &blocks,
     ) {
         // Si un bloc est détecté sous le joueur, il reste sur le bloc
         player.on_ground = true;
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use crate::camera::CameraController;
+use crate::input::data::GameAction;
+use crate::input::keyboard::is_action_just_pressed;
+use crate::network::api::{send_network_action, NetworkAction};
+use crate::player::inventory::Inventory;
+use crate::ui::hud::hotbar::Hotbar;
+use crate::ui::hud::UIMode;
+use crate::world::MaterialResource;
+use crate::{GameState, KeyMap};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy_renet::renet::RenetClient;
+use shared::messages::{DroppedItemsUpdate, ItemPickedUp};
+use shared::world::{ItemId, ItemRegistry, ItemStack};
+
+use super::CurrentPlayerMarker;
+
+/// Side of the sprites of dropped items
+const ITEM_SIZE: f32 = 0.4;
+/// Radians per second dropped items turn by
+const ROTATION_SPEED: f32 = 1.5;
+/// Players pick up the items closer than this to the center of their body
+const PICKUP_DISTANCE: f32 = 1.5;
+/// Seconds before asking again for an item the server did not give
+const PICKUP_RETRY_DELAY: f32 = 1.;
+
+/// Stack of items lying in the world, simulated by the server
+#[derive(Component, Debug)]
+pub struct DroppedItemEntity {
+    id: u64,
+    stack: ItemStack,
+    /// Last position sent by the server, which the entity moves towards
+    target: Vec3,
+    pickable: bool,
+    /// When the item was last asked for
+    requested_at: Option<f32>,
+}
+
+/// Rendering of dropped items, and the most recent state received from the server
+#[derive(Resource, Debug, Default)]
+pub struct DroppedItems {
+    last_tick: Option<u64>,
+    material: Option<Handle<StandardMaterial>>,
+    meshes: HashMap<ItemId, Handle<Mesh>>,
+}
+
+/// Drops items at the feet of the player, such as the ones the inventory has no room for
+pub fn drop_items(client: &mut ResMut<RenetClient>, stack: ItemStack) {
+    if stack.nb > 0 {
+        send_network_action(
+            client,
+            NetworkAction::DropItem {
+                stack,
+                direction: Vec3::ZERO,
+            },
+        );
+    }
+}
+
+/// Square showing the texture of an item, from both sides
+fn item_mesh(material_resource: &MaterialResource, texture: &str) -> Option<Mesh> {
+    let uvs = material_resource.items.uvs.get(texture)?;
+    let half = ITEM_SIZE / 2.;
+    Some(
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [-half, -half, 0.],
+                [half, -half, 0.],
+                [half, half, 0.],
+                [-half, half, 0.],
+            ],
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; 4])
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![
+                [uvs.u0, uvs.v1],
+                [uvs.u1, uvs.v1],
+                [uvs.u1, uvs.v0],
+                [uvs.u0, uvs.v0],
+            ],
+        )
+        .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3])),
+    )
+}
+
+pub fn update_dropped_items(
+    mut commands: Commands,
+    mut entities: Query<(Entity, &mut DroppedItemEntity)>,
+    (mut dropped, mut meshes, mut materials, material_resource, items): (
+        ResMut<DroppedItems>,
+        ResMut<Assets<Mesh>>,
+        ResMut<Assets<StandardMaterial>>,
+        Res<MaterialResource>,
+        Res<ItemRegistry>,
+    ),
+    mut updates: EventReader<DroppedItemsUpdate>,
+) {
+    // Updates may arrive out of order : only the most recent one matters
+    let Some(update) = updates
+        .read()
+        .filter(|update| dropped.last_tick.is_none_or(|tick| update.tick > tick))
+        .max_by_key(|update| update.tick)
+    else {
+        return;
+    };
+    dropped.last_tick = Some(update.tick);
+
+    let mut received: HashMap<u64, _> = update.items.iter().map(|item| (item.id, item)).collect();
+    for (entity, mut item) in entities.iter_mut() {
+        match received.remove(&item.id) {
+            Some(state) => {
+                item.stack = state.stack;
+                item.target = state.position;
+                item.pickable = state.pickable;
+            }
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+
+    let material = dropped
+        .material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                base_color_texture: material_resource.items.texture.clone(),
+                alpha_mode: AlphaMode::Mask(0.5),
+                cull_mode: None,
+                double_sided: true,
+                perceptual_roughness: 1.,
+                reflectance: 0.,
+                ..default()
+            })
+        })
+        .clone();
+    for state in received.into_values() {
+        if !items.contains(state.stack.item_id) {
+            continue;
+        }
+        let texture = &items.get(state.stack.item_id).texture;
+        let mesh = match dropped.meshes.get(&state.stack.item_id) {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let Some(mesh) = item_mesh(&material_resource, texture) else {
+                    warn!("No texture {} for dropped items", texture);
+                    continue;
+                };
+                let mesh = meshes.add(mesh);
+                dropped.meshes.insert(state.stack.item_id, mesh.clone());
+                mesh
+            }
+        };
+
+        commands.spawn((
+            StateScoped(GameState::Game),
+            PbrBundle {
+                mesh,
+                material: material.clone(),
+                transform: Transform::from_translation(state.position),
+                ..default()
+            },
+            DroppedItemEntity {
+                id: state.id,
+                stack: state.stack,
+                target: state.position,
+                pickable: state.pickable,
+                requested_at: None,
+            },
+        ));
+    }
+}
+
+pub fn animate_dropped_items(
+    mut entities: Query<(&DroppedItemEntity, &mut Transform)>,
+    time: Res<Time>,
+) {
+    // Positions are received less often than frames are drawn : items move smoothly towards them
+    let smoothing = (time.delta_seconds() * 15.).min(1.);
+    for (item, mut transform) in entities.iter_mut() {
+        transform.translation = transform.translation.lerp(item.target, smoothing);
+        transform.rotate_y(time.delta_seconds() * ROTATION_SPEED);
+    }
+}
+
+pub fn pick_up_dropped_items(
+    player: Query<&Transform, With<CurrentPlayerMarker>>,
+    mut entities: Query<&mut DroppedItemEntity>,
+    (mut inventory, mut client, items, time): (
+        ResMut<Inventory>,
+        ResMut<RenetClient>,
+        Res<ItemRegistry>,
+        Res<Time>,
+    ),
+    mut picked_up: EventReader<ItemPickedUp>,
+) {
+    for event in picked_up.read() {
+        // The inventory may have filled up since the items were asked for
+        let nb = inventory.add_item_to_inventory(event.stack, &items);
+        drop_items(&mut client, ItemStack { nb, ..event.stack });
+    }
+
+    let Ok(player) = player.get_single() else {
+        return;
+    };
+    let now = time.elapsed_seconds();
+    for mut item in entities.iter_mut() {
+        let waiting = item
+            .requested_at
+            .is_some_and(|requested_at| now - requested_at < PICKUP_RETRY_DELAY);
+        if !item.pickable
+            || waiting
+            || item.target.distance(player.translation) > PICKUP_DISTANCE
+            || !items.contains(item.stack.item_id)
+            || !inventory.has_room_for(&item.stack, &items)
+        {
+            continue;
+        }
+        // The server decides who gets the items
+        item.requested_at = Some(now);
+        send_network_action(&mut client, NetworkAction::PickUpItem { id: item.id });
+    }
+}
+
+/// Throws the selected stack of the hotbar in front of the player
+pub fn handle_item_dropping(
+    camera: Query<&Transform, With<CameraController>>,
+    hotbar: Query<&Hotbar>,
+    (mut inventory, mut client, keyboard_input, key_map, ui_mode): (
+        ResMut<Inventory>,
+        ResMut<RenetClient>,
+        Res<ButtonInput<KeyCode>>,
+        Res<KeyMap>,
+        Res<UIMode>,
+    ),
+) {
+    if *ui_mode == UIMode::Opened
+        || !is_action_just_pressed(GameAction::DropItem, &keyboard_input, &key_map)
+    {
+        return;
+    }
+    let Some(stack) = inventory.inner.remove(&hotbar.single().selected) else {
+        return;
+    };
+    send_network_action(
+        &mut client,
+        NetworkAction::DropItem {
+            stack,
+            direction: *camera.single().forward(),
+        },
+    );
+}
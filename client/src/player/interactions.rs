@@ -5,14 +5,16 @@ use crate::network::api::NetworkAction;
 use crate::player::inventory::*;
 use crate::player::spawn::Player;
 use crate::ui::hud::hotbar::Hotbar;
-use crate::ui::hud::UIMode;
+use crate::ui::hud::{CraftingGrid, InventoryRoot, UIMode};
 use crate::world::ClientWorldMap;
 use crate::world::WorldRenderRequestUpdateEvent;
 use bevy::math::NormedVectorSpace;
 use bevy::prelude::*;
 use bevy_mod_raycast::prelude::*;
 use bevy_renet::renet::RenetClient;
-use shared::world::{BlockData, ItemStack, ItemType};
+use shared::world::{
+    BlockData, BlockDirection, BlockModel, BlockRegistry, BlockTags, ItemType, WorldHeight,
+};
 
 use super::CurrentPlayerMarker;
 
@@ -21,13 +23,30 @@ fn snap_to_grid(position: Vec3) -> Vec3 {
     Vec3::new(position.x.round(), position.y.round(), position.z.round())
 }
 
-// Function to handle block placement and breaking
+/// Block hit by a ray, including through the faces inside blocks such as the top of slabs
+pub fn targeted_block(intersection: &IntersectionData) -> IVec3 {
+    (intersection.position() - intersection.normal() * 0.01)
+        .floor()
+        .as_ivec3()
+}
+
+/// Whether a block placed there would leave the player stuck inside it
+fn is_inside_player(position: IVec3, player: &Player, player_position: Vec3) -> bool {
+    let distance = position.as_vec3() + Vec3::splat(CUBE_SIZE / 2.) - player_position;
+    distance.x.abs() <= (CUBE_SIZE + player.width) / 2.
+        && distance.z.abs() <= (CUBE_SIZE + player.width) / 2.
+        && distance.y.abs() <= (CUBE_SIZE + player.height) / 2.
+}
+
+// Function to handle block placement and crafting tables. Breaking is timed, see `handle_block_breaking`
 pub fn handle_block_interactions(
     queries: (
         Query<&Player, With<CurrentPlayerMarker>>,
         Query<&mut Transform, With<CurrentPlayerMarker>>,
         Query<&RaycastSource<BlockRaycastSet>>,
         Query<&Hotbar>,
+        Query<&mut Visibility, With<InventoryRoot>>,
+        Query<&Transform, (With<CameraController>, Without<CurrentPlayerMarker>)>,
     ),
     resources: (
         ResMut<ClientWorldMap>,
@@ -35,11 +54,21 @@ pub fn handle_block_interactions(
         Res<UIMode>,
         ResMut<Inventory>,
         ResMut<RenetClient>,
+        (Res<WorldHeight>, Res<BlockRegistry>),
+        ResMut<CraftingGrid>,
     ),
     mut ev_render: EventWriter<WorldRenderRequestUpdateEvent>,
 ) {
-    let (player_query, mut p_transform, raycast_source, hotbar) = queries;
-    let (mut world_map, mouse_input, ui_mode, mut inventory, mut client) = resources;
+    let (player_query, p_transform, raycast_source, hotbar, mut inventory_root, camera) = queries;
+    let (
+        mut world_map,
+        mouse_input,
+        ui_mode,
+        mut inventory,
+        mut client,
+        (world_height, blocks),
+        mut crafting,
+    ) = resources;
 
     let player = player_query.single().clone();
 
@@ -49,106 +78,121 @@ pub fn handle_block_interactions(
 
     let raycast_source = raycast_source.single();
 
-    // Handle left-click for breaking blocks
-    if mouse_input.just_pressed(MouseButton::Left) {
-        // Check if there are any intersections with a block
-        if let Some((_, intersection)) = raycast_source.intersections().first() {
-            // Check if block is close enough to the player
-            if (intersection.position() - p_transform.single_mut().translation).norm()
-                < INTERACTION_DISTANCE
-            {
-                let block_pos = intersection.position() - intersection.normal() * (CUBE_SIZE / 2.);
-                let global_block_coords = IVec3::new(
-                    block_pos.x.floor() as i32,
-                    block_pos.y.floor() as i32,
-                    block_pos.z.floor() as i32,
-                );
-
-                // Remove the hit block
-                let block = world_map.remove_block_by_coordinates(&global_block_coords);
-
-                if let Some(block) = block {
-                    // add the block to the player's inventory
-
-                    // If block has corresponding item, add it to inventory
-                    for (item_id, nb) in block.id.get_drops(1) {
-                        inventory.add_item_to_inventory(ItemStack {
-                            item_id,
-                            item_type: item_id.get_default_type(),
-                            nb,
-                        });
-                    }
-
-                    ev_render.send(WorldRenderRequestUpdateEvent::BlockToReload(
-                        global_block_coords,
-                    ));
-
-                    // Send the bloc to the serveur to delete it
-                    send_network_action(
-                        &mut client,
-                        NetworkAction::BlockInteraction {
-                            position: global_block_coords,
-                            block_type: None, // None signify suppression
-                        },
-                    );
-                }
-            }
-        }
+    // Handle right-click for placing blocks
+    if !mouse_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some((_entity, intersection)) = raycast_source.intersections().first() else {
+        return;
+    };
+    let player_position = p_transform.single().translation;
+    if (intersection.position() - player_position).norm() > INTERACTION_DISTANCE {
+        return;
+    }
+    let global_block_coords = targeted_block(intersection);
+    let target = world_map
+        .get_block_by_coordinates(&global_block_coords)
+        .copied();
+
+    // Crafting tables open their crafting grid instead of getting a block placed against them
+    if target.is_some_and(|block| blocks.get(block.id).has_tag(BlockTags::CraftingTable)) {
+        crafting.open_table(global_block_coords);
+        *inventory_root.single_mut() = Visibility::Visible;
+        return;
     }
 
-    // Handle right-click for placing blocks
-    if mouse_input.just_pressed(MouseButton::Right) {
-        if let Some((_entity, intersection)) = raycast_source.intersections().first() {
-            let block_pos = intersection.position() - intersection.normal() * (CUBE_SIZE / 2.);
-            let global_block_coords = IVec3::new(
-                block_pos.x.floor() as i32,
-                block_pos.y.floor() as i32,
-                block_pos.z.floor() as i32,
+    // Doors open and close, both of their halves at once
+    if let Some(door) = target.filter(|block| blocks.get(block.id).model == BlockModel::Door) {
+        let other_half = blocks
+            .get(door.id)
+            .other_half(&door, global_block_coords)
+            .filter(|other| {
+                world_map
+                    .get_block_by_coordinates(other)
+                    .is_some_and(|other| other.id == door.id)
+            });
+        for position in std::iter::once(global_block_coords).chain(other_half) {
+            let Some(&half) = world_map.get_block_by_coordinates(&position) else {
+                continue;
+            };
+            let block = BlockData {
+                open: !door.open,
+                ..half
+            };
+            world_map.set_block(&position, block);
+            ev_render.send(WorldRenderRequestUpdateEvent::BlockToReload(position));
+            send_network_action(
+                &mut client,
+                NetworkAction::BlockInteraction {
+                    position,
+                    block_type: Some(block),
+                },
             );
+        }
+        return;
+    }
 
-            // Get the normal of the face where the block will be placed
-            let normal = intersection.normal(); // This is already a Vec3, no need to unwrap
-                                                // Calculate the block position by adding a small offset to the intersection point
-            let mut position = global_block_coords.as_vec3() + normal * 0.51;
-            // Snap the position to the grid
-            position = snap_to_grid(position);
-
-            // Difference vector between player position and block center
-            let distance = position + (Vec3::new(CUBE_SIZE, CUBE_SIZE, CUBE_SIZE) / 2.)
-                - p_transform.single_mut().translation;
-
-            // Check if target space is close enough to the player
-            if (intersection.position() - p_transform.single_mut().translation).norm()
-                <= INTERACTION_DISTANCE
-                // Guarantees a block cannot be placed too close to the player (which would be unable to move because of constant collision)
-                && (distance.x.abs() > (CUBE_SIZE + player.width) / 2. || distance.z.abs() > (CUBE_SIZE + player.width ) / 2. || distance.y.abs() > (CUBE_SIZE + player.height) / 2.)
-            {
-                // Try to get item currently selected in player hotbar
-                if let Some(&item) = inventory.inner.get(&hotbar.single().selected) {
-                    inventory.remove_item_from_stack(hotbar.single().selected, 1);
-
-                    // Check if the item has a block counterpart
-                    if let ItemType::Block(block_id) = item.item_type {
-                        let block_pos =
-                            IVec3::new(position.x as i32, position.y as i32, position.z as i32);
-                        let block =
-                            BlockData::new(block_id, false, shared::world::BlockDirection::Front);
-
-                        world_map.set_block(&block_pos, block);
-
-                        ev_render.send(WorldRenderRequestUpdateEvent::BlockToReload(block_pos));
-
-                        // Send to server the bloc to add
-                        send_network_action(
-                            &mut client,
-                            NetworkAction::BlockInteraction {
-                                position: block_pos,
-                                block_type: Some(block), // Some signify adding
-                            },
-                        );
-                    }
-                }
-            }
+    // Try to get item currently selected in player hotbar, if it has a block counterpart
+    let slot = hotbar.single().selected;
+    let Some(ItemType::Block(block_id)) = inventory.inner.get(&slot).map(|item| item.item_type)
+    else {
+        return;
+    };
+
+    // Get the normal of the face where the block will be placed
+    let normal = intersection.normal();
+    let block_pos = snap_to_grid(global_block_coords.as_vec3() + normal * 0.51).as_ivec3();
+
+    // Blocks face the player, and slabs and stairs go in the upper half of the block
+    // when placed against a ceiling or the upper half of a side
+    let definition = blocks.get(block_id);
+    let direction = BlockDirection::facing(*camera.single().forward());
+    let hit_height = intersection.position().y - intersection.position().y.floor();
+    let flipped = match definition.model {
+        BlockModel::Slab | BlockModel::Stairs => {
+            normal.y < -0.5 || (normal.y.abs() < 0.5 && hit_height > 0.5)
         }
+        _ => false,
+    };
+    let block = BlockData::new(block_id, flipped, direction);
+
+    // Doors are two blocks high, their upper half being flipped
+    let mut placed = vec![(block_pos, block)];
+    if definition.model == BlockModel::Door {
+        placed.push((
+            block_pos + IVec3::Y,
+            BlockData::new(block_id, true, direction),
+        ));
+    }
+
+    let can_place = placed.iter().all(|(position, _)| {
+        // Blocks cannot be placed above or below the limits of the world
+        world_height.contains(position.y)
+            // Nor replace anything but liquids
+            && world_map
+                .get_block_by_coordinates(position)
+                .is_none_or(|block| blocks.get(block.id).is_liquid())
+            // Guarantees a block cannot be placed too close to the player (which would be unable to move because of constant collision)
+            && !is_inside_player(*position, &player, player_position)
+    });
+    if !can_place {
+        return;
+    }
+
+    // Items are only used up by the blocks actually placed
+    inventory.remove_item_from_stack(slot, 1);
+    for (position, block) in placed {
+        world_map.set_block(&position, block);
+
+        ev_render.send(WorldRenderRequestUpdateEvent::BlockToReload(position));
+
+        // Send to server the bloc to add
+        send_network_action(
+            &mut client,
+            NetworkAction::BlockInteraction {
+                position,
+                block_type: Some(block), // Some signify adding
+            },
+        );
     }
 }
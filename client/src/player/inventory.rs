@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::constants::MAX_INVENTORY_SLOTS;
 use bevy::prelude::*;
-use shared::world::{ItemId, ItemStack, ItemType};
+use shared::world::{ItemId, ItemRegistry, ItemStack, ItemType};
 
 #[derive(Debug, Resource, Clone)]
 pub struct Inventory {
@@ -16,17 +16,17 @@ impl Inventory {
         }
     }
 
-    // Ajoute un item à l'inventaire du joueur
-    pub fn add_item_to_inventory(&mut self, mut stack: ItemStack) {
+    /// Ajoute un item à l'inventaire du joueur\
+    /// Returns number of items which did not fit in the inventory
+    pub fn add_item_to_inventory(&mut self, mut stack: ItemStack, items: &ItemRegistry) -> u32 {
+        let max_stack = items.get(stack.item_id).max_stack;
         for i in 0..MAX_INVENTORY_SLOTS {
             let item_option = self.inner.get(&i);
 
             if item_option.is_some() {
                 let existing_item = item_option.expect("Error : empty item");
                 // If not item of right type or stack already full : pass
-                if existing_item.item_id != stack.item_id
-                    || existing_item.nb >= stack.item_id.get_max_stack()
-                {
+                if existing_item.item_id != stack.item_id || existing_item.nb >= max_stack {
                     continue;
                 }
 
@@ -36,8 +36,8 @@ impl Inventory {
             let inserted_stack = ItemStack {
                 item_id: stack.item_id,
                 item_type: stack.item_type,
-                nb: if stack.nb >= stack.item_id.get_max_stack() {
-                    stack.item_id.get_max_stack()
+                nb: if stack.nb >= max_stack {
+                    max_stack
                 } else {
                     stack.nb
                 },
@@ -53,11 +53,21 @@ impl Inventory {
             }
         }
 
-        // Problem : if inventory full, items disappear
+        stack.nb
+    }
+
+    /// Whether at least one item of a stack fits in the inventory
+    pub fn has_room_for(&self, stack: &ItemStack, items: &ItemRegistry) -> bool {
+        let max_stack = items.get(stack.item_id).max_stack;
+        (0..MAX_INVENTORY_SLOTS).any(|i| {
+            self.inner
+                .get(&i)
+                .is_none_or(|existing| existing.item_id == stack.item_id && existing.nb < max_stack)
+        })
     }
 
     /// Add items to stack at specified position\
-    /// Stacks cannot exceed MAX_ITEM_STACK number of items\
+    /// Stacks cannot exceed the max stack of their item\
     /// Returns number of items really added to the stack
     pub fn add_item_to_stack(
         &mut self,
@@ -65,6 +75,7 @@ impl Inventory {
         mut nb: u32,
         id: ItemId,
         item_type: ItemType,
+        items: &ItemRegistry,
     ) -> u32 {
         let item_option = self.inner.get(&stack);
         let mut new_item = ItemStack {
@@ -74,8 +85,9 @@ impl Inventory {
         };
 
         if let Some(item) = item_option {
-            if nb + item.nb > item.item_id.get_max_stack() {
-                nb = item.item_id.get_max_stack() - item.nb;
+            let max_stack = items.get(item.item_id).max_stack;
+            if nb + item.nb > max_stack {
+                nb = max_stack - item.nb;
             }
             new_item.nb = nb + item.nb;
         }
@@ -107,6 +119,19 @@ impl Inventory {
         }
         0
     }
+
+    /// Uses the tool at specified position once\
+    /// The tool is removed once it has no durability left
+    pub fn wear_tool(&mut self, stack: u32) {
+        if let Some(item) = self.inner.get_mut(&stack) {
+            if let ItemType::Tool { durability } = &mut item.item_type {
+                *durability = durability.saturating_sub(1);
+                if *durability == 0 {
+                    self.inner.remove(&stack);
+                }
+            }
+        }
+    }
 }
 
 // ! ------- UNUSED CODE ------------
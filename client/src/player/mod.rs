@@ -1,8 +1,12 @@
+mod breaking;
 mod controller;
+mod dropped_items;
 mod interactions;
 pub(crate) mod inventory;
 mod spawn;
 
+pub use breaking::*;
 pub use controller::*;
+pub use dropped_items::*;
 pub use interactions::*;
 pub use spawn::*;
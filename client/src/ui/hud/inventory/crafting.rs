@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use bevy_renet::renet::RenetClient;
+use shared::messages::{CraftRequest, CraftResponse};
+use shared::world::{
+    ItemId, ItemRegistry, ItemStack, RecipeRegistry, INVENTORY_CRAFTING_SIZE, TABLE_CRAFTING_SIZE,
+};
+
+use super::{add_item_floating_stack, update_inventory_cell};
+use crate::constants::CRAFTING_SLOTS_START;
+use crate::network::api::{send_network_action, NetworkAction};
+use crate::player::drop_items;
+use crate::player::inventory::Inventory;
+use crate::ui::hud::{FloatingStack, InventoryCell, InventoryRoot};
+use crate::world::MaterialResource;
+
+/// Cell showing what the crafting grid makes, taken by clicking it
+#[derive(Component)]
+pub struct CraftingResultCell;
+
+/// Crafting grid of the inventory dialog : the one of the inventory, or the larger one
+/// of the crafting table it was opened from
+#[derive(Resource, Debug)]
+pub struct CraftingGrid {
+    pub size: usize,
+    pub table: Option<IVec3>,
+    /// A craft was sent to the server, which has not answered yet
+    pending: bool,
+}
+
+impl Default for CraftingGrid {
+    fn default() -> Self {
+        CraftingGrid {
+            size: INVENTORY_CRAFTING_SIZE,
+            table: None,
+            pending: false,
+        }
+    }
+}
+
+impl CraftingGrid {
+    /// Uses the grid of a crafting table until the inventory is closed
+    pub fn open_table(&mut self, table: IVec3) {
+        self.size = TABLE_CRAFTING_SIZE;
+        self.table = Some(table);
+    }
+
+    /// Inventory slots of the grid, row by row
+    fn slots(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.size).flat_map(move |y| {
+            (0..self.size).map(move |x| CRAFTING_SLOTS_START + (y * TABLE_CRAFTING_SIZE + x) as u32)
+        })
+    }
+
+    fn items(&self, inventory: &Inventory) -> Vec<Option<ItemId>> {
+        self.slots()
+            .map(|slot| inventory.inner.get(&slot).map(|stack| stack.item_id))
+            .collect()
+    }
+}
+
+fn is_crafting_slot(slot: u32) -> bool {
+    (CRAFTING_SLOTS_START
+        ..CRAFTING_SLOTS_START + (TABLE_CRAFTING_SIZE * TABLE_CRAFTING_SIZE) as u32)
+        .contains(&slot)
+}
+
+pub fn update_crafting(
+    (
+        root_query,
+        mut cell_query,
+        result_query,
+        mut floating_stack_query,
+        mut text_query,
+        mut atlas_query,
+    ): (
+        Query<&Visibility, With<InventoryRoot>>,
+        Query<(&InventoryCell, &mut Style)>,
+        Query<(&Interaction, &Children), With<CraftingResultCell>>,
+        Query<&mut FloatingStack>,
+        Query<&mut Text>,
+        Query<(&mut TextureAtlas, &mut Visibility), Without<InventoryRoot>>,
+    ),
+    (mut crafting, mut inventory, mut client, recipes, items, materials, mouse_input): (
+        ResMut<CraftingGrid>,
+        ResMut<Inventory>,
+        ResMut<RenetClient>,
+        Res<RecipeRegistry>,
+        Res<ItemRegistry>,
+        Res<MaterialResource>,
+        Res<ButtonInput<MouseButton>>,
+    ),
+    mut responses: EventReader<CraftResponse>,
+) {
+    let mut floating_stack = floating_stack_query.single_mut();
+
+    for response in responses.read() {
+        crafting.pending = false;
+        let Some(result) = &response.result else {
+            continue;
+        };
+        // The grid may have changed while waiting for the server
+        if response.grid != crafting.items(&inventory) {
+            continue;
+        }
+        for slot in crafting.slots().collect::<Vec<_>>() {
+            inventory.remove_item_from_stack(slot, 1);
+        }
+        let item_type = items.get(result.item).default_type();
+        let added = add_item_floating_stack(
+            &mut floating_stack,
+            result.count,
+            result.item,
+            item_type,
+            &items,
+        );
+        if added < result.count {
+            let stack = ItemStack {
+                item_id: result.item,
+                item_type,
+                nb: result.count - added,
+            };
+            let nb = inventory.add_item_to_inventory(stack, &items);
+            drop_items(&mut client, ItemStack { nb, ..stack });
+        }
+    }
+
+    // Items left in the grid go back to the inventory once it is closed, or fall when it is full
+    if *root_query.single() != Visibility::Visible {
+        let slots: Vec<u32> = inventory
+            .inner
+            .keys()
+            .copied()
+            .filter(|slot| is_crafting_slot(*slot))
+            .collect();
+        for slot in slots {
+            if let Some(stack) = inventory.inner.remove(&slot) {
+                let nb = inventory.add_item_to_inventory(stack, &items);
+                drop_items(&mut client, ItemStack { nb, ..stack });
+            }
+        }
+        *crafting = CraftingGrid::default();
+        return;
+    }
+
+    for (cell, mut style) in cell_query.iter_mut() {
+        if !is_crafting_slot(cell.id) {
+            continue;
+        }
+        let index = (cell.id - CRAFTING_SLOTS_START) as usize;
+        let in_grid = index % TABLE_CRAFTING_SIZE < crafting.size
+            && index / TABLE_CRAFTING_SIZE < crafting.size;
+        style.display = if in_grid {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    let grid = crafting.items(&inventory);
+    let result = recipes
+        .find(crafting.size, &grid)
+        .map(|recipe| recipe.result())
+        .map(|result| ItemStack {
+            item_id: result.item,
+            item_type: items.get(result.item).default_type(),
+            nb: result.count,
+        });
+
+    let (interaction, children) = result_query.single();
+    let mut txt = text_query.get_mut(children[0]).unwrap();
+    let (mut stack_atlas, mut stack_vis) = atlas_query.get_mut(children[1]).unwrap();
+    update_inventory_cell(
+        &result,
+        &mut txt,
+        &mut stack_vis,
+        &mut stack_atlas,
+        &materials,
+        &items,
+    );
+
+    let Some(result) = result else {
+        return;
+    };
+    if *interaction != Interaction::Pressed
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || crafting.pending
+    {
+        return;
+    }
+    // The result is taken with the floating stack, which must be able to hold it
+    if let Some(stack) = floating_stack.items {
+        if stack.item_id != result.item_id
+            || stack.nb + result.nb > items.get(stack.item_id).max_stack
+        {
+            return;
+        }
+    }
+
+    crafting.pending = true;
+    send_network_action(
+        &mut client,
+        NetworkAction::CraftRequest(CraftRequest {
+            size: crafting.size,
+            grid,
+            table: crafting.table,
+        }),
+    );
+}
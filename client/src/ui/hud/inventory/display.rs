@@ -19,7 +19,7 @@ use bevy::prelude::{
 use bevy::sprite::TextureAtlas;
 use bevy::ui::{BorderColor, Interaction};
 use bevy::window::PrimaryWindow;
-use shared::world::{ItemId, ItemStack};
+use shared::world::{ItemRegistry, ItemStack, ItemType};
 
 pub fn render_inventory_hotbar(
     (
@@ -39,12 +39,13 @@ pub fn render_inventory_hotbar(
         Query<&Window, With<PrimaryWindow>>,
         Query<&mut Hotbar>,
     ),
-    (keyboard_input, mouse_input, key_map, mut inventory, materials): (
+    (keyboard_input, mouse_input, key_map, mut inventory, materials, items): (
         Res<ButtonInput<KeyCode>>,
         Res<ButtonInput<MouseButton>>,
         Res<KeyMap>,
         ResMut<Inventory>,
         Res<MaterialResource>,
+        Res<ItemRegistry>,
     ),
     mut scroll: EventReader<MouseWheel>,
 ) {
@@ -58,23 +59,19 @@ pub fn render_inventory_hotbar(
 
     if is_action_just_pressed(GameAction::DebugGetBlock, &keyboard_input, &key_map) {
         debug!("Blocks given to user");
-        inventory.add_item_to_inventory(ItemStack {
-            item_id: ItemId::Glass,
-            item_type: ItemId::Glass.get_default_type(),
-            nb: 64,
-        });
-
-        inventory.add_item_to_inventory(ItemStack {
-            item_id: ItemId::Poppy,
-            item_type: ItemId::Poppy.get_default_type(),
-            nb: 64,
-        });
-
-        inventory.add_item_to_inventory(ItemStack {
-            item_id: ItemId::Dandelion,
-            item_type: ItemId::Dandelion.get_default_type(),
-            nb: 64,
-        });
+        for name in ["glass", "poppy", "dandelion"] {
+            let Some(item_id) = items.id(name) else {
+                continue;
+            };
+            inventory.add_item_to_inventory(
+                ItemStack {
+                    item_id,
+                    item_type: items.get(item_id).default_type(),
+                    nb: 64,
+                },
+                &items,
+            );
+        }
     }
 
     let (mut style, mut floating_stack, children) = floating_stack_query.single_mut();
@@ -103,6 +100,7 @@ pub fn render_inventory_hotbar(
         &mut stack_vis,
         &mut stack_atlas,
         &materials,
+        &items,
     );
 
     if let Some(c_pos) = window_query.single().cursor_position() {
@@ -126,6 +124,7 @@ pub fn render_inventory_hotbar(
             &mut stack_vis,
             &mut stack_atlas,
             &materials,
+            &items,
         );
 
         // Show selected stack in hotbar
@@ -154,7 +153,7 @@ pub fn render_inventory_hotbar(
             if stack_exists
                 && floating_exists
                 && stack.unwrap().item_id == floating_items.unwrap().item_id
-                && stack.unwrap().nb < stack.unwrap().item_id.get_max_stack()
+                && stack.unwrap().nb < items.get(stack.unwrap().item_id).max_stack
             {
                 let stack = stack.unwrap();
                 let floating_items = floating_items.unwrap();
@@ -162,10 +161,11 @@ pub fn render_inventory_hotbar(
                     cell.id,
                     remove_item_floating_stack(
                         &mut floating_stack,
-                        stack.item_id.get_max_stack() - stack.nb,
+                        items.get(stack.item_id).max_stack - stack.nb,
                     ),
                     floating_items.item_id,
                     stack.item_type,
+                    &items,
                 );
             } else {
                 if stack_exists {
@@ -207,6 +207,7 @@ pub fn render_inventory_hotbar(
                                 1,
                                 floating_items.item_id,
                                 floating_items.item_type,
+                                &items,
                             ),
                         );
                     }
@@ -219,6 +220,7 @@ pub fn render_inventory_hotbar(
                             1,
                             floating_items.item_id,
                             floating_items.item_type,
+                            &items,
                         ),
                     );
                 }
@@ -233,6 +235,7 @@ pub fn render_inventory_hotbar(
                     inventory.remove_item_from_stack(cell.id, nb),
                     stack.item_id,
                     stack.item_type,
+                    &items,
                 );
             }
         } else {
@@ -247,14 +250,19 @@ pub fn update_inventory_cell(
     visibility: &mut Visibility,
     atlas: &mut TextureAtlas,
     materials: &MaterialResource,
+    items: &ItemRegistry,
 ) {
     // Set content
     if let Some(fstack) = stack {
-        txt.sections[0].value = format!("{:?}", fstack.nb);
+        // Tools do not stack : their remaining uses are shown instead
+        txt.sections[0].value = match fstack.item_type {
+            ItemType::Tool { durability } => format!("{}", durability),
+            _ => format!("{:?}", fstack.nb),
+        };
         atlas.index = (materials
             .items
             .uvs
-            .get(&format!("{:?}", fstack.item_id))
+            .get(&items.get(fstack.item_id).texture)
             .unwrap()
             .u0
             * materials.items.uvs.len() as f32) as usize;
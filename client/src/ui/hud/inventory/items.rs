@@ -1,4 +1,4 @@
-use shared::world::{ItemId, ItemStack, ItemType};
+use shared::world::{ItemId, ItemRegistry, ItemStack, ItemType};
 
 use crate::ui::hud::inventory::FloatingStack;
 
@@ -19,7 +19,7 @@ pub fn remove_item_floating_stack(floating_stack: &mut FloatingStack, nb: u32) -
 }
 
 /// Adds `nb` items to the floating stack\
-/// Cannot go higher than `MAX_ITEM_STACK` items\
+/// Cannot go higher than the max stack of the item\
 /// Parameter `item_type` will **ONLY BE USED** if no items are present in the floating stack\
 /// Returns number of items _actually_ added
 pub fn add_item_floating_stack(
@@ -27,18 +27,22 @@ pub fn add_item_floating_stack(
     mut nb: u32,
     item_id: ItemId,
     item_type: ItemType,
+    items: &ItemRegistry,
 ) -> u32 {
     if nb == 0 {
         0
     } else if let Some(mut item) = floating_stack.items {
-        if nb + item.nb > item.item_id.get_max_stack() {
-            nb = item.item_id.get_max_stack() - item.nb;
+        let max_stack = items.get(item.item_id).max_stack;
+        if nb + item.nb > max_stack {
+            nb = max_stack - item.nb;
         }
         item.nb += nb;
+        floating_stack.items = Some(item);
         nb
     } else {
-        if nb > item_id.get_max_stack() {
-            nb = item_id.get_max_stack();
+        let max_stack = items.get(item_id).max_stack;
+        if nb > max_stack {
+            nb = max_stack;
         }
         floating_stack.items = Some(ItemStack {
             item_id,
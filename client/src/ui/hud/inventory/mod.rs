@@ -43,10 +43,12 @@ pub fn set_ui_mode(mut ui_mode: ResMut<UIMode>, visibility: Query<&Visibility, W
     *ui_mode = UIMode::Closed;
 }
 
+mod crafting;
 mod display;
 pub mod items;
 mod setup;
 
+pub use crafting::*;
 pub use display::*;
 use items::*;
 pub use setup::*;
@@ -1,12 +1,15 @@
 use super::UiDialog;
 use crate::constants::{
-    HOTBAR_BORDER, HOTBAR_CELL_SIZE, HOTBAR_PADDING, MAX_HOTBAR_SLOTS, MAX_INVENTORY_SLOTS,
-    TEXTURE_SIZE,
+    CRAFTING_SLOTS_START, HOTBAR_BORDER, HOTBAR_CELL_SIZE, HOTBAR_PADDING, MAX_HOTBAR_SLOTS,
+    MAX_INVENTORY_SLOTS, TEXTURE_SIZE,
+};
+use crate::ui::hud::{
+    CraftingResultCell, FloatingStack, InventoryCell, InventoryDialog, InventoryRoot,
 };
-use crate::ui::hud::{FloatingStack, InventoryCell, InventoryDialog, InventoryRoot};
 use crate::world::MaterialResource;
 use crate::GameState;
 use bevy::{prelude::*, ui::FocusPolicy};
+use shared::world::TABLE_CRAFTING_SIZE;
 
 pub fn setup_inventory(
     mut commands: Commands,
@@ -104,62 +107,56 @@ pub fn setup_inventory(
         })
         .with_children(|builder| {
             for i in MAX_HOTBAR_SLOTS..MAX_INVENTORY_SLOTS {
-                builder
-                    .spawn((
-                        InventoryCell { id: i },
-                        ButtonBundle {
-                            border_color: BorderColor(Color::srgb(0.3, 0.3, 0.3)),
-                            focus_policy: FocusPolicy::Block,
-                            style: Style {
-                                width: Val::Px(HOTBAR_CELL_SIZE),
-                                height: Val::Px(HOTBAR_CELL_SIZE),
-                                margin: UiRect::ZERO,
-                                position_type: PositionType::Relative,
-                                padding: UiRect::all(Val::Px(HOTBAR_PADDING)),
-                                border: UiRect::all(Val::Px(HOTBAR_BORDER)),
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        },
-                    ))
-                    .with_children(|btn| {
-                        btn.spawn(TextBundle {
-                            text: Text::from_section(
-                                "Test",
-                                TextStyle {
-                                    font_size: 15.,
-                                    ..Default::default()
-                                },
-                            ),
-                            style: Style {
-                                position_type: PositionType::Absolute,
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        });
-                        btn.spawn((
-                            ImageBundle {
-                                z_index: ZIndex::Local(-1),
-                                style: Style {
-                                    width: Val::Px(
-                                        HOTBAR_CELL_SIZE - 2. * (HOTBAR_PADDING + HOTBAR_BORDER),
-                                    ),
-                                    position_type: PositionType::Relative,
-                                    ..Default::default()
-                                },
-                                image: UiImage {
-                                    texture: img.clone_weak(),
-                                    ..default()
-                                },
-                                ..Default::default()
-                            },
-                            atlas.clone(),
-                        ));
-                    });
+                spawn_cell(builder, InventoryCell { id: i }, &img, &atlas);
             }
         })
         .id();
 
+    // Crafting grid and its result. Only the slots of the current grid size are displayed
+    let crafting_area = commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(20.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_children(|builder| {
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Grid,
+                        grid_template_columns: RepeatedGridTrack::auto(TABLE_CRAFTING_SIZE as u16),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|grid| {
+                    for i in 0..(TABLE_CRAFTING_SIZE * TABLE_CRAFTING_SIZE) as u32 {
+                        spawn_cell(
+                            grid,
+                            InventoryCell {
+                                id: CRAFTING_SLOTS_START + i,
+                            },
+                            &img,
+                            &atlas,
+                        );
+                    }
+                });
+            builder.spawn(TextBundle::from_section(
+                "->",
+                TextStyle {
+                    font_size: 24.,
+                    ..Default::default()
+                },
+            ));
+            spawn_cell(builder, CraftingResultCell, &img, &atlas);
+        })
+        .id();
+
     let floating_stack = commands
         .spawn((
             FloatingStack { items: None },
@@ -206,9 +203,68 @@ pub fn setup_inventory(
 
     commands
         .entity(dialog)
-        .push_children(&[inventory_title, inventory_grid]);
+        .push_children(&[inventory_title, crafting_area, inventory_grid]);
 
     commands
         .entity(root)
         .push_children(&[dialog, floating_stack]);
 }
+
+/// Spawns an item slot of the inventory dialog, with the text and image showing its stack
+fn spawn_cell(
+    builder: &mut ChildBuilder,
+    cell: impl Component,
+    img: &Handle<Image>,
+    atlas: &TextureAtlas,
+) {
+    builder
+        .spawn((
+            cell,
+            ButtonBundle {
+                border_color: BorderColor(Color::srgb(0.3, 0.3, 0.3)),
+                focus_policy: FocusPolicy::Block,
+                style: Style {
+                    width: Val::Px(HOTBAR_CELL_SIZE),
+                    height: Val::Px(HOTBAR_CELL_SIZE),
+                    margin: UiRect::ZERO,
+                    position_type: PositionType::Relative,
+                    padding: UiRect::all(Val::Px(HOTBAR_PADDING)),
+                    border: UiRect::all(Val::Px(HOTBAR_BORDER)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|btn| {
+            btn.spawn(TextBundle {
+                text: Text::from_section(
+                    "Test",
+                    TextStyle {
+                        font_size: 15.,
+                        ..Default::default()
+                    },
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            btn.spawn((
+                ImageBundle {
+                    z_index: ZIndex::Local(-1),
+                    style: Style {
+                        width: Val::Px(HOTBAR_CELL_SIZE - 2. * (HOTBAR_PADDING + HOTBAR_BORDER)),
+                        position_type: PositionType::Relative,
+                        ..Default::default()
+                    },
+                    image: UiImage {
+                        texture: img.clone_weak(),
+                        ..default()
+                    },
+                    ..Default::default()
+                },
+                atlas.clone(),
+            ));
+        });
+}
@@ -25,7 +25,7 @@ use bevy_simple_text_input::{
     TextInputBundle, TextInputInactive, TextInputPlaceholder, TextInputSettings,
     TextInputTextStyle, TextInputValue,
 };
-use shared::world::get_game_folder;
+use shared::world::{get_game_folder, BiomeRegistry, GeneratorSettings, WorldPreset};
 use shared::GameFolderPaths;
 use std::io;
 use std::{
@@ -35,6 +35,10 @@ use std::{
 
 pub struct WorldItem {
     pub name: String,
+    /// Only used if the world does not exist yet
+    pub preset: WorldPreset,
+    /// Only used if the world does not exist yet
+    pub settings: GeneratorSettings,
 }
 
 #[derive(Component, Default)]
@@ -52,9 +56,132 @@ pub enum MultiplayerButtonAction {
 #[derive(Component)]
 pub struct WorldNameInput;
 
+/// Button choosing the preset of the next created world, cycling through them when clicked
+#[derive(Component, Default)]
+pub struct WorldPresetSelector {
+    /// Index in `WorldPreset::all`
+    pub index: usize,
+}
+
+/// Button showing or hiding the advanced settings of the next created world
+#[derive(Component)]
+pub struct AdvancedSettingsToggle;
+
+/// Node holding the generator setting selectors, hidden by default
+#[derive(Component)]
+pub struct AdvancedSettingsPanel;
+
+/// Generator settings that can be changed in the advanced settings of the solo menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorSetting {
+    TerrainScale,
+    BiomeSize,
+    SeaLevel,
+    Flora,
+    Boulders,
+    Caves,
+    Ores,
+    Structures,
+}
+
+impl GeneratorSetting {
+    pub const ALL: [GeneratorSetting; 8] = [
+        GeneratorSetting::TerrainScale,
+        GeneratorSetting::BiomeSize,
+        GeneratorSetting::SeaLevel,
+        GeneratorSetting::Flora,
+        GeneratorSetting::Boulders,
+        GeneratorSetting::Caves,
+        GeneratorSetting::Ores,
+        GeneratorSetting::Structures,
+    ];
+
+    fn name(&self) -> &'static str {
+        match *self {
+            GeneratorSetting::TerrainScale => "Terrain scale",
+            GeneratorSetting::BiomeSize => "Biome size",
+            GeneratorSetting::SeaLevel => "Sea level",
+            GeneratorSetting::Flora => "Flora",
+            GeneratorSetting::Boulders => "Boulders",
+            GeneratorSetting::Caves => "Caves",
+            GeneratorSetting::Ores => "Ores",
+            GeneratorSetting::Structures => "Structures",
+        }
+    }
+
+    /// Values the selector cycles through, which include the default one
+    fn values(&self) -> &'static [f64] {
+        match *self {
+            GeneratorSetting::TerrainScale => &[0.05, 0.1, 0.2],
+            GeneratorSetting::BiomeSize => &[0.5, 1.0, 2.0, 4.0],
+            GeneratorSetting::SeaLevel => &[40.0, 52.0, 62.0, 72.0],
+            _ => &[0.0, 0.5, 1.0, 2.0],
+        }
+    }
+
+    fn get(&self, settings: &GeneratorSettings) -> f64 {
+        match *self {
+            GeneratorSetting::TerrainScale => settings.terrain_scale,
+            // biomes get wider as their scale gets lower
+            GeneratorSetting::BiomeSize => {
+                GeneratorSettings::default().biome_scale / settings.biome_scale
+            }
+            GeneratorSetting::SeaLevel => settings.sea_level as f64,
+            GeneratorSetting::Flora => settings.flora_density as f64,
+            GeneratorSetting::Boulders => settings.boulder_density as f64,
+            GeneratorSetting::Caves => settings.cave_frequency,
+            GeneratorSetting::Ores => settings.ore_frequency as f64,
+            GeneratorSetting::Structures => settings.structure_chance as f64,
+        }
+    }
+
+    fn set(&self, settings: &mut GeneratorSettings, value: f64) {
+        match *self {
+            GeneratorSetting::TerrainScale => settings.terrain_scale = value,
+            GeneratorSetting::BiomeSize => {
+                settings.biome_scale = GeneratorSettings::default().biome_scale / value
+            }
+            GeneratorSetting::SeaLevel => settings.sea_level = value as i32,
+            GeneratorSetting::Flora => settings.flora_density = value as f32,
+            GeneratorSetting::Boulders => settings.boulder_density = value as f32,
+            GeneratorSetting::Caves => settings.cave_frequency = value,
+            GeneratorSetting::Ores => settings.ore_frequency = value as f32,
+            GeneratorSetting::Structures => settings.structure_chance = value as f32,
+        }
+    }
+
+    fn default_index(&self) -> usize {
+        let default = self.get(&GeneratorSettings::default());
+        self.values()
+            .iter()
+            .position(|value| *value == default)
+            .unwrap_or(0)
+    }
+
+    fn label(&self, index: usize) -> String {
+        let value = self.values()[index];
+        match *self {
+            GeneratorSetting::TerrainScale | GeneratorSetting::SeaLevel => {
+                format!("{}: {}", self.name(), value)
+            }
+            _ => format!("{}: x{}", self.name(), value),
+        }
+    }
+}
+
+/// Button choosing the value of a generator setting, cycling through them when clicked
+#[derive(Component)]
+pub struct GeneratorSettingSelector {
+    pub setting: GeneratorSetting,
+    /// Index in `GeneratorSetting::values`
+    pub index: usize,
+}
+
 #[derive(Resource, Default, Debug, Clone)]
 pub struct SelectedWorld {
     pub name: Option<String>,
+    pub preset: WorldPreset,
+    pub settings: GeneratorSettings,
 }
 
 pub fn solo_menu_setup(
@@ -188,6 +315,97 @@ pub fn solo_menu_setup(
                     },
                 ));
 
+                wrapper
+                    .spawn((
+                        ButtonBundle {
+                            border_color: BorderColor(Color::BLACK),
+                            background_color: BackgroundColor(BACKGROUND_COLOR),
+                            style: {
+                                let mut style = btn_style.clone();
+                                style.grid_column = GridPlacement::span(2);
+                                style
+                            },
+                            ..Default::default()
+                        },
+                        WorldPresetSelector::default(),
+                    ))
+                    .with_children(|btn| {
+                        btn.spawn(TextBundle {
+                            text: Text::from_section(
+                                preset_label(&WorldPreset::default()),
+                                txt_style.clone(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+
+                wrapper
+                    .spawn((
+                        ButtonBundle {
+                            border_color: BorderColor(Color::BLACK),
+                            background_color: BackgroundColor(BACKGROUND_COLOR),
+                            style: {
+                                let mut style = btn_style.clone();
+                                style.grid_column = GridPlacement::span(2);
+                                style
+                            },
+                            ..Default::default()
+                        },
+                        AdvancedSettingsToggle,
+                    ))
+                    .with_children(|btn| {
+                        btn.spawn(TextBundle {
+                            text: Text::from_section("Advanced settings", txt_style.clone()),
+                            ..Default::default()
+                        });
+                    });
+
+                wrapper
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                display: Display::None,
+                                grid_column: GridPlacement::span(2),
+                                grid_template_columns: vec![
+                                    GridTrack::flex(1.),
+                                    GridTrack::flex(1.),
+                                ],
+                                row_gap: Val::Px(5.),
+                                column_gap: Val::Px(5.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        AdvancedSettingsPanel,
+                    ))
+                    .with_children(|panel| {
+                        for setting in GeneratorSetting::ALL {
+                            let index = setting.default_index();
+                            panel
+                                .spawn((
+                                    ButtonBundle {
+                                        border_color: BorderColor(Color::BLACK),
+                                        background_color: BackgroundColor(BACKGROUND_COLOR),
+                                        style: Style {
+                                            height: Val::Px(35.0),
+                                            ..btn_style.clone()
+                                        },
+                                        ..Default::default()
+                                    },
+                                    GeneratorSettingSelector { setting, index },
+                                ))
+                                .with_children(|btn| {
+                                    btn.spawn(TextBundle {
+                                        text: Text::from_section(
+                                            setting.label(index),
+                                            txt_style.clone(),
+                                        ),
+                                        ..Default::default()
+                                    });
+                                });
+                        }
+                    });
+
                 wrapper
                     .spawn((
                         ButtonBundle {
@@ -254,9 +472,15 @@ pub fn list_worlds(
     for path in paths {
         let path_str = path.unwrap().file_name().into_string().unwrap();
 
-        if path_str.ends_with(".ron") {
+        // Worlds can be stored as RON or binary files
+        if let Some(world_name) = path_str
+            .strip_suffix(".ron")
+            .or_else(|| path_str.strip_suffix(".bin"))
+        {
             add_world_item(
-                path_str.replace(".ron", ""),
+                world_name.to_string(),
+                WorldPreset::default(),
+                GeneratorSettings::default(),
                 &mut commands,
                 &assets,
                 &mut list,
@@ -270,6 +494,8 @@ pub fn list_worlds(
 
 fn add_world_item(
     name: String,
+    preset: WorldPreset,
+    settings: GeneratorSettings,
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     list: &mut WorldList,
@@ -383,7 +609,14 @@ fn add_world_item(
 
     commands.entity(list_entity).push_children(&[world]);
 
-    list.worlds.insert(world, WorldItem { name: name.clone() });
+    list.worlds.insert(
+        world,
+        WorldItem {
+            name,
+            preset,
+            settings,
+        },
+    );
 }
 
 fn generate_new_world_name(world_list: &WorldList) -> String {
@@ -402,11 +635,91 @@ fn generate_new_world_name(world_list: &WorldList) -> String {
     }
 }
 
+fn preset_label(preset: &WorldPreset) -> String {
+    format!("Preset: {}", preset.get_name())
+}
+
+pub fn preset_selector_action(
+    mut selector_query: Query<
+        (&Interaction, &mut WorldPresetSelector, &Children),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut text_query: Query<&mut Text>,
+    biomes: Res<BiomeRegistry>,
+) {
+    for (interaction, mut selector, children) in selector_query.iter_mut() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let presets = WorldPreset::all(&biomes);
+        selector.index = (selector.index + 1) % presets.len();
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value = preset_label(&presets[selector.index]);
+            }
+        }
+    }
+}
+
+pub fn advanced_settings_toggle_action(
+    toggle_query: Query<&Interaction, (Changed<Interaction>, With<AdvancedSettingsToggle>)>,
+    mut panel_query: Query<&mut Style, With<AdvancedSettingsPanel>>,
+) {
+    for interaction in toggle_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        for mut style in panel_query.iter_mut() {
+            style.display = match style.display {
+                Display::None => Display::Grid,
+                _ => Display::None,
+            };
+        }
+    }
+}
+
+pub fn generator_setting_selector_action(
+    mut selector_query: Query<
+        (&Interaction, &mut GeneratorSettingSelector, &Children),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, mut selector, children) in selector_query.iter_mut() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        selector.index = (selector.index + 1) % selector.setting.values().len();
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value = selector.setting.label(selector.index);
+            }
+        }
+    }
+}
+
+/// Generator settings chosen in the advanced settings, the others keep their default value
+fn selected_settings<'a>(
+    selectors: impl Iterator<Item = &'a GeneratorSettingSelector>,
+) -> GeneratorSettings {
+    let mut settings = GeneratorSettings::default();
+    for selector in selectors {
+        let value = selector.setting.values()[selector.index];
+        selector.setting.set(&mut settings, value);
+    }
+    settings
+}
+
 pub fn solo_action(
-    (interaction_query, mut name_query, mut list_query): (
+    (interaction_query, mut name_query, mut list_query, preset_query, settings_query): (
         Query<(&Interaction, &MultiplayerButtonAction), (Changed<Interaction>, With<Button>)>,
         Query<&mut TextInputValue, With<WorldNameInput>>,
         Query<(Entity, &mut WorldList), With<WorldList>>,
+        Query<&WorldPresetSelector>,
+        Query<&GeneratorSettingSelector>,
     ),
     (asset_server, mut menu_state, mut game_state, mut world_map, mut selected_world): (
         Res<AssetServer>,
@@ -417,7 +730,7 @@ pub fn solo_action(
     ),
     mut commands: Commands,
     mut load_event: EventWriter<LoadWorldEvent>,
-    paths: Res<GameFolderPaths>,
+    (paths, biomes): (Res<GameFolderPaths>, Res<BiomeRegistry>),
 ) {
     if list_query.is_empty() {
         return;
@@ -439,8 +752,18 @@ pub fn solo_action(
                             name.0.clone()
                         };
 
+                        let preset = preset_query
+                            .get_single()
+                            .ok()
+                            .and_then(|selector| {
+                                WorldPreset::all(&biomes).get(selector.index).cloned()
+                            })
+                            .unwrap_or_default();
+
                         add_world_item(
                             new_name,
+                            preset,
+                            selected_settings(settings_query.iter()),
                             &mut commands,
                             &asset_server,
                             &mut list,
@@ -456,6 +779,8 @@ pub fn solo_action(
                     if let Some(world) = list.worlds.get(&world_entity) {
                         // update ressource name
                         selected_world.name = Some(world.name.clone());
+                        selected_world.preset = world.preset.clone();
+                        selected_world.settings = world.settings.clone();
 
                         load_event.send(LoadWorldEvent {
                             world_name: world.name.clone(),
@@ -483,19 +808,27 @@ pub fn delete_save_files(
     world_name: &str,
     game_folder_path: &Res<GameFolderPaths>,
 ) -> Result<(), io::Error> {
-    // Delete `world_save.ron`
-    match fs::remove_file(format!(
-        "{}{}.ron",
-        get_game_folder(Some(game_folder_path))
-            .join(SAVE_PATH)
-            .display(),
-        world_name
-    )) {
-        Ok(_) => info!("Successfully deleted world"),
-        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
-            error!("world_save.ron not found, skipping.")
+    // Delete the save file, whichever format it is stored in
+    let mut deleted = false;
+    for extension in ["ron", "bin"] {
+        match fs::remove_file(format!(
+            "{}{}.{}",
+            get_game_folder(Some(game_folder_path))
+                .join(SAVE_PATH)
+                .display(),
+            world_name,
+            extension
+        )) {
+            Ok(_) => deleted = true,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => error!("Failed to delete world: {}", e),
         }
-        Err(e) => error!("Failed to delete world: {}", e),
+    }
+
+    if deleted {
+        info!("Successfully deleted world");
+    } else {
+        error!("world save not found, skipping.")
     }
 
     Ok(())
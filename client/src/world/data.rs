@@ -1,5 +1,8 @@
 use bevy::prelude::*;
+use shared::world::column_index;
+use shared::world::BiomeTint;
 use shared::world::BlockData;
+use shared::world::PalettedContainer;
 use std::collections::HashSet;
 use std::hash::Hash;
 
@@ -20,7 +23,9 @@ pub enum GlobalMaterial {
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct ClientChunk {
-    pub map: HashMap<IVec3, BlockData>, // Maps block positions within a chunk to block IDs
+    pub map: PalettedContainer, // Maps block positions within a chunk to block IDs
+    /// Biome colours of each column, empty if the server did not send any
+    pub tints: Vec<BiomeTint>,
     #[serde(skip)]
     pub entity: Option<Entity>,
 }
@@ -34,6 +39,12 @@ pub struct ClientWorldMap {
 }
 
 impl ClientWorldMap {
+    /// Biome colours of the column containing a block, if its chunk has them
+    pub fn get_column_tint(&self, position: &IVec3) -> Option<&BiomeTint> {
+        let chunk = self.map.get(&global_block_to_chunk_pos(position))?;
+        chunk.tints.get(column_index(to_local_pos(position)))
+    }
+
     pub fn get_block_by_coordinates(&self, position: &IVec3) -> Option<&BlockData> {
         let x: i32 = position.x;
         let y: i32 = position.y;
@@ -1,13 +1,14 @@
-use std::f32::consts::PI;
 use std::{collections::HashMap, time::Instant};
 
+use crate::constants::GRASS_COLOR;
 use crate::world::{ClientChunk, ClientWorldMap};
 use bevy::{
     math::IVec3,
     prelude::*,
     render::mesh::{Indices, PrimitiveTopology},
 };
-use shared::world::{to_global_pos, BlockDirection, BlockId, BlockTransparency};
+use shared::world::{to_global_pos, BiomeColor, BlockId, BlockRegistry, BlockTransparency};
+use shared::CHUNK_SIZE;
 
 use super::voxel::{Face, FaceDirection, VoxelShape};
 
@@ -30,6 +31,7 @@ pub(crate) fn generate_chunk_mesh(
     chunk: &ClientChunk,
     chunk_pos: &IVec3,
     block_uvs: &HashMap<String, UvCoords>,
+    blocks: &BlockRegistry,
 ) -> Mesh {
     let start = Instant::now();
 
@@ -46,10 +48,11 @@ pub(crate) fn generate_chunk_mesh(
         let y = local_block_pos.y as f32;
         let z = local_block_pos.z as f32;
 
-        let global_block_pos = &to_global_pos(chunk_pos, local_block_pos);
-        let visibility = block.id.get_visibility();
+        let global_block_pos = &to_global_pos(chunk_pos, &local_block_pos);
+        let definition = blocks.get(block.id);
+        let visibility = definition.visibility;
 
-        if is_block_surrounded(world_map, global_block_pos, &visibility, &block.id) {
+        if is_block_surrounded(world_map, blocks, global_block_pos, &visibility, &block.id) {
             continue;
         }
 
@@ -59,7 +62,9 @@ pub(crate) fn generate_chunk_mesh(
         let mut local_uvs: Vec<[f32; 2]> = vec![];
         let mut local_colors: Vec<[f32; 4]> = vec![];
 
-        let voxel = VoxelShape::create_from_block(block);
+        let mut tinted_vertices: Vec<(usize, BiomeColor)> = vec![];
+
+        let voxel = VoxelShape::create_from_block(block, definition);
 
         for face in voxel.faces.iter() {
             let uv_coords: &UvCoords;
@@ -70,7 +75,18 @@ pub(crate) fn generate_chunk_mesh(
                 uv_coords = block_uvs.get("_Default").unwrap();
             }
 
-            if should_render_face(world_map, global_block_pos, &face.direction, &visibility) {
+            if should_render_face(
+                world_map,
+                blocks,
+                global_block_pos,
+                &face.direction,
+                &visibility,
+            ) {
+                if let Some(biome_color) = face.biome_color {
+                    let first = local_vertices.len();
+                    tinted_vertices
+                        .extend((first..first + face.vertices.len()).map(|i| (i, biome_color)));
+                }
                 render_face(
                     &mut local_vertices,
                     &mut local_indices,
@@ -84,18 +100,17 @@ pub(crate) fn generate_chunk_mesh(
             }
         }
 
+        // Voxel shapes are already turned and flipped like their block
         let local_vertices: Vec<[f32; 3]> = local_vertices
             .iter()
-            .map(|v| {
-                let v = rotate_vertices(v, &block.direction);
-                [
-                    v[0] + x,
-                    if block.flipped { 1. - v[1] } else { v[1] } + y,
-                    v[2] + z,
-                ]
-            })
+            .map(|v| [v[0] + x, v[1] + y, v[2] + z])
             .collect();
 
+        for (i, biome_color) in tinted_vertices {
+            local_colors[i] =
+                blended_biome_color(world_map, chunk_pos, &local_vertices[i], biome_color);
+        }
+
         vertices.extend(local_vertices);
         indices.extend(local_indices);
         normals.extend(local_normals);
@@ -120,6 +135,7 @@ pub(crate) fn generate_chunk_mesh(
 
 pub(crate) fn is_block_surrounded(
     world_map: &ClientWorldMap,
+    blocks: &BlockRegistry,
     global_block_pos: &IVec3,
     block_visibility: &BlockTransparency,
     block_id: &BlockId,
@@ -129,7 +145,7 @@ pub(crate) fn is_block_surrounded(
 
         // Check if the block exists at the neighboring position
         if let Some(block) = world_map.get_block_by_coordinates(&neighbor_pos) {
-            let vis = block.id.get_visibility();
+            let vis = blocks.get(block.id).visibility;
             match vis {
                 BlockTransparency::Solid => {}
                 BlockTransparency::Decoration => return false,
@@ -152,19 +168,34 @@ pub(crate) fn is_block_surrounded(
     true
 }
 
-pub fn rotate_vertices(v: &[f32; 3], direction: &BlockDirection) -> [f32; 3] {
-    let angle = match *direction {
-        BlockDirection::Front => 0.,
-        BlockDirection::Right => -PI / 2.,
-        BlockDirection::Left => PI / 2.,
-        BlockDirection::Back => PI,
-    };
+/// Biome colour at a vertex, averaged over the four columns around it so that
+/// colours fade across biome borders
+fn blended_biome_color(
+    world_map: &ClientWorldMap,
+    chunk_pos: &IVec3,
+    vertex: &[f32; 3],
+    biome_color: BiomeColor,
+) -> [f32; 4] {
+    let corner =
+        IVec3::new(vertex[0].round() as i32, 0, vertex[2].round() as i32) + *chunk_pos * CHUNK_SIZE;
+
+    let mut sum = [0u32; 3];
+    let mut count = 0;
+    for (dx, dz) in [(-1, -1), (-1, 0), (0, -1), (0, 0)] {
+        if let Some(tint) = world_map.get_column_tint(&(corner + IVec3::new(dx, 0, dz))) {
+            for (total, value) in sum.iter_mut().zip(tint.get(biome_color)) {
+                *total += value as u32;
+            }
+            count += 1;
+        }
+    }
 
-    [
-        angle.cos() * v[0] + angle.sin() * v[2],
-        v[1],
-        (-angle).sin() * v[0] + angle.cos() * v[2],
-    ]
+    // Chunks from generators without biomes keep the default colour
+    if count == 0 {
+        return GRASS_COLOR;
+    }
+    let [r, g, b] = sum.map(|total| total as f32 / (count * 255) as f32);
+    [r, g, b, 1.0]
 }
 
 fn render_face(
@@ -186,16 +217,18 @@ fn render_face(
 
     local_colors.extend(face.colors.iter());
 
+    // Faces smaller than a block only use a part of their texture
     local_uvs.extend(face.uvs.iter().map(|uv| {
         [
-            (uv[0] + uv_coords.u0).min(uv_coords.u1),
-            (uv[1] + uv_coords.v0).min(uv_coords.v1),
+            uv_coords.u0 + uv[0] * (uv_coords.u1 - uv_coords.u0),
+            uv_coords.v0 + uv[1] * (uv_coords.v1 - uv_coords.v0),
         ]
     }));
 }
 
 fn should_render_face(
     world_map: &ClientWorldMap,
+    blocks: &BlockRegistry,
     global_block_pos: &IVec3,
     direction: &FaceDirection,
     block_visibility: &BlockTransparency,
@@ -211,7 +244,7 @@ fn should_render_face(
     };
 
     if let Some(block) = world_map.get_block_by_coordinates(&(*global_block_pos + offset)) {
-        let vis = block.id.get_visibility();
+        let vis = blocks.get(block.id).visibility;
         match vis {
             BlockTransparency::Solid => false,
             BlockTransparency::Decoration => true,
@@ -11,7 +11,7 @@ use bevy::{
 };
 use bevy_mod_raycast::deferred::RaycastMesh;
 use shared::{
-    world::{global_block_to_chunk_pos, SIX_OFFSETS},
+    world::{global_block_to_chunk_pos, BlockRegistry, SIX_OFFSETS},
     CHUNK_SIZE,
 };
 
@@ -79,6 +79,7 @@ pub fn world_render_system(
     mut meshes: ResMut<Assets<Mesh>>,
     mut commands: Commands,
     mut first_chunk_received: ResMut<FirstChunkReceived>,
+    blocks: Res<BlockRegistry>,
 ) {
     for event in ev_render.read() {
         queued_events.events.insert(*event);
@@ -96,6 +97,7 @@ pub fn world_render_system(
     if !events.is_empty() {
         let map_ptr = Arc::new(world_map.clone());
         let block_uvs = Arc::new(material_resource.blocks.uvs.clone());
+        let block_registry = Arc::new(blocks.clone());
         let mut chunks_to_reload: HashSet<IVec3> = HashSet::new();
 
         // Using a set so same chunks are not reloaded multiple times
@@ -125,11 +127,18 @@ pub fn world_render_system(
                 // Define variables to move to the thread
                 let map_clone = Arc::clone(&map_ptr);
                 let uvs_clone = Arc::clone(&block_uvs);
+                let blocks_clone = Arc::clone(&block_registry);
                 let ch = chunk.clone();
                 let t = pool.spawn(async move {
                     (
                         pos,
-                        world::meshing::generate_chunk_mesh(&map_clone, &ch, &pos, &uvs_clone),
+                        world::meshing::generate_chunk_mesh(
+                            &map_clone,
+                            &ch,
+                            &pos,
+                            &uvs_clone,
+                            &blocks_clone,
+                        ),
                     )
                 });
                 queued_meshes.meshes.push(t);
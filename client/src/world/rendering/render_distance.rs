@@ -1,5 +1,7 @@
 use crate::{
-    constants::DEFAULT_CHUNK_RENDER_DISTANCE_RADIUS,
+    constants::{
+        DEFAULT_CHUNK_RENDER_DISTANCE_RADIUS, DEFAULT_CHUNK_VERTICAL_RENDER_DISTANCE_RADIUS,
+    },
     input::{data::GameAction, keyboard::is_action_just_pressed},
     KeyMap,
 };
@@ -8,6 +10,8 @@ use bevy::prelude::*;
 #[derive(Resource, Default)]
 pub struct RenderDistance {
     pub distance: u32,
+    /// Chunk layers loaded above and below the player
+    pub vertical_distance: u32,
 }
 
 pub fn render_distance_update_system(
@@ -19,6 +23,10 @@ pub fn render_distance_update_system(
         render_distance.distance = DEFAULT_CHUNK_RENDER_DISTANCE_RADIUS;
     }
 
+    if render_distance.vertical_distance == 0 {
+        render_distance.vertical_distance = DEFAULT_CHUNK_VERTICAL_RENDER_DISTANCE_RADIUS;
+    }
+
     if is_action_just_pressed(GameAction::RenderDistanceMinus, &keyboard_input, &key_map) {
         render_distance.distance -= 1;
     }
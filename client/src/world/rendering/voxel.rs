@@ -1,9 +1,12 @@
-use crate::constants::GRASS_COLOR;
-use shared::world::{BlockData, BlockId};
+use bevy::math::Vec3;
+use shared::world::{
+    BiomeColor, BlockData, BlockDefinition, BlockDirection, BlockModel, BlockTextures, ShapeBox,
+};
 
 /// Specifies which position in the voxel this face occupies
 ///
 /// These faces do not render under certain circumstances to preserve resources
+#[derive(Clone, Copy, PartialEq)]
 pub enum FaceDirection {
     /// The face is at the top of the voxel. Won't render if the block above this one is full
     Top,
@@ -21,6 +24,50 @@ pub enum FaceDirection {
     Inset,
 }
 
+impl FaceDirection {
+    /// Outward normal of the faces on this side of the voxel
+    fn normal(self) -> Option<Vec3> {
+        match self {
+            FaceDirection::Top => Some(Vec3::Y),
+            FaceDirection::Bottom => Some(Vec3::NEG_Y),
+            FaceDirection::Front => Some(Vec3::NEG_Z),
+            FaceDirection::Back => Some(Vec3::Z),
+            FaceDirection::Right => Some(Vec3::X),
+            FaceDirection::Left => Some(Vec3::NEG_X),
+            FaceDirection::Inset => None,
+        }
+    }
+
+    fn from_normal(normal: Vec3) -> Self {
+        let rounded = normal.round();
+        [
+            FaceDirection::Top,
+            FaceDirection::Bottom,
+            FaceDirection::Front,
+            FaceDirection::Back,
+            FaceDirection::Right,
+            FaceDirection::Left,
+        ]
+        .into_iter()
+        .find(|direction| direction.normal() == Some(rounded))
+        .unwrap_or(FaceDirection::Inset)
+    }
+
+    /// Texture coordinates of a point of a face on this side, so that the faces of boxes
+    /// show the part of the texture matching their place in the block
+    fn uv(self, vertex: [f32; 3]) -> [f32; 2] {
+        let [x, y, z] = vertex;
+        match self {
+            FaceDirection::Top => [x, 1. - z],
+            FaceDirection::Bottom => [1. - x, z],
+            FaceDirection::Front => [1. - x, 1. - y],
+            FaceDirection::Back => [x, 1. - y],
+            FaceDirection::Left => [z, 1. - y],
+            FaceDirection::Right | FaceDirection::Inset => [1. - z, 1. - y],
+        }
+    }
+}
+
 /// Structure for cube voxel rendering
 pub struct Face {
     pub direction: FaceDirection,
@@ -30,6 +77,52 @@ pub struct Face {
     pub colors: Vec<[f32; 4]>,
     pub uvs: Vec<[f32; 2]>,
     pub texture: String,
+    /// Biome colour replacing the colors of the vertices when meshing
+    pub biome_color: Option<BiomeColor>,
+}
+
+impl Face {
+    /// Whether the face lies on the side of the voxel its direction points to
+    fn is_on_boundary(&self) -> bool {
+        let Some(normal) = self.direction.normal() else {
+            return false;
+        };
+        let side = if normal.max_element() > 0. { 1. } else { 0. };
+        self.vertices
+            .iter()
+            .all(|vertex| Vec3::from_array(*vertex).dot(normal.abs()) == side)
+    }
+
+    /// Turns and flips the face of a block facing `Front` like the block
+    fn orient(&mut self, block: &BlockData, definition: &BlockDefinition) {
+        let mirrored = definition.is_mirrored(block);
+        let orient_normal = |normal: Vec3| {
+            let normal = if mirrored {
+                normal * Vec3::new(1., -1., 1.)
+            } else {
+                normal
+            };
+            block.direction.rotate_vector(normal)
+        };
+
+        for vertex in self.vertices.iter_mut() {
+            *vertex = definition
+                .orient(block, Vec3::from_array(*vertex))
+                .to_array();
+        }
+        for normal in self.normals.iter_mut() {
+            *normal = orient_normal(Vec3::from_array(*normal)).to_array();
+        }
+        if mirrored {
+            // Mirrored triangles would face inwards
+            for triangle in self.indices.chunks_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        if let Some(normal) = self.direction.normal() {
+            self.direction = FaceDirection::from_normal(orient_normal(normal));
+        }
+    }
 }
 
 /// Structure for voxel rendering
@@ -38,62 +131,73 @@ pub struct VoxelShape {
 }
 
 impl VoxelShape {
-    /// Creates a VoxelShape based on the given BlockData
-    pub fn create_from_block(block: &BlockData) -> VoxelShape {
-        match block.id {
-            BlockId::Grass => {
-                let mut shape = Self::full_cube(block);
-
-                // Apply grass-specific textures and coloring
-                for (index, face) in shape.faces.iter_mut().enumerate() {
-                    if index == 0 {
-                        face.texture += "Top";
-                        for col in face.colors.iter_mut() {
-                            *col = GRASS_COLOR;
-                        }
-                    }
-                }
-
-                shape
+    /// Creates a VoxelShape based on the given BlockData and the definition of its block
+    pub fn create_from_block(block: &BlockData, definition: &BlockDefinition) -> VoxelShape {
+        let mut shape = match definition.model {
+            BlockModel::Cross => Self::flora(&definition.textures.all),
+            BlockModel::Cube | BlockModel::Liquid => Self::full_cube(&definition.textures),
+            BlockModel::Slab | BlockModel::Stairs => {
+                Self::from_boxes(&definition.model.boxes(block), &definition.textures)
             }
-            BlockId::OakLog | BlockId::SpruceLog => {
-                let mut shape = Self::full_cube(block);
-                shape.faces[0].texture += "Top";
-                shape.faces[1].texture += "Top";
-                shape
+            BlockModel::Door => {
+                // Each half shows its part of the door on all of its faces
+                let textures = &definition.textures;
+                let texture = if block.flipped {
+                    &textures.top
+                } else {
+                    &textures.bottom
+                };
+                let textures = BlockTextures {
+                    all: texture.clone().unwrap_or_else(|| textures.all.clone()),
+                    top: None,
+                    bottom: None,
+                    front: None,
+                    back: None,
+                    left: None,
+                    right: None,
+                };
+                Self::from_boxes(&definition.model.boxes(block), &textures)
             }
-            BlockId::OakLeaves | BlockId::SpruceLeaves => {
-                let mut shape = Self::full_cube(block);
+        };
 
-                // Apply leaves color
-                for face in shape.faces.iter_mut() {
-                    for col in face.colors.iter_mut() {
-                        *col = GRASS_COLOR;
-                    }
+        if definition.model == BlockModel::Liquid {
+            // Flowing liquids get lower the farther they are from their source
+            let height = (8 - block.level.min(7)) as f32 / 8.;
+            for face in shape.faces.iter_mut() {
+                for vertex in face.vertices.iter_mut() {
+                    vertex[1] *= height;
                 }
+            }
+        }
 
-                shape
+        if block.flipped || block.direction != BlockDirection::Front {
+            for face in shape.faces.iter_mut() {
+                face.orient(block, definition);
             }
-            BlockId::Debug => {
-                let mut shape = Self::full_cube(block);
-                shape.faces[0].texture = "Top".into();
-                shape.faces[1].texture = "Down".into();
-                shape.faces[2].texture = "Front".into();
-                shape.faces[3].texture = "Back".into();
-                shape.faces[4].texture = "Left".into();
-                shape.faces[5].texture = "Right".into();
-                shape
+        }
+
+        for (index, face) in shape.faces.iter_mut().enumerate() {
+            for col in face.colors.iter_mut() {
+                *col = definition.color;
+            }
+            if let Some(tint) = definition.tint {
+                if index == 0 || !tint.top_only {
+                    face.biome_color = Some(tint.color);
+                }
             }
-            BlockId::Poppy | BlockId::Dandelion => Self::flora(block),
-            _ => Self::full_cube(block),
         }
+
+        shape
     }
 
-    pub fn full_cube(block: &BlockData) -> Self {
+    pub fn full_cube(textures: &BlockTextures) -> Self {
+        // Faces without a texture of their own use the one of the whole block
+        let texture = |face: &Option<String>| face.clone().unwrap_or_else(|| textures.all.clone());
         VoxelShape {
             faces: vec![
                 Face {
-                    texture: format!("{:?}", block.id),
+                    texture: texture(&textures.top),
+                    biome_color: None,
                     direction: FaceDirection::Top,
                     vertices: vec![[0., 1., 1.], [1., 1., 1.], [1., 1., 0.], [0., 1., 0.]],
                     indices: vec![0, 1, 2, 2, 3, 0],
@@ -112,7 +216,8 @@ impl VoxelShape {
                     uvs: vec![[0., 0.], [1., 0.], [1., 1.], [0., 1.]],
                 },
                 Face {
-                    texture: format!("{:?}", block.id),
+                    texture: texture(&textures.bottom),
+                    biome_color: None,
                     direction: FaceDirection::Bottom,
                     vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 0., 1.], [0., 0., 1.]],
                     indices: vec![0, 1, 2, 2, 3, 0],
@@ -131,7 +236,8 @@ impl VoxelShape {
                     uvs: vec![[1., 0.], [0., 0.], [0., 1.], [1., 1.]],
                 },
                 Face {
-                    texture: format!("{:?}", block.id),
+                    texture: texture(&textures.front),
+                    biome_color: None,
                     direction: FaceDirection::Front,
                     vertices: vec![[1., 1., 0.], [0., 1., 0.], [0., 0., 0.], [1., 0., 0.]],
                     indices: vec![0, 3, 2, 2, 1, 0],
@@ -150,7 +256,8 @@ impl VoxelShape {
                     uvs: vec![[0., 0.], [1., 0.], [1., 1.], [0., 1.]],
                 },
                 Face {
-                    texture: format!("{:?}", block.id),
+                    texture: texture(&textures.back),
+                    biome_color: None,
                     direction: FaceDirection::Back,
                     vertices: vec![[1., 1., 1.], [0., 1., 1.], [0., 0., 1.], [1., 0., 1.]],
                     indices: vec![0, 1, 2, 2, 3, 0],
@@ -169,7 +276,8 @@ impl VoxelShape {
                     uvs: vec![[1., 0.], [0., 0.], [0., 1.], [1., 1.]],
                 },
                 Face {
-                    texture: format!("{:?}", block.id),
+                    texture: texture(&textures.left),
+                    biome_color: None,
                     direction: FaceDirection::Left,
                     vertices: vec![[0., 1., 1.], [0., 1., 0.], [0., 0., 0.], [0., 0., 1.]],
                     indices: vec![3, 0, 1, 1, 2, 3],
@@ -188,7 +296,8 @@ impl VoxelShape {
                     uvs: vec![[1., 0.], [0., 0.], [0., 1.], [1., 1.]],
                 },
                 Face {
-                    texture: format!("{:?}", block.id),
+                    texture: texture(&textures.right),
+                    biome_color: None,
                     direction: FaceDirection::Right,
                     vertices: vec![[1., 1., 0.], [1., 1., 1.], [1., 0., 1.], [1., 0., 0.]],
                     indices: vec![0, 1, 2, 2, 3, 0],
@@ -210,7 +319,35 @@ impl VoxelShape {
         }
     }
 
-    pub fn flora(block: &BlockData) -> VoxelShape {
+    /// Shape made of boxes, such as stairs. The faces of the boxes inside the block always render
+    pub fn from_boxes(boxes: &[ShapeBox], textures: &BlockTextures) -> VoxelShape {
+        let faces = boxes
+            .iter()
+            .flat_map(|shape_box| {
+                let size = shape_box.max - shape_box.min;
+                Self::full_cube(textures)
+                    .faces
+                    .into_iter()
+                    .map(move |mut face| {
+                        for vertex in face.vertices.iter_mut() {
+                            *vertex = (shape_box.min + Vec3::from_array(*vertex) * size).to_array();
+                        }
+                        face.uvs = face
+                            .vertices
+                            .iter()
+                            .map(|vertex| face.direction.uv(*vertex))
+                            .collect();
+                        if !face.is_on_boundary() {
+                            face.direction = FaceDirection::Inset;
+                        }
+                        face
+                    })
+            })
+            .collect();
+        VoxelShape { faces }
+    }
+
+    pub fn flora(texture: &str) -> VoxelShape {
         VoxelShape {
             faces: vec![Face {
                 direction: FaceDirection::Inset,
@@ -264,7 +401,8 @@ impl VoxelShape {
                     [1., 0.],
                     [1., 0.],
                 ],
-                texture: format!("{:?}", block.id),
+                texture: texture.to_string(),
+                biome_color: None,
             }],
         }
     }
@@ -0,0 +1,381 @@
+use bevy::math::IVec2;
+use clap::Subcommand;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use shared::world::{BiomeRegistry, BlockRegistry, GeneratorSettings, WorldHeight, WorldPreset};
+use shared::GameFolderPaths;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::network::utils::format_bytes;
+use crate::world::data::{Save, StorageFormat, TerrainDataHash};
+use crate::world::generation::place_missing_blocks;
+use crate::world::generators::create_generator;
+use crate::world::load_from_file::{find_save_file, get_saves_folder, read_save, save_path};
+use crate::world::migration::parse_save;
+use crate::world::preview::generate_preview;
+use crate::world::save::write_save;
+use crate::world::structures::StructureRegistry;
+
+/// Magic bytes at the start of exported world archives
+const ARCHIVE_MAGIC: &[u8] = b"RCWORLD\0";
+const ARCHIVE_EXTENSION: &str = "rcworld";
+const BACKUP_FOLDER: &str = "backups";
+
+/// Offline world management commands, working directly on the saves folder
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export a world to a portable archive
+    Export {
+        world: String,
+        /// Defaults to `<world>.rcworld` in the current directory
+        output: Option<PathBuf>,
+    },
+    /// Import a world from an archive created with `export`
+    Import {
+        archive: PathBuf,
+        /// Name of the imported world, defaults to the one stored in the archive
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = StorageFormat::Ron)]
+        format: StorageFormat,
+        /// Overwrite the world if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Copy a world save into the backups folder
+    Backup { world: String },
+    /// Print the seed, chunk count, size and players of a world
+    Info { world: String },
+    /// Remove the chunks that are identical to freshly generated terrain
+    Prune { world: String },
+    /// Convert a world to another storage format
+    Convert {
+        world: String,
+        #[arg(value_enum)]
+        format: StorageFormat,
+    },
+    /// Generate a region without saving it, and write height, biome and surface images of it
+    /// along with generation timings
+    Preview {
+        #[arg(long, default_value_t = 0)]
+        seed: u32,
+        /// "default", "superflat", "void", "amplified" or the name of a biome
+        #[arg(long, default_value = "default")]
+        preset: String,
+        /// Generator settings as RON text or a path to a RON file
+        #[arg(long)]
+        settings: Option<String>,
+        /// Use the seed, preset and generator settings of an existing world instead
+        #[arg(long, conflicts_with_all = ["seed", "preset", "settings"])]
+        world: Option<String>,
+        /// Chunk coordinates of the center of the region
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        chunk_x: i32,
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        chunk_z: i32,
+        /// Chunks generated around the center, in each direction
+        #[arg(long, default_value_t = 8)]
+        radius: u32,
+        /// Folder the images are written into
+        #[arg(short, long, default_value = "preview")]
+        output: PathBuf,
+    },
+}
+
+pub fn run(command: Command, game_folder_path: String) -> Result<(), Box<dyn std::error::Error>> {
+    let game_folder_paths = GameFolderPaths {
+        assets_folder_path: format!("{}/data", game_folder_path),
+        game_folder_path,
+    };
+    let saves_folder = get_saves_folder(&game_folder_paths);
+
+    match command {
+        Command::Export { world, output } => {
+            let (path, format) = find_world(&saves_folder, &world)?;
+            let save = read_save(&path, format)?;
+            let output =
+                output.unwrap_or_else(|| PathBuf::from(format!("{}.{}", world, ARCHIVE_EXTENSION)));
+
+            // Archives always contain the RON representation, readable on any platform
+            let mut file = File::create(&output)?;
+            file.write_all(ARCHIVE_MAGIC)?;
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(ron::ser::to_string(&save)?.as_bytes())?;
+            encoder.finish()?;
+
+            println!("Exported world {} to {}", world, output.display());
+        }
+        Command::Import {
+            archive,
+            name,
+            format,
+            force,
+        } => {
+            let mut bytes = Vec::new();
+            File::open(&archive)?.read_to_end(&mut bytes)?;
+            let Some(compressed) = bytes.strip_prefix(ARCHIVE_MAGIC) else {
+                return Err(format!("{} is not a world archive", archive.display()).into());
+            };
+
+            let mut contents = String::new();
+            GzDecoder::new(compressed).read_to_string(&mut contents)?;
+            let mut save = parse_save(&contents)?;
+            if let Some(name) = name {
+                save.name = name;
+            }
+            // The name comes from the archive, which may have been crafted to escape the saves folder
+            check_world_name(&save.name)?;
+
+            if let Some((existing, _)) = find_save_file(&saves_folder, &save.name) {
+                if !force {
+                    return Err(format!(
+                        "world {} already exists, use --force to overwrite it",
+                        save.name
+                    )
+                    .into());
+                }
+                fs::remove_file(existing)?;
+            }
+
+            fs::create_dir_all(&saves_folder)?;
+            write_save(&save, &save_path(&saves_folder, &save.name, format), format)?;
+            println!("Imported world {}", save.name);
+        }
+        Command::Backup { world } => {
+            let (path, format) = find_world(&saves_folder, &world)?;
+            let backup_folder = saves_folder.join(BACKUP_FOLDER);
+            fs::create_dir_all(&backup_folder)?;
+
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let backup_path =
+                backup_folder.join(format!("{}-{}.{}", world, timestamp, format.extension()));
+            fs::copy(&path, &backup_path)?;
+
+            println!("Backed up world {} to {}", world, backup_path.display());
+        }
+        Command::Info { world } => {
+            let (path, format) = find_world(&saves_folder, &world)?;
+            let size = fs::metadata(&path)?.len();
+            let save = read_save(&path, format)?;
+
+            println!("World: {}", save.name);
+            println!("File: {} ({:?})", path.display(), format);
+            println!("Size: {}", format_bytes(size));
+            println!("Seed: {}", save.seed.0);
+            println!("Preset: {}", save.preset.get_name());
+            println!("Height: {} to {}", save.height.min_y, save.height.max_y);
+            println!("Generator settings: {:?}", save.settings);
+            println!("Time: {}", save.time);
+            println!("Chunks: {}", save.chunks.len());
+            println!("Players: {}", save.player_positions.len());
+            for (id, position) in save.player_positions.iter() {
+                println!("  {} at {}", id, position);
+            }
+        }
+        Command::Prune { world } => {
+            let (path, format) = find_world(&saves_folder, &world)?;
+            let assets_folder = Path::new(&game_folder_paths.assets_folder_path);
+            let blocks = BlockRegistry::load(assets_folder);
+            let biomes = BiomeRegistry::load(assets_folder, &blocks);
+            let structures = StructureRegistry::load(assets_folder, &blocks);
+            let save = read_save(&path, format)?;
+            // Unmodified chunks would be compared with terrain the world was not generated with
+            if save.terrain_data.is_some_and(|terrain_data| {
+                terrain_data != TerrainDataHash::of_folder(assets_folder)
+            }) {
+                return Err(format!(
+                    "world {} was generated with other blocks, biomes or structures, pruning it would lose edited chunks",
+                    world
+                )
+                .into());
+            }
+            let mut world_data = save.into_world(&blocks);
+            let seed = world_data.seed.0;
+            let generator = create_generator(
+                &world_data.preset,
+                &world_data.settings,
+                &biomes,
+                &structures,
+                &blocks,
+            );
+
+            let before = world_data.map.map.len();
+            // Saved chunks contain the features their neighbours placed into them, which are
+            // not in the pending writes anymore. Pruned chunks get them back there, so that
+            // they are placed again when the chunks are generated again
+            let map = &mut world_data.map;
+            let mut overflows = HashMap::new();
+            map.map.retain(|chunk_pos, chunk| {
+                let mut features = map.pending_writes.remove(chunk_pos).unwrap_or_default();
+                for (source_pos, targets) in map.features_placed.iter() {
+                    if !targets.contains(chunk_pos) {
+                        continue;
+                    }
+                    let overflow = overflows
+                        .entry(*source_pos)
+                        .or_insert_with(|| generator.generate_chunk(*source_pos, seed).overflow);
+                    if let Some(blocks) = overflow.get(chunk_pos) {
+                        place_missing_blocks(&mut features, blocks);
+                    }
+                }
+
+                let mut generated = generator.generate_chunk(*chunk_pos, seed).chunk;
+                place_missing_blocks(&mut generated.map, &features);
+                let modified = generated.map != chunk.map;
+                if !modified && !features.is_empty() {
+                    map.pending_writes.insert(*chunk_pos, features);
+                }
+                modified
+            });
+            let pruned = before - world_data.map.map.len();
+
+            write_save(&Save::from_world(&world_data, &blocks), &path, format)?;
+            println!(
+                "Pruned {} of {} chunks from world {}",
+                pruned, before, world
+            );
+        }
+        Command::Convert { world, format } => {
+            let (path, current_format) = find_world(&saves_folder, &world)?;
+            if current_format == format {
+                println!("World {} is already stored as {:?}", world, format);
+                return Ok(());
+            }
+
+            let save = read_save(&path, current_format)?;
+            write_save(&save, &save_path(&saves_folder, &world, format), format)?;
+            fs::remove_file(&path)?;
+
+            println!("Converted world {} to {:?}", world, format);
+        }
+        Command::Preview {
+            seed,
+            preset,
+            settings,
+            world,
+            chunk_x,
+            chunk_z,
+            radius,
+            output,
+        } => {
+            let assets_folder = Path::new(&game_folder_paths.assets_folder_path);
+            let blocks = BlockRegistry::load(assets_folder);
+            let biomes = BiomeRegistry::load(assets_folder, &blocks);
+            let structures = StructureRegistry::load(assets_folder, &blocks);
+
+            let (seed, preset, settings, height) = match world {
+                Some(world) => {
+                    let (path, format) = find_world(&saves_folder, &world)?;
+                    let save = read_save(&path, format)?;
+                    (save.seed.0, save.preset, save.settings, save.height)
+                }
+                None => (
+                    seed,
+                    parse_preset(&preset, &biomes)?,
+                    settings
+                        .as_deref()
+                        .map(parse_generator_settings)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    WorldHeight::default(),
+                ),
+            };
+
+            settings.check_height(&height)?;
+            let generator = create_generator(&preset, &settings, &biomes, &structures, &blocks);
+            println!(
+                "Previewing seed {} with preset {} around chunk ({}, {})",
+                seed,
+                preset.get_name(),
+                chunk_x,
+                chunk_z
+            );
+            let report = generate_preview(
+                generator.as_ref(),
+                seed,
+                IVec2::new(chunk_x, chunk_z),
+                radius as i32,
+                height,
+                &blocks,
+                &output,
+            )?;
+            print!("{}", report);
+            println!("Images written to {}", output.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads generator settings given as RON text, or as the path to a RON file
+pub fn parse_generator_settings(
+    arg: &str,
+) -> Result<GeneratorSettings, Box<dyn std::error::Error>> {
+    let path = Path::new(arg);
+    if path.is_file() {
+        GeneratorSettings::from_ron(&fs::read_to_string(path)?)
+    } else {
+        GeneratorSettings::from_ron(arg)
+    }
+}
+
+/// Finds a preset by its name, or a single biome preset by the name of its biome
+fn parse_preset(
+    name: &str,
+    biomes: &BiomeRegistry,
+) -> Result<WorldPreset, Box<dyn std::error::Error>> {
+    WorldPreset::all(biomes)
+        .into_iter()
+        .find(|preset| match preset {
+            WorldPreset::SingleBiome { biome } => biome == name,
+            _ => preset.get_name().eq_ignore_ascii_case(name),
+        })
+        .ok_or_else(|| format!("unknown preset {}", name).into())
+}
+
+/// World names become file names in the saves folder : they cannot contain paths
+fn check_world_name(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("invalid world name {:?}", name).into());
+    }
+    Ok(())
+}
+
+/// Save file of a world given on the command line, whose name is checked first
+fn find_world(
+    saves_folder: &Path,
+    world: &str,
+) -> Result<(PathBuf, StorageFormat), Box<dyn std::error::Error>> {
+    check_world_name(world)?;
+    find_save_file(saves_folder, world)
+        .ok_or_else(|| format!("world {} not found in {}", world, saves_folder.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_names_cannot_leave_the_saves_folder() {
+        assert!(check_world_name("My world").is_ok());
+        for name in ["", "../../something", "a/b", "a\\b", ".."] {
+            assert!(check_world_name(name).is_err(), "{:?} was accepted", name);
+        }
+
+        // Every subcommand finds its world through `find_world`, even when the file exists
+        let saves_folder = std::env::temp_dir()
+            .join("rustcraft_cli_test")
+            .join("saves");
+        fs::create_dir_all(&saves_folder).unwrap();
+        let outside = saves_folder.parent().unwrap().join("outside.ron");
+        fs::write(&outside, "").unwrap();
+        let error = find_world(&saves_folder, "../outside").err().unwrap();
+        assert!(error.to_string().contains("invalid world name"));
+        fs::remove_file(outside).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use crate::inventory::HeldItems;
+use bevy::prelude::*;
+use shared::messages::{CraftRequest, CraftResponse, PlayerId};
+use shared::world::{
+    BlockRegistry, BlockTags, ItemRegistry, ItemStack, RecipeRegistry, ServerWorldMap,
+    INVENTORY_CRAFTING_SIZE, TABLE_CRAFTING_SIZE,
+};
+
+/// Farthest a player can be from the crafting table they use, a bit more than their reach
+/// since the server only knows their last sent position
+const MAX_TABLE_DISTANCE: f32 = 9.;
+
+/// Checks that the items of a crafting grid make a recipe, in a grid the player can use
+pub fn handle_craft_request(
+    player: PlayerId,
+    request: CraftRequest,
+    recipes: &RecipeRegistry,
+    (blocks, items): (&BlockRegistry, &ItemRegistry),
+    world_map: &ServerWorldMap,
+    held_items: &mut HeldItems,
+) -> CraftResponse {
+    let result = if can_use_grid(player, &request, blocks, world_map) {
+        recipes
+            .find(request.size, &request.grid)
+            .map(|recipe| recipe.result().clone())
+    } else {
+        None
+    };
+    // Ingredients are used up, and must have been handed out to the player
+    let ingredients: Vec<_> = request
+        .grid
+        .iter()
+        .flatten()
+        .map(|item| (*item, 1))
+        .collect();
+    let result = result.filter(|_| held_items.take_all(player, &ingredients));
+    if let Some(result) = &result {
+        held_items.give(
+            player,
+            ItemStack {
+                item_id: result.item,
+                item_type: items.get(result.item).default_type(),
+                nb: result.count,
+            },
+        );
+    }
+    if result.is_none() {
+        debug!("Craft request of player {} rejected: {:?}", player, request);
+    }
+
+    CraftResponse {
+        grid: request.grid,
+        result,
+    }
+}
+
+fn can_use_grid(
+    player: PlayerId,
+    request: &CraftRequest,
+    blocks: &BlockRegistry,
+    world_map: &ServerWorldMap,
+) -> bool {
+    match (request.size, request.table) {
+        (INVENTORY_CRAFTING_SIZE, _) => true,
+        (TABLE_CRAFTING_SIZE, Some(table)) => {
+            let is_table = world_map
+                .get_block_by_coordinates(&table)
+                .is_some_and(|block| {
+                    blocks.contains(block.id)
+                        && blocks.get(block.id).has_tag(BlockTags::CraftingTable)
+                });
+            let in_reach = world_map
+                .player_positions
+                .get(&player)
+                .is_none_or(|position| {
+                    position.distance(table.as_vec3() + Vec3::splat(0.5)) <= MAX_TABLE_DISTANCE
+                });
+            is_table && in_reach
+        }
+        _ => false,
+    }
+}
@@ -1,3 +1,4 @@
+use crate::inventory::HeldItems;
 use crate::network::dispatcher::{self, setup_resources_and_events};
 use bevy::{
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
@@ -8,12 +9,16 @@ use bevy_renet::renet::transport::NetcodeServerTransport;
 use bevy_renet::renet::RenetServer;
 use bevy_renet::RenetServerPlugin;
 use serde::{Deserialize, Serialize};
+use shared::world::{BiomeRegistry, BlockRegistry, ItemRegistry, RecipeRegistry};
 use shared::{get_shared_renet_config, messages::PlayerId, GameFolderPaths, GameServerConfig};
 use std::fmt::Debug;
+use std::path::Path;
 use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, net::IpAddr};
 
-use crate::world::load_from_file::{load_world_map, load_world_seed, load_world_time};
+use crate::world::generators::{create_generator, ActiveGenerator};
+use crate::world::load_from_file::load_world_data;
+use crate::world::structures::StructureRegistry;
 
 use bevy_renet::renet::transport::{ServerAuthentication, ServerConfig};
 use bevy_renet::transport::NetcodeServerPlugin;
@@ -74,13 +79,27 @@ pub fn init(socket: UdpSocket, config: GameServerConfig, game_folder_path: Strin
     app.add_plugins(bevy::log::LogPlugin::default());
 
     app.insert_resource(ServerLobby::default());
+    let assets_folder_path = format!("{}/data", game_folder_path);
+    let blocks = BlockRegistry::load(Path::new(&assets_folder_path));
+    app.insert_resource(BiomeRegistry::load(Path::new(&assets_folder_path), &blocks));
+    let items = match ItemRegistry::load(Path::new(&assets_folder_path), &blocks) {
+        Ok(items) => items,
+        Err(e) => {
+            error!("Error loading items: {}", e);
+            panic!();
+        }
+    };
+    app.insert_resource(RecipeRegistry::load(Path::new(&assets_folder_path), &items));
+    app.insert_resource(items);
+    app.insert_resource(blocks);
     app.insert_resource(GameFolderPaths {
         game_folder_path: game_folder_path.clone(),
-        assets_folder_path: format!("{}/data", game_folder_path),
+        assets_folder_path,
     });
 
     let world_name = &config.world_name.clone();
 
+    app.insert_resource(config.chunk_compression);
     app.insert_resource(config);
 
     info!("Starting server on {}", socket.local_addr().unwrap());
@@ -90,37 +109,45 @@ pub fn init(socket: UdpSocket, config: GameServerConfig, game_folder_path: Strin
     setup_resources_and_events(&mut app);
 
     // Load world from files
-    let world_map = match load_world_map(world_name, &app) {
-        Ok(world) => world,
-        Err(e) => {
-            error!("Error loading world: {}. Generating a new one.", e);
-            panic!();
-        }
-    };
-
-    let world_seed = match load_world_seed(world_name, &app) {
-        Ok(seed) => {
-            info!("World seed loaded successfully: {}", seed.0); // Affiche la seed chargée
-            seed
-        }
+    let world_data = match load_world_data(world_name, &app) {
+        Ok(data) => data,
         Err(e) => {
-            error!("Error loading seed: {}. Generating a new one.", e);
+            error!("Error loading world: {}", e);
             panic!();
         }
     };
-
-    let server_time = match load_world_time(world_name, &app) {
-        Ok(time) => time,
-        Err(e) => {
-            error!("Error loading time: {}. Defaulting to 0.", e);
-            0
-        }
-    };
-
-    // Insert world_map and seed into ressources
+    info!("World seed loaded successfully: {}", world_data.seed.0); // Affiche la seed chargée
+
+    // Insert world_map, seed and generator into ressources
+    let mut world_map = world_data.map;
+    let biomes = app.world().resource::<BiomeRegistry>();
+    let blocks = app.world().resource::<BlockRegistry>();
+    let paths = app.world().resource::<GameFolderPaths>();
+    let structures = StructureRegistry::load(Path::new(&paths.assets_folder_path), blocks);
+    let generator = create_generator(
+        &world_data.preset,
+        &world_data.settings,
+        biomes,
+        &structures,
+        blocks,
+    );
+    // Tints are not saved, saved chunks get them from the generator again
+    for (chunk_pos, chunk) in world_map.map.iter_mut() {
+        chunk.tints = generator.column_tints(*chunk_pos, world_data.seed.0);
+    }
     app.insert_resource(world_map);
-    app.insert_resource(world_seed);
-    app.insert_resource(ServerTime(server_time));
+    app.insert_resource(world_data.seed);
+    app.insert_resource(ActiveGenerator(generator.into()));
+    app.insert_resource(world_data.preset);
+    app.insert_resource(world_data.settings);
+    app.insert_resource(world_data.height);
+    if let Some(terrain_data) = world_data.terrain_data {
+        app.insert_resource(terrain_data);
+    }
+    app.insert_resource(ServerTime(world_data.time));
+    let items = app.world().resource::<ItemRegistry>();
+    let held_items = HeldItems::from_saved(&world_data.held_items, items);
+    app.insert_resource(held_items);
 
     dispatcher::register_systems(&mut app);
 
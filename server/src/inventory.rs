@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use shared::messages::PlayerId;
+use shared::world::{ItemId, ItemRegistry, ItemStack, ItemType};
+
+/// Items held by one player
+#[derive(Debug, Default)]
+struct PlayerItems {
+    counts: HashMap<ItemId, u32>,
+    /// Remaining durability of each tool, as many as the count of their item
+    durabilities: HashMap<ItemId, Vec<u16>>,
+}
+
+impl PlayerItems {
+    /// Removes the held tool of a kind whose durability is the closest to the one of a client
+    fn take_tool(&mut self, item: ItemId, durability: u16) -> Option<u16> {
+        let durabilities = self.durabilities.get_mut(&item)?;
+        let (index, _) = durabilities
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, held)| held.abs_diff(durability))?;
+        let held = durabilities.swap_remove(index);
+        if durabilities.is_empty() {
+            self.durabilities.remove(&item);
+        }
+        Some(held)
+    }
+
+    /// Removes the most worn tools of a kind, such as when crafting with them
+    fn take_worn_tools(&mut self, item: ItemId, nb: u32) {
+        if let Some(durabilities) = self.durabilities.get_mut(&item) {
+            durabilities.sort_unstable_by(|a, b| b.cmp(a));
+            durabilities.truncate(durabilities.len().saturating_sub(nb as usize));
+            if durabilities.is_empty() {
+                self.durabilities.remove(&item);
+            }
+        }
+    }
+
+    fn remove(&mut self, item: ItemId, nb: u32) {
+        let count = self.counts.get_mut(&item).unwrap();
+        *count -= nb;
+        if *count == 0 {
+            self.counts.remove(&item);
+        }
+    }
+}
+
+/// Stack held by a player, as stored in saves : items are referenced by name,
+/// since their numeric IDs may change between versions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedStack {
+    pub item: String,
+    pub nb: u32,
+    pub durability: Option<u16>,
+}
+
+/// Items the server handed out to each player, because they picked them up or crafted them,
+/// minus the ones they used since. Clients manage their own inventory : the server only checks
+/// that the items they claim to use are among these, not which slot they are in.
+/// Tools are worn out here, clients only tell which of their tools of a kind they use
+#[derive(Resource, Debug, Default)]
+pub struct HeldItems(HashMap<PlayerId, PlayerItems>);
+
+impl HeldItems {
+    pub fn give(&mut self, player: PlayerId, stack: ItemStack) {
+        let held = self.0.entry(player).or_default();
+        *held.counts.entry(stack.item_id).or_default() += stack.nb;
+        if let ItemType::Tool { durability } = stack.item_type {
+            held.durabilities
+                .entry(stack.item_id)
+                .or_default()
+                .extend(std::iter::repeat_n(durability, stack.nb as usize));
+        }
+    }
+
+    pub fn count(&self, player: PlayerId, item: ItemId) -> u32 {
+        self.0
+            .get(&player)
+            .and_then(|held| held.counts.get(&item))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn has(&self, player: PlayerId, item: ItemId) -> bool {
+        self.count(player, item) > 0
+    }
+
+    /// Uses up items of a player, only if they hold all of them
+    pub fn take_all(&mut self, player: PlayerId, taken: &[(ItemId, u32)]) -> bool {
+        let mut needed: HashMap<ItemId, u32> = HashMap::new();
+        for (item, nb) in taken.iter().filter(|(_, nb)| *nb > 0) {
+            *needed.entry(*item).or_default() += nb;
+        }
+        if needed
+            .iter()
+            .any(|(item, nb)| self.count(player, *item) < *nb)
+        {
+            return false;
+        }
+        let held = self.0.entry(player).or_default();
+        for (item, nb) in needed {
+            held.remove(item, nb);
+            held.take_worn_tools(item, nb);
+        }
+        true
+    }
+
+    /// Uses up as many of the items as the player holds, returning how many were taken
+    pub fn take_at_most(&mut self, player: PlayerId, item: ItemId, nb: u32) -> u32 {
+        let nb = nb.min(self.count(player, item));
+        self.take_all(player, &[(item, nb)]);
+        nb
+    }
+
+    /// Takes a stack out of the items of a player, if they hold it. Tools keep
+    /// the durability the server knows, the one of the client only tells them apart
+    pub fn take_stack(&mut self, player: PlayerId, stack: ItemStack) -> Option<ItemStack> {
+        let ItemType::Tool { durability } = stack.item_type else {
+            return self
+                .take_all(player, &[(stack.item_id, stack.nb)])
+                .then_some(stack);
+        };
+        // Tools do not stack
+        if stack.nb != 1 {
+            return None;
+        }
+        let held = self.0.get_mut(&player)?;
+        let durability = held.take_tool(stack.item_id, durability)?;
+        held.remove(stack.item_id, 1);
+        Some(ItemStack {
+            item_type: ItemType::Tool { durability },
+            ..stack
+        })
+    }
+
+    /// Uses a tool of a player once, the one closest to the durability the client knows.
+    /// It is removed once it has no durability left
+    pub fn wear(&mut self, player: PlayerId, tool: ItemStack) {
+        let ItemType::Tool { durability } = tool.item_type else {
+            return;
+        };
+        let Some(held) = self.0.get_mut(&player) else {
+            return;
+        };
+        let Some(durability) = held.take_tool(tool.item_id, durability) else {
+            return;
+        };
+        match durability.saturating_sub(1) {
+            0 => held.remove(tool.item_id, 1),
+            worn => held
+                .durabilities
+                .entry(tool.item_id)
+                .or_default()
+                .push(worn),
+        }
+    }
+
+    /// Items of a player as stacks, tools one by one, for their client to fill its inventory
+    pub fn stacks(&self, player: PlayerId, items: &ItemRegistry) -> Vec<ItemStack> {
+        let Some(held) = self.0.get(&player) else {
+            return Vec::new();
+        };
+        let mut stacks = Vec::new();
+        for (item, nb) in &held.counts {
+            match held.durabilities.get(item) {
+                Some(durabilities) => {
+                    stacks.extend(durabilities.iter().map(|durability| ItemStack {
+                        item_id: *item,
+                        item_type: ItemType::Tool {
+                            durability: *durability,
+                        },
+                        nb: 1,
+                    }))
+                }
+                None => stacks.push(ItemStack {
+                    item_id: *item,
+                    item_type: items.get(*item).default_type(),
+                    nb: *nb,
+                }),
+            }
+        }
+        stacks
+    }
+
+    pub fn to_saved(&self, items: &ItemRegistry) -> HashMap<PlayerId, Vec<SavedStack>> {
+        self.0
+            .keys()
+            .map(|player| {
+                let stacks = self
+                    .stacks(*player, items)
+                    .into_iter()
+                    .map(|stack| SavedStack {
+                        item: items.get(stack.item_id).name.clone(),
+                        nb: stack.nb,
+                        durability: match stack.item_type {
+                            ItemType::Tool { durability } => Some(durability),
+                            _ => None,
+                        },
+                    })
+                    .collect();
+                (*player, stacks)
+            })
+            .collect()
+    }
+
+    pub fn from_saved(saved: &HashMap<PlayerId, Vec<SavedStack>>, items: &ItemRegistry) -> Self {
+        let mut held = HeldItems::default();
+        for (player, stacks) in saved {
+            for stack in stacks {
+                // Items unknown to this build are dropped, like blocks
+                let Some(item_id) = items.id(&stack.item) else {
+                    warn!("Unknown item '{}' held by player {}", stack.item, player);
+                    continue;
+                };
+                let item_type = match items.get(item_id).default_type() {
+                    ItemType::Tool { durability: max } => ItemType::Tool {
+                        durability: stack.durability.unwrap_or(max).min(max),
+                    },
+                    default => default,
+                };
+                held.give(
+                    *player,
+                    ItemStack {
+                        item_id,
+                        item_type,
+                        nb: stack.nb,
+                    },
+                );
+            }
+        }
+        held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::world::BlockRegistry;
+
+    fn stack(items: &ItemRegistry, name: &str, nb: u32) -> ItemStack {
+        let item_id = items.id(name).unwrap();
+        ItemStack {
+            item_id,
+            item_type: items.get(item_id).default_type(),
+            nb,
+        }
+    }
+
+    #[test]
+    fn only_held_items_can_be_used() {
+        let items = ItemRegistry::builtin(&BlockRegistry::builtin());
+        let (stick, planks) = (items.id("stick").unwrap(), items.id("oak_planks").unwrap());
+        let mut held = HeldItems::default();
+        held.give(7, stack(&items, "oak_planks", 3));
+        held.give(7, stack(&items, "stick", 1));
+
+        assert!(!held.has(8, planks));
+        assert!(!held.take_all(7, &[(planks, 2), (stick, 1), (planks, 2)]));
+        assert_eq!(held.count(7, planks), 3);
+
+        assert!(held.take_all(7, &[(planks, 2), (stick, 1)]));
+        assert_eq!(held.count(7, planks), 1);
+        assert!(!held.has(7, stick));
+
+        assert_eq!(held.take_at_most(7, planks, 5), 1);
+        assert!(!held.has(7, planks));
+        assert_eq!(held.take_at_most(7, planks, 1), 0);
+    }
+
+    #[test]
+    fn tools_are_worn_out_on_the_server() {
+        let items = ItemRegistry::builtin(&BlockRegistry::builtin());
+        let pickaxe = stack(&items, "wooden_pickaxe", 1);
+        let ItemType::Tool { durability: max } = pickaxe.item_type else {
+            panic!("wooden_pickaxe is not a tool");
+        };
+        let worn = |durability| ItemStack {
+            item_type: ItemType::Tool { durability },
+            ..pickaxe
+        };
+        let mut held = HeldItems::default();
+        held.give(7, pickaxe);
+        held.give(7, worn(2));
+
+        // The tool the client uses is told apart by its durability
+        held.wear(7, worn(2));
+        held.wear(7, worn(1));
+        assert_eq!(held.count(7, pickaxe.item_id), 1);
+
+        // Thrown tools keep the durability of the server, whatever the client claims
+        held.wear(7, pickaxe);
+        assert_eq!(held.take_stack(7, pickaxe), Some(worn(max - 1)));
+        assert_eq!(held.take_stack(7, pickaxe), None);
+    }
+
+    #[test]
+    fn held_items_survive_saving() {
+        let items = ItemRegistry::builtin(&BlockRegistry::builtin());
+        let pickaxe = stack(&items, "stone_pickaxe", 1);
+        let mut held = HeldItems::default();
+        held.give(7, stack(&items, "dirt", 20));
+        held.give(7, pickaxe);
+        held.wear(7, pickaxe);
+
+        let mut saved = held.to_saved(&items);
+        saved.get_mut(&7).unwrap().push(SavedStack {
+            item: "removed_item".into(),
+            nb: 1,
+            durability: None,
+        });
+        let loaded = HeldItems::from_saved(&saved, &items);
+        let mut stacks = loaded.stacks(7, &items);
+        stacks.sort_by_key(|stack| stack.item_id);
+        let mut expected = held.stacks(7, &items);
+        expected.sort_by_key(|stack| stack.item_id);
+        assert_eq!(stacks, expected);
+        assert!(loaded.stacks(8, &items).is_empty());
+    }
+}
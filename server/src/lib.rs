@@ -1,4 +1,7 @@
+pub mod cli;
+mod crafting;
 mod init;
+mod inventory;
 mod network;
 mod player;
 pub mod time;
@@ -2,9 +2,14 @@ use std::net::Ipv4Addr;
 
 use crate::init::acquire_socket_by_port;
 use clap::Parser;
+use shared::messages::{ChunkCompression, CompressionAlgorithm};
+use shared::world::GeneratorSettings;
 use shared::GameServerConfig;
 
+mod cli;
+mod crafting;
 mod init;
+mod inventory;
 mod network;
 mod player;
 mod time;
@@ -19,12 +24,51 @@ struct Args {
     #[arg(short, long, default_value = "default")]
     world: String,
 
-    #[arg(short, long, default_value = "../")]
+    #[arg(short, long, default_value = "../", global = true)]
     game_folder_path: String,
+
+    /// Generator settings of the world if it does not exist yet, as RON text or a path
+    /// to a RON file, for example "(terrain_scale: 0.05, sea_level: 40)"
+    #[arg(long)]
+    generator_settings: Option<String>,
+
+    /// Compression of the chunks sent to clients : none, deflate or lz4
+    #[arg(long, default_value = "lz4")]
+    compression: CompressionAlgorithm,
+
+    /// Chunk payloads smaller than this many bytes are sent uncompressed
+    #[arg(long, default_value_t = ChunkCompression::default().threshold)]
+    compression_threshold: usize,
+
+    /// World management command to run instead of starting the server
+    #[command(subcommand)]
+    command: Option<cli::Command>,
 }
 
 fn main() {
     let args = Args::parse();
+
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command, args.game_folder_path) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let generator_settings = match args
+        .generator_settings
+        .as_deref()
+        .map(cli::parse_generator_settings)
+    {
+        Some(Ok(settings)) => settings,
+        Some(Err(e)) => {
+            eprintln!("Error: invalid generator settings: {}", e);
+            std::process::exit(1);
+        }
+        None => GeneratorSettings::default(),
+    };
+
     let socket = acquire_socket_by_port(std::net::IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), args.port);
 
     let game_folder_path = args.game_folder_path.clone();
@@ -34,6 +78,12 @@ fn main() {
         GameServerConfig {
             world_name: args.world,
             is_solo: false,
+            world_preset: Default::default(),
+            generator_settings,
+            chunk_compression: ChunkCompression {
+                algorithm: args.compression,
+                threshold: args.compression_threshold,
+            },
         },
         game_folder_path,
     );
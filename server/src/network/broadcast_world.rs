@@ -1,93 +1,184 @@
 use crate::init::ServerTime;
 use crate::init::TickCounter;
 use crate::network::utils::format_bytes;
-use crate::world::generation::generate_chunk;
+use crate::world::generation::{insert_generated_chunk, GeneratedChunk};
+use crate::world::generators::ActiveGenerator;
 use bevy::math::IVec3;
 use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
 use bevy_ecs::system::ResMut;
 use bevy_renet::renet::{ClientId, DefaultChannel, RenetServer};
 use bincode::Options;
-use shared::messages::{ServerToClientMessage, WorldUpdate};
+use shared::messages::{ChunkCompression, ChunkPayload, ServerToClientMessage, WorldUpdate};
 use shared::world::{chunk_in_radius, ServerChunk, ServerWorldMap};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use shared::world::data::WorldSeed;
+use shared::world::data::{WorldHeight, WorldSeed};
 
 #[derive(Event, Debug)]
 pub struct WorldUpdateRequestEvent {
     pub client: ClientId,
     pub chunks: Vec<IVec3>,
     pub render_distance: u32,
+    pub vertical_render_distance: u32,
     pub player_chunk_position: IVec3,
 }
 
+/// Chunk being generated in the background, and the clients waiting for it
+struct GenerationTask {
+    task: Task<GeneratedChunk>,
+    clients: HashSet<ClientId>,
+}
+
+/// Chunks being generated in the background, so that generation does not stall the server tick
+#[derive(Resource, Default)]
+pub struct ChunkGenerationTasks {
+    tasks: HashMap<IVec3, GenerationTask>,
+}
+
+impl ChunkGenerationTasks {
+    /// Starts generating a chunk for a client, unless it is already being generated
+    fn request(
+        &mut self,
+        chunk_pos: IVec3,
+        client: ClientId,
+        generator: &ActiveGenerator,
+        seed: u32,
+    ) {
+        let pending = self.tasks.entry(chunk_pos).or_insert_with(|| {
+            let generator = Arc::clone(&generator.0);
+            GenerationTask {
+                task: AsyncComputeTaskPool::get()
+                    .spawn(async move { generator.generate_chunk(chunk_pos, seed) }),
+                clients: HashSet::new(),
+            }
+        });
+        pending.clients.insert(client);
+    }
+}
+
 pub fn send_world_update(
     mut server: ResMut<RenetServer>,
     ticker: Res<TickCounter>,
     seed: Res<WorldSeed>,
-    mut world_map: ResMut<ServerWorldMap>,
+    generator: Res<ActiveGenerator>,
+    world_height: Res<WorldHeight>,
+    compression: Res<ChunkCompression>,
+    mut generation: ResMut<ChunkGenerationTasks>,
+    world_map: Res<ServerWorldMap>,
     mut ev_update: EventReader<WorldUpdateRequestEvent>,
 ) {
-    let mut chunks_to_update_count = 0;
     for event in ev_update.read() {
-        let payload = bincode::options()
-            .serialize(&ServerToClientMessage::WorldUpdate(WorldUpdate {
-                tick: ticker.tick,
-                player_positions: world_map.player_positions.clone(),
-                new_map: {
-                    let mut map: HashMap<IVec3, ServerChunk> = HashMap::new();
-                    for c in event.chunks.iter() {
-                        if chunk_in_radius(
-                            &event.player_chunk_position,
-                            c,
-                            event.render_distance as i32,
-                        ) {
-                            let chunk = world_map.map.get(c);
-
-                            // If chunk already exists, transmit it to client
-                            if let Some(chunk) = chunk {
-                                if chunk.map.is_empty() {
-                                    continue;
-                                }
-
-                                chunks_to_update_count += 1;
-                                map.insert(*c, chunk.clone());
-                            } else {
-                                // If chunk does not exists, generate it before transmitting it
-                                let chunk = generate_chunk(*c, seed.0);
-
-                                // If chunk is empty, do not create it to prevent unnecessary data transmission
-                                if chunk.map.is_empty() {
-                                    continue;
-                                }
-
-                                chunks_to_update_count += 1;
-                                map.insert(*c, chunk.clone());
-                                world_map.map.insert(*c, chunk);
-                            }
-                        }
-                    }
-                    trace!("Update event yippeee :D    len={}", map.len());
-                    map
-                },
-                time: world_map.time,
-            }))
-            .unwrap();
-
-        debug!(
-            "Broadcasting world state, number of chunks = {}, payload size: {}",
-            chunks_to_update_count,
-            format_bytes(payload.len() as u64)
+        let mut map: HashMap<IVec3, ServerChunk> = HashMap::new();
+        for c in event.chunks.iter() {
+            if !chunk_in_radius(
+                &event.player_chunk_position,
+                c,
+                event.render_distance as i32,
+                event.vertical_render_distance as i32,
+            ) || !world_height.contains_chunk(c.y)
+            {
+                continue;
+            }
+
+            // If chunk already exists, transmit it to client
+            if let Some(chunk) = world_map.map.get(c) {
+                if chunk.map.is_empty() {
+                    continue;
+                }
+                map.insert(*c, chunk.clone());
+            } else {
+                // If chunk does not exists, it is generated in the background
+                // and sent by `send_generated_chunks` once it is done
+                generation.request(*c, event.client, &generator, seed.0);
+            }
+        }
+        trace!("Update event yippeee :D    len={}", map.len());
+        send_chunks(
+            &mut server,
+            event.client,
+            &map,
+            &world_map,
+            ticker.tick,
+            &compression,
         );
-        server.send_message(event.client, DefaultChannel::ReliableUnordered, payload);
     }
 }
 
+/// Stores the chunks whose generation is done, and sends them to the clients waiting for them
+pub fn send_generated_chunks(
+    mut server: ResMut<RenetServer>,
+    ticker: Res<TickCounter>,
+    compression: Res<ChunkCompression>,
+    mut generation: ResMut<ChunkGenerationTasks>,
+    mut world_map: ResMut<ServerWorldMap>,
+) {
+    let mut maps: HashMap<ClientId, HashMap<IVec3, ServerChunk>> = HashMap::new();
+
+    generation.tasks.retain(|chunk_pos, pending| {
+        let Some(generated) = block_on(future::poll_once(&mut pending.task)) else {
+            // Keep the task until it is done
+            return true;
+        };
+
+        // If chunk is empty, it is not created to prevent unnecessary data transmission
+        if let Some(chunk) = insert_generated_chunk(&mut world_map, *chunk_pos, generated) {
+            for client in pending.clients.iter() {
+                maps.entry(*client)
+                    .or_default()
+                    .insert(*chunk_pos, chunk.clone());
+            }
+        }
+        false
+    });
+
+    for (client, map) in maps {
+        // The client may have left while its chunks were generated
+        if server.is_connected(client) {
+            send_chunks(
+                &mut server,
+                client,
+                &map,
+                &world_map,
+                ticker.tick,
+                &compression,
+            );
+        }
+    }
+}
+
+fn send_chunks(
+    server: &mut RenetServer,
+    client: ClientId,
+    map: &HashMap<IVec3, ServerChunk>,
+    world_map: &ServerWorldMap,
+    tick: u64,
+    compression: &ChunkCompression,
+) {
+    let payload = bincode::options()
+        .serialize(&ServerToClientMessage::WorldUpdate(WorldUpdate {
+            tick,
+            player_positions: world_map.player_positions.clone(),
+            new_map: ChunkPayload::new(map, compression.algorithm, compression.threshold),
+            time: world_map.time,
+        }))
+        .unwrap();
+
+    debug!(
+        "Broadcasting world state, number of chunks = {}, payload size: {}",
+        map.len(),
+        format_bytes(payload.len() as u64)
+    );
+    server.send_message(client, DefaultChannel::ReliableUnordered, payload);
+}
+
 pub fn broadcast_world_state(
     mut server: ResMut<RenetServer>,
     ticker: Res<TickCounter>,
     mut world_map: ResMut<ServerWorldMap>,
     time: Res<ServerTime>,
+    compression: Res<ChunkCompression>,
 ) {
     if ticker.tick % 10 != 0 {
         return;
@@ -101,24 +192,31 @@ pub fn broadcast_world_state(
         .serialize(&ServerToClientMessage::WorldUpdate(to_network(
             &mut world_map,
             ticker.tick,
+            &compression,
         )))
         .unwrap();
     server.broadcast_message(DefaultChannel::ReliableUnordered, payload);
 }
 
-fn to_network(world_map: &mut ServerWorldMap, tick: u64) -> WorldUpdate {
+fn to_network(
+    world_map: &mut ServerWorldMap,
+    tick: u64,
+    compression: &ChunkCompression,
+) -> WorldUpdate {
     WorldUpdate {
         tick,
         player_positions: world_map.player_positions.clone(),
         new_map: {
             let mut m: HashMap<IVec3, ServerChunk> = HashMap::new();
-            // Only send chunks that must be updated
+            // Only send chunks that must be updated, skipping positions missing from the map
             for v in world_map.chunks_to_update.iter() {
-                m.insert(*v, world_map.map.get(v).unwrap().clone());
+                if let Some(chunk) = world_map.map.get(v) {
+                    m.insert(*v, chunk.clone());
+                }
             }
             // Chunks are up do date, clear the vector
             world_map.chunks_to_update.clear();
-            m
+            ChunkPayload::new(&m, compression.algorithm, compression.threshold)
         },
         time: world_map.time,
     }
@@ -1,20 +1,24 @@
+use crate::crafting::handle_craft_request;
 use crate::init::{ServerLobby, TickCounter};
+use crate::inventory::HeldItems;
 use crate::network::broadcast_chat::*;
 use crate::network::broadcast_world::WorldUpdateRequestEvent;
 use crate::network::broadcast_world::*;
 use crate::player::handle_player_inputs;
 use crate::time::update_server_time;
 use crate::world;
+use crate::world::dropped_items::{validate_stack, DroppedItems};
+use crate::world::fluids::FluidUpdates;
 use crate::world::save::SaveRequestEvent;
-use crate::world::BlockInteractionEvent;
+use crate::world::{BlockBreaking, BlockBreakings, BlockInteractionEvent};
 use bevy::prelude::*;
 use bevy_renet::renet::{DefaultChannel, RenetServer, ServerEvent};
 use bincode::Options;
 use shared::messages::{
-    AuthRegisterResponse, ChatConversation, ClientToServerMessage, PlayerSpawnEvent,
+    AuthRegisterResponse, ChatConversation, ClientToServerMessage, ItemPickedUp, PlayerSpawnEvent,
     ServerToClientMessage,
 };
-use shared::world::ServerWorldMap;
+use shared::world::{BlockRegistry, ItemRegistry, RecipeRegistry, ServerWorldMap, WorldHeight};
 use shared::GameServerConfig;
 
 #[derive(Resource)]
@@ -26,6 +30,10 @@ pub fn setup_resources_and_events(app: &mut App) {
     app.insert_resource(BroadcastTimer {
         timer: Timer::from_seconds(2.0, TimerMode::Repeating),
     })
+    .insert_resource(ChunkGenerationTasks::default())
+    .insert_resource(FluidUpdates::default())
+    .insert_resource(BlockBreakings::default())
+    .insert_resource(DroppedItems::default())
     .add_event::<WorldUpdateRequestEvent>()
     .add_event::<SaveRequestEvent>()
     .add_event::<BlockInteractionEvent>();
@@ -38,11 +46,30 @@ pub fn register_systems(app: &mut App) {
 
     app.add_systems(Update, broadcast_chat_messages);
 
-    app.add_systems(Update, (broadcast_world_state, send_world_update));
+    app.add_systems(
+        Update,
+        (
+            broadcast_world_state,
+            send_world_update,
+            send_generated_chunks,
+        ),
+    );
 
     app.add_systems(Update, world::save::save_world_system);
     app.add_systems(Update, world::handle_block_interactions);
 
+    app.add_systems(Update, world::fluids::update_fluids);
+
+    app.add_systems(
+        Update,
+        (
+            world::dropped_items::update_dropped_items,
+            world::dropped_items::broadcast_dropped_items,
+        )
+            .chain()
+            .after(world::handle_block_interactions),
+    );
+
     app.add_systems(Update, update_server_time);
 }
 
@@ -67,8 +94,20 @@ fn server_update_system(
         EventWriter<SaveRequestEvent>,
         EventWriter<BlockInteractionEvent>,
     ),
-    config: Res<GameServerConfig>,
-    mut world_map: ResMut<ServerWorldMap>,
+    (config, world_height, blocks, items, recipes): (
+        Res<GameServerConfig>,
+        Res<WorldHeight>,
+        Res<BlockRegistry>,
+        Res<ItemRegistry>,
+        Res<RecipeRegistry>,
+    ),
+    (mut world_map, mut breakings, mut dropped_items, mut held_items, time): (
+        ResMut<ServerWorldMap>,
+        ResMut<BlockBreakings>,
+        ResMut<DroppedItems>,
+        ResMut<HeldItems>,
+        Res<Time>,
+    ),
 ) {
     for event in server_events.read() {
         debug!("event received");
@@ -120,6 +159,11 @@ fn server_update_system(
                         username: spawn_message.name.clone(),
                         session_token: client_id.raw() as u128,
                         spawn_event: spawn_message.clone(),
+                        world_height: *world_height,
+                        blocks: blocks.clone(),
+                        items: items.clone(),
+                        recipes: recipes.clone(),
+                        inventory: held_items.stacks(client_id.raw(), &items),
                     });
                     let auth_response_payload = bincode::options().serialize(msg).unwrap();
 
@@ -179,16 +223,19 @@ fn server_update_system(
                     player_chunk_position,
                     requested_chunks,
                     render_distance,
+                    vertical_render_distance,
                 } => {
                     debug!(
-                        "Received WorldUpdateRequest: client_id = {}, player_chunk_position = {:?}, render_distance = {}, requested_chunks = {}",
+                        "Received WorldUpdateRequest: client_id = {}, player_chunk_position = {:?}, render_distance = {}, vertical_render_distance = {}, requested_chunks = {}",
                         client_id,
                         player_chunk_position,
                         render_distance,
+                        vertical_render_distance,
                         requested_chunks.len(),
                     );
                     ev_world_update_request.send(WorldUpdateRequestEvent {
                         render_distance,
+                        vertical_render_distance,
                         client: client_id,
                         chunks: requested_chunks,
                         player_chunk_position,
@@ -203,14 +250,85 @@ fn server_update_system(
                         position, block_type
                     );
 
+                    // Taken now, as the player may start breaking another block before
+                    // the event is handled
+                    let breaking = match block_type {
+                        Some(_) => None,
+                        None => breakings.0.remove(&client_id.raw()),
+                    };
                     ev_block_interaction.send(BlockInteractionEvent {
+                        player: client_id.raw(),
                         position,
                         block_type,
+                        breaking,
+                    });
+                }
+                ClientToServerMessage::BlockBreakingStart { position, tool } => {
+                    // Players breaking with a tool they were never given use their hand
+                    let tool = tool.filter(|tool| {
+                        let held = held_items.has(client_id.raw(), tool.item_id);
+                        if !held {
+                            warn!("Player {} does not hold the tool {:?}", client_id, tool);
+                        }
+                        held
                     });
+                    breakings.0.insert(
+                        client_id.raw(),
+                        BlockBreaking {
+                            position,
+                            tool,
+                            started: time.elapsed_seconds(),
+                        },
+                    );
                 }
                 ClientToServerMessage::SetPlayerPosition { position } => {
                     world_map.player_positions.insert(client_id.raw(), position);
                 }
+                ClientToServerMessage::CraftRequest(request) => {
+                    let response = handle_craft_request(
+                        client_id.raw(),
+                        request,
+                        &recipes,
+                        (&blocks, &items),
+                        &world_map,
+                        &mut held_items,
+                    );
+                    let payload = bincode::options()
+                        .serialize(&ServerToClientMessage::CraftResponse(response))
+                        .unwrap();
+                    server.send_message(client_id, DefaultChannel::ReliableUnordered, payload);
+                }
+                ClientToServerMessage::DropItem { stack, direction } => {
+                    // Checked first, so that the items are not used up for nothing
+                    let Some(position) = world_map.player_positions.get(&client_id.raw()) else {
+                        warn!(
+                            "Player {} dropped items before having a position",
+                            client_id
+                        );
+                        continue;
+                    };
+                    // Only the items handed out to the player can be thrown
+                    let thrown = validate_stack(stack, &items)
+                        .and_then(|valid| held_items.take_stack(client_id.raw(), valid));
+                    match thrown {
+                        Some(thrown) => dropped_items.throw(thrown, *position, direction),
+                        None => warn!("Player {} dropped invalid items: {:?}", client_id, stack),
+                    }
+                }
+                ClientToServerMessage::PickUpItem { id } => {
+                    let Some(position) = world_map.player_positions.get(&client_id.raw()) else {
+                        continue;
+                    };
+                    // Another player may have been faster
+                    let Some(stack) = dropped_items.pick_up(id, *position) else {
+                        continue;
+                    };
+                    held_items.give(client_id.raw(), stack);
+                    let payload = bincode::options()
+                        .serialize(&ServerToClientMessage::ItemPickedUp(ItemPickedUp { stack }))
+                        .unwrap();
+                    server.send_message(client_id, DefaultChannel::ReliableUnordered, payload);
+                }
             }
         }
     }
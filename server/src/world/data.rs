@@ -1,14 +1,378 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use shared::world::ServerChunk;
-use std::collections::HashMap;
+use shared::messages::PlayerId;
+use shared::world::{
+    BlockData, BlockDirection, BlockId, BlockRegistry, GeneratorSettings, PalettedContainer,
+    ServerChunk, ServerWorldMap, WorldHeight, WorldPreset, WorldSeed, BIOMES_FILE, BLOCKS_FILE,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::inventory::SavedStack;
+use crate::world::structures::STRUCTURES_FILE;
 
 pub const SAVE_PATH: &str = "saves/";
 
+/// Version of the save layout written by this build.
+/// Bump it and add a migration step in `migration.rs` whenever `Save` changes
+pub const SAVE_FORMAT_VERSION: u32 = 12;
+
+/// Formats a world can be stored in, inside the saves folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum StorageFormat {
+    /// Human readable RON file
+    Ron,
+    /// Compact bincode file
+    Binary,
+}
+
+impl StorageFormat {
+    pub const ALL: [StorageFormat; 2] = [StorageFormat::Ron, StorageFormat::Binary];
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            StorageFormat::Ron => "ron",
+            StorageFormat::Binary => "bin",
+        }
+    }
+}
+
+/// Fingerprint of the data files terrain generation depends on. Unmodified chunks are not saved
+/// but generated again, so editing these files changes the terrain of existing worlds
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerrainDataHash(pub u64);
+
+impl TerrainDataHash {
+    /// Hashes the files of a data folder with FNV-1a, which gives the same result on every build.
+    /// Missing files are hashed as empty ones, since the built-in data is used instead
+    pub fn of_folder(assets_folder: &Path) -> Self {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for file in [BLOCKS_FILE, BIOMES_FILE, STRUCTURES_FILE] {
+            let contents = fs::read(assets_folder.join(file)).unwrap_or_default();
+            // The length separates the files, so that moving text between them changes the hash
+            for byte in (contents.len() as u64)
+                .to_le_bytes()
+                .iter()
+                .chain(&contents)
+            {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        TerrainDataHash(hash)
+    }
+}
+
+/// World state as used by the server at runtime
+pub struct WorldData {
+    pub seed: WorldSeed,
+    pub preset: WorldPreset,
+    pub settings: GeneratorSettings,
+    pub height: WorldHeight,
+    /// Data files the terrain was generated with, unknown for worlds saved before it was recorded
+    pub terrain_data: Option<TerrainDataHash>,
+    pub map: ServerWorldMap,
+    /// Items handed out to the players, kept by name like in saves
+    pub held_items: HashMap<PlayerId, Vec<SavedStack>>,
+    pub time: u64,
+}
+
+/// On-disk representation of a world
 #[derive(Serialize, Deserialize)]
 pub struct Save {
-    pub map: HashMap<IVec3, ServerChunk>,
-    // pub player_pos: Vec3,
-    // pub inventory: HashMap<RegistryId, Item>,
-    // pub id_to_item: HashMap<RegistryId, String>,
+    pub version: u32,
+    pub name: String,
+    pub seed: WorldSeed,
+    /// Unmodified chunks are generated again with it, so it can never change
+    pub preset: WorldPreset,
+    /// Like the preset, generation depends on them so they can never change
+    pub settings: GeneratorSettings,
+    pub height: WorldHeight,
+    pub time: u64,
+    /// Like the preset, the generated terrain depends on the data files
+    pub terrain_data: Option<TerrainDataHash>,
+    /// Maps the numeric block IDs stored in chunks to stable block names
+    pub palette: Vec<String>,
+    pub chunks: HashMap<IVec3, SavedChunk>,
+    /// Blocks of features placed in other chunks whenever those are generated.
+    /// Only kept for unsaved chunks receiving features from chunks in `features_placed`
+    pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+    /// Chunks whose features crossing their borders were already placed, with the chunks these
+    /// features went into. Only kept for chunks that are saved or whose features went into saved
+    /// chunks, the others place their features again when generated
+    pub features_placed: HashMap<IVec3, HashSet<IVec3>>,
+    pub player_positions: HashMap<PlayerId, Vec3>,
+    pub held_items: HashMap<PlayerId, Vec<SavedStack>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedChunk {
+    pub blocks: PalettedContainer<SavedBlock>,
+    pub ts: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SavedBlock {
+    /// Index in the palette of the save
+    pub id: u16,
+    pub flipped: bool,
+    pub direction: BlockDirection,
+    pub level: u8,
+    pub open: bool,
+}
+
+impl Save {
+    /// Only modified chunks are stored, the others are generated again from the seed
+    pub fn from_world(world_data: &WorldData, registry: &BlockRegistry) -> Self {
+        let mut palette: Vec<String> = Vec::new();
+        let mut palette_ids: HashMap<BlockId, u16> = HashMap::new();
+        let mut save_blocks = |blocks: &PalettedContainer| {
+            blocks.convert(|block| {
+                let id = *palette_ids.entry(block.id).or_insert_with(|| {
+                    palette.push(registry.get(block.id).name.clone());
+                    (palette.len() - 1) as u16
+                });
+                Some(SavedBlock {
+                    id,
+                    flipped: block.flipped,
+                    direction: block.direction,
+                    level: block.level,
+                    open: block.open,
+                })
+            })
+        };
+
+        let chunks = world_data
+            .map
+            .map
+            .iter()
+            .filter(|(_, chunk)| chunk.modified)
+            .map(|(chunk_pos, chunk)| {
+                (
+                    *chunk_pos,
+                    SavedChunk {
+                        blocks: save_blocks(&chunk.map),
+                        ts: chunk.ts,
+                    },
+                )
+            })
+            .collect();
+
+        // Exploring without editing anything must not make the save grow
+        let saved = |chunk_pos: &IVec3| {
+            world_data
+                .map
+                .map
+                .get(chunk_pos)
+                .is_some_and(|chunk| chunk.modified)
+        };
+        let features_placed: HashMap<IVec3, HashSet<IVec3>> = world_data
+            .map
+            .features_placed
+            .iter()
+            .filter(|(chunk_pos, targets)| saved(chunk_pos) || targets.iter().any(saved))
+            .map(|(chunk_pos, targets)| (*chunk_pos, targets.clone()))
+            .collect();
+        // Saved chunks already contain the features of their neighbours
+        let targets: HashSet<IVec3> = features_placed.values().flatten().copied().collect();
+        let pending_writes = world_data
+            .map
+            .pending_writes
+            .iter()
+            .filter(|(chunk_pos, _)| targets.contains(chunk_pos) && !saved(chunk_pos))
+            .map(|(chunk_pos, blocks)| (*chunk_pos, save_blocks(blocks)))
+            .collect();
+
+        Save {
+            version: SAVE_FORMAT_VERSION,
+            name: world_data.map.name.clone(),
+            seed: world_data.seed.clone(),
+            preset: world_data.preset.clone(),
+            settings: world_data.settings.clone(),
+            height: world_data.height,
+            time: world_data.time,
+            terrain_data: world_data.terrain_data,
+            palette,
+            chunks,
+            pending_writes,
+            features_placed,
+            player_positions: world_data.map.player_positions.clone(),
+            held_items: world_data.held_items.clone(),
+        }
+    }
+
+    pub fn into_world(self, registry: &BlockRegistry) -> WorldData {
+        // Blocks unknown to this build are dropped instead of making the whole world unloadable
+        let palette: Vec<Option<BlockId>> = self
+            .palette
+            .iter()
+            .map(|name| {
+                let id = registry.id(name);
+                if id.is_none() {
+                    warn!("Unknown block '{}' in save, it will be removed", name);
+                }
+                id
+            })
+            .collect();
+
+        let load_blocks = |blocks: &PalettedContainer<SavedBlock>| {
+            blocks.convert(|block| {
+                let id = (*palette.get(block.id as usize)?)?;
+                Some(BlockData {
+                    level: block.level,
+                    open: block.open,
+                    ..BlockData::new(id, block.flipped, block.direction)
+                })
+            })
+        };
+
+        let map = self
+            .chunks
+            .into_iter()
+            .map(|(chunk_pos, chunk)| {
+                (
+                    chunk_pos,
+                    ServerChunk {
+                        map: load_blocks(&chunk.blocks),
+                        ts: chunk.ts,
+                        tints: Vec::new(),
+                        modified: true,
+                    },
+                )
+            })
+            .collect();
+
+        let pending_writes = self
+            .pending_writes
+            .iter()
+            .map(|(chunk_pos, blocks)| (*chunk_pos, load_blocks(blocks)))
+            .collect();
+
+        WorldData {
+            seed: self.seed,
+            preset: self.preset,
+            settings: self.settings,
+            height: self.height,
+            terrain_data: self.terrain_data,
+            map: ServerWorldMap {
+                name: self.name,
+                map,
+                pending_writes,
+                features_placed: self.features_placed,
+                player_positions: self.player_positions,
+                time: self.time,
+                ..Default::default()
+            },
+            held_items: self.held_items,
+            time: self.time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::migration::{parse_binary_save, parse_save};
+    use bincode::Options;
+
+    #[test]
+    fn modified_chunks_survive_saving_and_loading() {
+        let blocks = BlockRegistry::builtin();
+        let block = |name: &str, flipped: bool, direction: BlockDirection| {
+            BlockData::new(blocks.id(name).unwrap(), flipped, direction)
+        };
+        let stone = block("stone", false, BlockDirection::Front);
+        let door = BlockData {
+            open: true,
+            ..block("oak_door", true, BlockDirection::Left)
+        };
+        let water = BlockData {
+            level: 3,
+            ..block("water", false, BlockDirection::Front)
+        };
+
+        let mut map = ServerWorldMap {
+            name: "round_trip".into(),
+            ..Default::default()
+        };
+        map.set_block(&IVec3::new(1, 2, 3), stone);
+        map.set_block(&IVec3::new(-5, 40, 20), door);
+        map.set_block(&IVec3::new(-5, 41, 20), water);
+        // Chunks that were only generated are not saved
+        let mut generated = ServerChunk::default();
+        generated.map.insert(IVec3::ZERO, stone);
+        map.map.insert(IVec3::new(5, 5, 5), generated);
+        let mut pending = PalettedContainer::default();
+        pending.insert(IVec3::new(4, 4, 4), stone);
+        map.pending_writes.insert(IVec3::new(9, 0, 9), pending);
+        // Its features went into a saved chunk, and one that is not generated yet
+        map.features_placed.insert(
+            IVec3::new(5, 5, 5),
+            HashSet::from([IVec3::ZERO, IVec3::new(9, 0, 9)]),
+        );
+        map.player_positions.insert(1, Vec3::new(0.5, 70., 0.5));
+        let pickaxe = SavedStack {
+            item: "stone_pickaxe".into(),
+            nb: 1,
+            durability: Some(12),
+        };
+
+        let world = WorldData {
+            seed: WorldSeed(42),
+            preset: WorldPreset::Default,
+            settings: GeneratorSettings::default(),
+            height: WorldHeight::default(),
+            terrain_data: Some(TerrainDataHash(5)),
+            map,
+            held_items: HashMap::from([(1, vec![pickaxe])]),
+            time: 77,
+        };
+        let save = Save::from_world(&world, &blocks);
+        let ron = ron::to_string(&save).unwrap();
+        let binary = bincode::options().serialize(&save).unwrap();
+
+        for loaded in [
+            parse_save(&ron).unwrap(),
+            parse_binary_save(&binary).unwrap(),
+        ] {
+            let loaded = loaded.into_world(&blocks);
+            assert_eq!(loaded.seed.0, 42);
+            assert_eq!(loaded.time, 77);
+            assert_eq!(loaded.terrain_data, Some(TerrainDataHash(5)));
+            assert_eq!(loaded.map.name, "round_trip");
+            assert_eq!(loaded.map.map.len(), world.map.map.len() - 1);
+            assert!(!loaded.map.map.contains_key(&IVec3::new(5, 5, 5)));
+
+            let get = |x, y, z| loaded.map.get_block_by_coordinates(&IVec3::new(x, y, z));
+            assert_eq!(get(1, 2, 3), Some(&stone));
+            assert_eq!(get(-5, 40, 20), Some(&door));
+            assert_eq!(get(-5, 41, 20), Some(&water));
+            assert_eq!(get(0, 0, 0), None);
+            assert_eq!(
+                loaded.map.pending_writes[&IVec3::new(9, 0, 9)].get(&IVec3::new(4, 4, 4)),
+                Some(&stone)
+            );
+            assert_eq!(loaded.map.features_placed, world.map.features_placed);
+            assert_eq!(loaded.map.player_positions, world.map.player_positions);
+            assert_eq!(loaded.held_items, world.held_items);
+        }
+    }
+
+    #[test]
+    fn terrain_data_hash_follows_the_data_files() {
+        let folder = std::env::temp_dir().join("rustcraft_terrain_data_test");
+        fs::create_dir_all(&folder).unwrap();
+        let _ = fs::remove_file(folder.join(BIOMES_FILE));
+        let empty = TerrainDataHash::of_folder(&folder);
+
+        fs::write(folder.join(BIOMES_FILE), "[]").unwrap();
+        let biomes = TerrainDataHash::of_folder(&folder);
+        assert_ne!(biomes, empty);
+        assert_eq!(TerrainDataHash::of_folder(&folder), biomes);
+
+        fs::write(folder.join(BIOMES_FILE), "[ ]").unwrap();
+        assert_ne!(TerrainDataHash::of_folder(&folder), biomes);
+        fs::remove_file(folder.join(BIOMES_FILE)).unwrap();
+    }
 }
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+
+use crate::init::TickCounter;
+use bevy::prelude::*;
+use bevy_renet::renet::{DefaultChannel, RenetServer};
+use bincode::Options;
+use rand::Rng;
+use shared::messages::{DroppedItem, DroppedItemsUpdate, ServerToClientMessage};
+use shared::world::{
+    BlockRegistry, ItemRegistry, ItemStack, ItemType, ServerWorldMap, WorldHeight,
+};
+
+/// Seconds before an item lying in the world disappears
+const ITEM_LIFETIME: f32 = 300.;
+/// Seconds before thrown items can be picked up, so that they do not go back right away
+/// to the player who threw them
+const THROWN_PICKUP_DELAY: f32 = 1.5;
+/// Seconds before the items dropped by broken blocks can be picked up
+const BLOCK_DROP_PICKUP_DELAY: f32 = 0.5;
+/// Farthest a player can be from the items they pick up. A bit more than the distance used
+/// by clients, since the server only knows their last sent position
+const MAX_PICKUP_DISTANCE: f32 = 2.5;
+/// Items of the same kind closer than this are merged into one stack
+const MERGE_DISTANCE: f32 = 1.;
+/// Half of the height of items, which rest on the blocks below them
+const ITEM_HALF_SIZE: f32 = 0.2;
+const GRAVITY: f32 = 20.;
+const MAX_FALL_SPEED: f32 = 40.;
+/// Horizontal speed lost per second by items lying on the ground
+const GROUND_FRICTION: f32 = 8.;
+/// Speed of thrown items
+const THROW_SPEED: f32 = 6.;
+/// Items are sent to the clients at most once every this many ticks
+const BROADCAST_INTERVAL: u64 = 3;
+/// Items are sent every this many ticks even when nothing moved, for players who just joined
+const FULL_BROADCAST_INTERVAL: u64 = 60;
+
+/// Stack of items simulated by the server
+#[derive(Debug)]
+struct ItemEntity {
+    stack: ItemStack,
+    position: Vec3,
+    velocity: Vec3,
+    /// Seconds since the item was dropped
+    age: f32,
+    pickup_delay: f32,
+}
+
+impl ItemEntity {
+    fn is_pickable(&self) -> bool {
+        self.age >= self.pickup_delay
+    }
+}
+
+/// Items lying in the world. They are not part of the save, and vanish when the server stops
+#[derive(Resource, Debug, Default)]
+pub struct DroppedItems {
+    next_id: u64,
+    items: HashMap<u64, ItemEntity>,
+    /// Items changed since they were last sent to the clients
+    changed: bool,
+}
+
+impl DroppedItems {
+    pub fn spawn(&mut self, stack: ItemStack, position: Vec3, velocity: Vec3, pickup_delay: f32) {
+        self.items.insert(
+            self.next_id,
+            ItemEntity {
+                stack,
+                position,
+                velocity,
+                age: 0.,
+                pickup_delay,
+            },
+        );
+        self.next_id += 1;
+        self.changed = true;
+    }
+
+    /// Drops the items of a broken block from its center, bouncing a bit
+    pub fn spawn_block_drop(&mut self, stack: ItemStack, block: IVec3) {
+        let mut rng = rand::thread_rng();
+        let velocity = Vec3::new(rng.gen_range(-1.0..1.0), 3., rng.gen_range(-1.0..1.0));
+        self.spawn(
+            stack,
+            block.as_vec3() + Vec3::splat(0.5),
+            velocity,
+            BLOCK_DROP_PICKUP_DELAY,
+        );
+    }
+
+    /// Throws a stack of a player in a direction. The stack must come from `validate_stack`
+    pub fn throw(&mut self, stack: ItemStack, player_position: Vec3, direction: Vec3) {
+        // Thrown from the eyes of the player, a bit upwards
+        let velocity = direction.normalize_or_zero() * THROW_SPEED + Vec3::Y * 2.;
+        self.spawn(
+            stack,
+            player_position + Vec3::Y * 0.6,
+            velocity,
+            THROWN_PICKUP_DELAY,
+        );
+    }
+
+    /// Removes an item picked up by a player, if they are allowed to take it
+    pub fn pick_up(&mut self, id: u64, player_position: Vec3) -> Option<ItemStack> {
+        let item = self.items.get(&id)?;
+        if !item.is_pickable() || item.position.distance(player_position) > MAX_PICKUP_DISTANCE {
+            return None;
+        }
+        self.changed = true;
+        self.items.remove(&id).map(|item| item.stack)
+    }
+
+    /// Moves the items for `delta` seconds, then merges the nearby stacks of the same item
+    fn simulate(
+        &mut self,
+        delta: f32,
+        world_map: &ServerWorldMap,
+        blocks: &BlockRegistry,
+        items: &ItemRegistry,
+        min_y: f32,
+    ) {
+        let mut changed = false;
+        self.items.retain(|_, item| {
+            let (position, was_pickable) = (item.position, item.is_pickable());
+            item.age += delta;
+            move_item(item, delta, world_map, blocks);
+            changed |= item.position != position || item.is_pickable() != was_pickable;
+
+            // Items falling out of the world are lost
+            let alive = item.age < ITEM_LIFETIME && item.position.y >= min_y;
+            changed |= !alive;
+            alive
+        });
+
+        // Stacks are merged into the oldest one. Items are sorted into cells as large as the
+        // merge distance, so that each of them is only compared to the ones of the cells around
+        let mut cells: HashMap<IVec3, Vec<u64>> = HashMap::new();
+        for (id, item) in &self.items {
+            cells
+                .entry(merge_cell(item.position))
+                .or_default()
+                .push(*id);
+        }
+        let mut ids: Vec<u64> = self.items.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let Some(cell) = self.items.get(&id).map(|item| merge_cell(item.position)) else {
+                continue;
+            };
+            let mut others: Vec<u64> = NEIGHBOUR_CELLS
+                .iter()
+                .filter_map(|offset| cells.get(&(cell + *offset)))
+                .flatten()
+                .copied()
+                .filter(|other| *other > id)
+                .collect();
+            others.sort_unstable();
+            for other in &others {
+                let (Some(item), Some(other_item)) = (self.items.get(&id), self.items.get(other))
+                else {
+                    continue;
+                };
+                if item.stack.item_id != other_item.stack.item_id
+                    || item.stack.item_type != other_item.stack.item_type
+                    || item.stack.nb + other_item.stack.nb > items.get(item.stack.item_id).max_stack
+                    || item.position.distance(other_item.position) > MERGE_DISTANCE
+                {
+                    continue;
+                }
+                let nb = other_item.stack.nb;
+                self.items.remove(other);
+                self.items.get_mut(&id).unwrap().stack.nb += nb;
+                changed = true;
+            }
+        }
+
+        self.changed |= changed;
+    }
+
+    fn to_network(&self, tick: u64) -> DroppedItemsUpdate {
+        DroppedItemsUpdate {
+            tick,
+            items: self
+                .items
+                .iter()
+                .map(|(id, item)| DroppedItem {
+                    id: *id,
+                    stack: item.stack,
+                    position: item.position,
+                    pickable: item.is_pickable(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Cell of the items that may be merged with the ones of the cell and of its neighbours
+fn merge_cell(position: Vec3) -> IVec3 {
+    (position / MERGE_DISTANCE).floor().as_ivec3()
+}
+
+/// Offsets of a cell and of its neighbours
+const NEIGHBOUR_CELLS: [IVec3; 27] = {
+    let mut offsets = [IVec3::ZERO; 27];
+    let mut index = 0;
+    while index < 27 {
+        let i = index as i32;
+        offsets[index] = IVec3::new(i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1);
+        index += 1;
+    }
+    offsets
+};
+
+/// Checks a stack sent by a client : its item must exist and fit in one stack.
+/// Whether the player holds the items is checked separately, with `HeldItems`, which also
+/// knows the actual durability of tools. Other types are reset, clients cannot choose them
+pub fn validate_stack(stack: ItemStack, items: &ItemRegistry) -> Option<ItemStack> {
+    if !items.contains(stack.item_id) {
+        return None;
+    }
+    let definition = items.get(stack.item_id);
+    if stack.nb == 0 || stack.nb > definition.max_stack {
+        return None;
+    }
+    let item_type = match (definition.default_type(), stack.item_type) {
+        (ItemType::Tool { durability: max }, ItemType::Tool { durability }) => ItemType::Tool {
+            durability: durability.min(max),
+        },
+        (default, _) => default,
+    };
+    Some(ItemStack { item_type, ..stack })
+}
+
+/// Height of the top of the collision box containing a point, such as the lower half of slabs
+fn solid_top(world_map: &ServerWorldMap, blocks: &BlockRegistry, position: Vec3) -> Option<f32> {
+    let floor = position.floor();
+    let block = world_map.get_block_by_coordinates(&floor.as_ivec3())?;
+    if !blocks.contains(block.id) {
+        return None;
+    }
+    blocks
+        .get(block.id)
+        .collision_boxes(block)
+        .iter()
+        .find(|shape_box| shape_box.contains(position - floor))
+        .map(|shape_box| floor.y + shape_box.max.y)
+}
+
+fn is_solid(world_map: &ServerWorldMap, blocks: &BlockRegistry, position: Vec3) -> bool {
+    solid_top(world_map, blocks, position).is_some()
+}
+
+/// Moves an item for `delta` seconds, one axis after the other so that it slides along walls
+fn move_item(
+    item: &mut ItemEntity,
+    delta: f32,
+    world_map: &ServerWorldMap,
+    blocks: &BlockRegistry,
+) {
+    item.velocity.y = (item.velocity.y - GRAVITY * delta).max(-MAX_FALL_SPEED);
+
+    for axis in [Vec3::X, Vec3::Z] {
+        let next = item.position + axis * item.velocity * delta;
+        if is_solid(world_map, blocks, next) {
+            item.velocity -= axis * item.velocity;
+        } else {
+            item.position = next;
+        }
+    }
+
+    let next = item.position + Vec3::Y * item.velocity.y * delta;
+    let bottom = next - Vec3::Y * ITEM_HALF_SIZE;
+    let ground = solid_top(world_map, blocks, bottom).filter(|_| item.velocity.y <= 0.);
+    if let Some(ground) = ground {
+        // Resting on the block below
+        item.position.y = ground + ITEM_HALF_SIZE;
+        item.velocity.y = 0.;
+        let friction = (1. - GROUND_FRICTION * delta).max(0.);
+        item.velocity.x *= friction;
+        item.velocity.z *= friction;
+        if item.velocity.length_squared() < 0.01 {
+            item.velocity = Vec3::ZERO;
+        }
+    } else if item.velocity.y > 0. && is_solid(world_map, blocks, next + Vec3::Y * ITEM_HALF_SIZE) {
+        item.velocity.y = 0.;
+    } else {
+        item.position = next;
+    }
+}
+
+pub fn update_dropped_items(
+    mut dropped: ResMut<DroppedItems>,
+    world_map: Res<ServerWorldMap>,
+    world_height: Res<WorldHeight>,
+    (blocks, items): (Res<BlockRegistry>, Res<ItemRegistry>),
+    time: Res<Time>,
+) {
+    dropped.simulate(
+        time.delta_seconds(),
+        &world_map,
+        &blocks,
+        &items,
+        world_height.min_y as f32 - 64.,
+    );
+}
+
+pub fn broadcast_dropped_items(
+    mut server: ResMut<RenetServer>,
+    mut dropped: ResMut<DroppedItems>,
+    ticker: Res<TickCounter>,
+) {
+    if ticker.tick % BROADCAST_INTERVAL != 0
+        || !(dropped.changed || ticker.tick % FULL_BROADCAST_INTERVAL == 0)
+    {
+        return;
+    }
+    dropped.changed = false;
+
+    let payload = bincode::options()
+        .serialize(&ServerToClientMessage::DroppedItems(
+            dropped.to_network(ticker.tick),
+        ))
+        .unwrap();
+    server.broadcast_message(DefaultChannel::ReliableUnordered, payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::world::{BlockData, BlockDirection};
+
+    #[test]
+    fn items_land_on_blocks_and_merge() {
+        let blocks = BlockRegistry::builtin();
+        let items = ItemRegistry::builtin(&blocks);
+        let mut world_map = ServerWorldMap::default();
+        for x in -2..=2 {
+            for z in -2..=2 {
+                let stone =
+                    BlockData::new(blocks.id("stone").unwrap(), false, BlockDirection::Front);
+                world_map.set_block(&IVec3::new(x, 10, z), stone);
+            }
+        }
+
+        let dirt = items.id("dirt").unwrap();
+        let stack = ItemStack {
+            item_id: dirt,
+            item_type: items.get(dirt).default_type(),
+            nb: 40,
+        };
+        let mut dropped = DroppedItems::default();
+        dropped.spawn(stack, Vec3::new(0.5, 15., 0.5), Vec3::ZERO, 0.);
+        dropped.spawn(stack, Vec3::new(0.9, 13., 0.5), Vec3::ZERO, 0.);
+        dropped.spawn(
+            ItemStack { nb: 20, ..stack },
+            Vec3::new(0.5, 12., 0.9),
+            Vec3::ZERO,
+            0.,
+        );
+        for _ in 0..100 {
+            dropped.simulate(0.05, &world_map, &blocks, &items, 0.);
+        }
+
+        // The first two stacks would exceed the max stack : the third one is merged instead
+        let mut counts: Vec<u32> = dropped.items.values().map(|item| item.stack.nb).collect();
+        counts.sort();
+        assert_eq!(counts, vec![40, 60]);
+        assert!(dropped
+            .items
+            .values()
+            .all(|item| (item.position.y - 11. - ITEM_HALF_SIZE).abs() < 0.01));
+    }
+
+    #[test]
+    fn only_close_stacks_merge() {
+        let blocks = BlockRegistry::builtin();
+        let items = ItemRegistry::builtin(&blocks);
+        let dirt = items.id("dirt").unwrap();
+        let stack = ItemStack {
+            item_id: dirt,
+            item_type: items.get(dirt).default_type(),
+            nb: 1,
+        };
+        let mut dropped = DroppedItems::default();
+        // Pairs on both sides of cell borders, far from each other
+        for x in 0..50 {
+            let x = x as f32 * 4.;
+            dropped.spawn(stack, Vec3::new(x - 0.1, 0., 0.), Vec3::ZERO, 0.);
+            dropped.spawn(stack, Vec3::new(x + 0.1, 0.5, -0.5), Vec3::ZERO, 0.);
+        }
+        dropped.simulate(0., &ServerWorldMap::default(), &blocks, &items, -10.);
+
+        assert_eq!(dropped.items.len(), 50);
+        assert!(dropped.items.values().all(|item| item.stack.nb == 2));
+        // Merged into the oldest stack of each pair
+        assert!(dropped.items.keys().all(|id| id % 2 == 0));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::init::TickCounter;
+use bevy::prelude::*;
+use shared::world::{global_block_to_chunk_pos, BlockData, BlockRegistry, ServerWorldMap};
+use std::collections::{HashSet, VecDeque};
+
+/// Fluids spread this many blocks away from their source
+const MAX_FLUID_LEVEL: u8 = 7;
+/// Fluids move once every this many ticks
+const FLUID_TICK_INTERVAL: u64 = 5;
+/// Maximum number of positions updated per fluid tick, the others wait for the next ticks
+const FLUID_UPDATE_BUDGET: usize = 256;
+
+const HORIZONTAL_OFFSETS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Positions where a fluid may have to spread or recede
+#[derive(Resource, Default, Debug)]
+pub struct FluidUpdates {
+    queue: VecDeque<IVec3>,
+    queued: HashSet<IVec3>,
+}
+
+impl FluidUpdates {
+    /// Schedules an update of a position and of its 6 neighbours
+    pub fn schedule_around(&mut self, position: IVec3) {
+        self.schedule(position);
+        for offset in HORIZONTAL_OFFSETS.iter().chain(&[IVec3::Y, IVec3::NEG_Y]) {
+            self.schedule(position + *offset);
+        }
+    }
+
+    fn schedule(&mut self, position: IVec3) {
+        if self.queued.insert(position) {
+            self.queue.push_back(position);
+        }
+    }
+}
+
+/// Fluid that a position should contain according to its neighbours, if any
+fn expected_fluid(
+    world_map: &ServerWorldMap,
+    blocks: &BlockRegistry,
+    position: IVec3,
+) -> Option<BlockData> {
+    let is_liquid = |block: &BlockData| blocks.get(block.id).is_liquid();
+
+    // Fluids falling from above
+    if let Some(above) = world_map.get_block_by_coordinates(&(position + IVec3::Y)) {
+        if is_liquid(above) {
+            return Some(BlockData::flowing(above.id, 1));
+        }
+    }
+
+    // Fluids spreading from the sides, which only happens when they rest on something
+    HORIZONTAL_OFFSETS
+        .iter()
+        .filter_map(|offset| {
+            let neighbour = world_map.get_block_by_coordinates(&(position + *offset))?;
+            if !is_liquid(neighbour) || neighbour.level >= MAX_FLUID_LEVEL {
+                return None;
+            }
+            let below = world_map.get_block_by_coordinates(&(position + *offset - IVec3::Y))?;
+            if is_liquid(below) && below.level != 0 {
+                return None;
+            }
+            Some(BlockData::flowing(neighbour.id, neighbour.level + 1))
+        })
+        .min_by_key(|fluid| fluid.level)
+}
+
+pub fn update_fluids(
+    mut world_map: ResMut<ServerWorldMap>,
+    mut fluids: ResMut<FluidUpdates>,
+    ticker: Res<TickCounter>,
+    blocks: Res<BlockRegistry>,
+) {
+    if ticker.tick % FLUID_TICK_INTERVAL != 0 {
+        return;
+    }
+    flow(&mut world_map, &mut fluids, &blocks);
+}
+
+/// Updates the positions scheduled before this fluid tick, up to `FLUID_UPDATE_BUDGET` of them
+fn flow(world_map: &mut ServerWorldMap, fluids: &mut FluidUpdates, blocks: &BlockRegistry) {
+    // Positions scheduled during this tick are only handled by the next ones,
+    // so that fluids advance by one block per fluid tick
+    let count = fluids.queue.len().min(FLUID_UPDATE_BUDGET);
+    for _ in 0..count {
+        let Some(position) = fluids.queue.pop_front() else {
+            break;
+        };
+        fluids.queued.remove(&position);
+
+        // Fluids never flow into chunks that are not loaded
+        if !world_map
+            .map
+            .contains_key(&global_block_to_chunk_pos(&position))
+        {
+            continue;
+        }
+
+        let current = world_map.get_block_by_coordinates(&position).copied();
+        let expected = match current {
+            // Solid blocks and sources are left untouched
+            Some(block) if !blocks.get(block.id).is_liquid() || block.level == 0 => continue,
+            _ => expected_fluid(world_map, blocks, position),
+        };
+        if expected == current {
+            continue;
+        }
+
+        // Marks the chunk of the position as modified and to be sent to clients
+        match expected {
+            Some(fluid) => world_map.set_block(&position, fluid),
+            None => {
+                world_map.remove_block_by_coordinates(&position);
+            }
+        }
+        fluids.schedule_around(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::world::BlockDirection;
+
+    /// Stone floor at y = 0 over two chunks, with a water source on top of it
+    fn pool(blocks: &BlockRegistry, source: IVec3) -> (ServerWorldMap, FluidUpdates) {
+        let stone = BlockData::new(blocks.id("stone").unwrap(), false, BlockDirection::Front);
+        let water = BlockData::new(blocks.id("water").unwrap(), false, BlockDirection::Front);
+        let mut world_map = ServerWorldMap::default();
+        for x in 0..32 {
+            for z in 0..16 {
+                world_map.set_block(&IVec3::new(x, 0, z), stone);
+            }
+        }
+        world_map.set_block(&source, water);
+        let mut fluids = FluidUpdates::default();
+        fluids.schedule_around(source);
+        (world_map, fluids)
+    }
+
+    fn flow_until_still(world_map: &mut ServerWorldMap, fluids: &mut FluidUpdates) {
+        let blocks = BlockRegistry::builtin();
+        for _ in 0..100 {
+            if fluids.queue.is_empty() {
+                return;
+            }
+            flow(world_map, fluids, &blocks);
+        }
+        panic!("fluids never stopped flowing");
+    }
+
+    fn level(world_map: &ServerWorldMap, position: IVec3) -> Option<u8> {
+        world_map
+            .get_block_by_coordinates(&position)
+            .map(|block| block.level)
+    }
+
+    #[test]
+    fn fluids_flow_into_broken_blocks() {
+        let blocks = BlockRegistry::builtin();
+        let source = IVec3::new(8, 1, 8);
+        let (mut world_map, mut fluids) = pool(&blocks, source);
+        let wall = source + IVec3::X;
+        let stone = *world_map.get_block_by_coordinates(&IVec3::ZERO).unwrap();
+        world_map.set_block(&wall, stone);
+        flow_until_still(&mut world_map, &mut fluids);
+        assert_eq!(world_map.get_block_by_coordinates(&wall), Some(&stone));
+        // Water goes around the wall
+        assert_eq!(level(&world_map, wall + IVec3::X), Some(4));
+
+        world_map.remove_block_by_coordinates(&wall);
+        fluids.schedule_around(wall);
+        flow(&mut world_map, &mut fluids, &blocks);
+        assert_eq!(level(&world_map, wall), Some(1));
+        flow_until_still(&mut world_map, &mut fluids);
+        assert_eq!(level(&world_map, wall + IVec3::X), Some(2));
+    }
+
+    #[test]
+    fn fluids_stop_at_the_level_limit() {
+        let blocks = BlockRegistry::builtin();
+        let source = IVec3::new(8, 1, 8);
+        let (mut world_map, mut fluids) = pool(&blocks, source);
+        flow_until_still(&mut world_map, &mut fluids);
+
+        for distance in 1..=MAX_FLUID_LEVEL as i32 {
+            let position = source + IVec3::X * distance;
+            assert_eq!(level(&world_map, position), Some(distance as u8));
+        }
+        let beyond = source + IVec3::X * (MAX_FLUID_LEVEL as i32 + 1);
+        assert_eq!(world_map.get_block_by_coordinates(&beyond), None);
+    }
+
+    #[test]
+    fn updates_over_the_budget_wait_for_the_next_tick() {
+        let blocks = BlockRegistry::builtin();
+        let (mut world_map, _) = pool(&blocks, IVec3::new(8, 1, 8));
+        let mut fluids = FluidUpdates::default();
+        // Empty positions far from the water, which do not schedule anything else
+        for x in 0..32 {
+            for z in 0..16 {
+                fluids.schedule(IVec3::new(x, 10, z));
+            }
+        }
+        let scheduled = fluids.queue.len();
+        assert!(scheduled > FLUID_UPDATE_BUDGET);
+
+        flow(&mut world_map, &mut fluids, &blocks);
+        assert_eq!(fluids.queue.len(), scheduled - FLUID_UPDATE_BUDGET);
+        flow(&mut world_map, &mut fluids, &blocks);
+        assert!(fluids.queue.is_empty());
+        assert!(fluids.queued.is_empty());
+    }
+}
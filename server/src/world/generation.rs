@@ -1,86 +1,329 @@
 use bevy::prelude::*;
 use noise::{NoiseFn, Perlin};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use shared::{world::*, CHUNK_SIZE};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
-fn generate_tree(chunk: &mut ServerChunk, x: i32, y: i32, z: i32, trunk: BlockId, leaves: BlockId) {
+use super::generators::WorldGenerator;
+use super::structures::{Placement, StructureRegistry};
+
+/// Features that draw random numbers during generation. Each one gets its own random
+/// source, so that changing how one feature uses randomness does not change the others
+#[derive(Debug, Clone, Copy)]
+enum Feature {
+    /// Which columns get a tree or a flower
+    Vegetation,
+    /// Shape of the trees, such as their height
+    TreeShape,
+    /// Placement and shape of the boulders on mountains
+    Boulders,
+    /// Placement and shape of the ore veins
+    Ores,
+}
+
+/// SplitMix64 finalizer, used instead of `std::hash` whose output may change between Rust versions
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// Stable hash of `(seed, chunk_pos, feature)`
+fn feature_hash(seed: u32, chunk_pos: IVec3, feature: Feature) -> u64 {
+    [
+        chunk_pos.x as u32 as u64,
+        chunk_pos.y as u32 as u64,
+        chunk_pos.z as u32 as u64,
+        feature as u64,
+    ]
+    .iter()
+    .fold(mix(seed as u64), |hash, value| mix(hash ^ value))
+}
+
+/// Stable hash of `(seed, salt, region)`, for structures spanning several chunks
+fn structure_hash(seed: u32, salt: u64, region: IVec2) -> u64 {
+    [region.x as u32 as u64, region.y as u32 as u64, salt]
+        .iter()
+        .fold(mix(seed as u64), |hash, value| mix(hash ^ value))
+}
+
+/// All randomness of the generation must come from here : the same seed has to give
+/// the same chunks, so that unmodified chunks can be generated again instead of being saved
+fn feature_rng(seed: u32, chunk_pos: IVec3, feature: Feature) -> StdRng {
+    StdRng::seed_from_u64(feature_hash(seed, chunk_pos, feature))
+}
+
+/// A chunk being generated. Features may place blocks outside of it, such as the leaves
+/// of a tree on its border : those are collected per chunk in `overflow`.
+/// A freshly generated chunk has not received any update yet, nor been modified
+#[derive(Default)]
+pub struct GeneratedChunk {
+    pub chunk: ServerChunk,
+    pub overflow: HashMap<IVec3, PalettedContainer>,
+}
+
+impl GeneratedChunk {
+    /// Places a block of a feature, at a position relative to the chunk that may be outside of it.
+    /// Features never replace existing blocks, so that the order in which they are placed does not matter
+    fn place_feature_block(&mut self, chunk_pos: IVec3, pos: IVec3, block: BlockData) {
+        let global_pos = chunk_pos * CHUNK_SIZE + pos;
+        let target = global_block_to_chunk_pos(&global_pos);
+        let local_pos = to_local_pos(&global_pos);
+
+        let blocks = if target == chunk_pos {
+            &mut self.chunk.map
+        } else {
+            self.overflow.entry(target).or_default()
+        };
+        if !blocks.contains_key(&local_pos) {
+            blocks.insert(local_pos, block);
+        }
+    }
+}
+
+/// Places the blocks of `source` where `target` has none, returns whether any was placed
+pub fn place_missing_blocks(target: &mut PalettedContainer, source: &PalettedContainer) -> bool {
+    let mut placed = false;
+    for (pos, block) in source.iter() {
+        if !target.contains_key(&pos) {
+            target.insert(pos, *block);
+            placed = true;
+        }
+    }
+    placed
+}
+
+fn generate_tree(
+    generated: &mut GeneratedChunk,
+    chunk_pos: IVec3,
+    rng: &mut StdRng,
+    pos: IVec3,
+    trunk: BlockId,
+    leaves: BlockId,
+) {
     // create trunk
-    let trunk_height = 3 + rand::random::<u8>() % 3; // random height between 3 and 5
+    let trunk_height = 3 + rng.gen::<u8>() % 3; // random height between 3 and 5
     for dy in 0..trunk_height {
-        chunk.map.insert(
-            IVec3::new(x, y + dy as i32, z),
+        generated.place_feature_block(
+            chunk_pos,
+            pos.with_y(pos.y + dy as i32),
             BlockData::new(trunk, false, BlockDirection::Front),
         );
     }
 
     // place the leaves
-    let leaf_start_y = y + trunk_height as i32 - 1;
+    let leaf_start_y = pos.y + trunk_height as i32 - 1;
     for offset_x in -1..=1 {
         for offset_z in -1..=1 {
             if (offset_x != 0 || offset_z != 0) && (offset_x == 0 || offset_z == 0) {
-                chunk.map.insert(
-                    IVec3::new(x + offset_x, leaf_start_y, z + offset_z),
+                generated.place_feature_block(
+                    chunk_pos,
+                    IVec3::new(pos.x + offset_x, leaf_start_y, pos.z + offset_z),
                     BlockData::new(leaves, false, BlockDirection::Front),
                 );
             }
         }
     }
     // add one leaf block at the top of the trunk
-    chunk.map.insert(
-        IVec3::new(x, leaf_start_y + 1, z),
+    generated.place_feature_block(
+        chunk_pos,
+        pos.with_y(leaf_start_y + 1),
         BlockData::new(leaves, false, BlockDirection::Front),
     );
 }
 
-pub fn determine_biome(temperature: f64, humidity: f64) -> BiomeType {
-    if temperature > 0.7 {
-        if humidity > 0.5 {
-            BiomeType::Forest
-        } else {
-            BiomeType::Desert
+/// Rough ball of cobblestone, half buried in the ground
+fn generate_boulder(
+    generated: &mut GeneratedChunk,
+    chunk_pos: IVec3,
+    rng: &mut StdRng,
+    center: IVec3,
+    block: BlockId,
+) {
+    let radius = rng.gen_range(1..=2);
+    for offset_x in -radius..=radius {
+        for offset_y in -radius..=radius {
+            for offset_z in -radius..=radius {
+                let distance = offset_x * offset_x + offset_y * offset_y + offset_z * offset_z;
+                // jagged edges
+                if distance > radius * radius + rng.gen_range(0..=1) {
+                    continue;
+                }
+                generated.place_feature_block(
+                    chunk_pos,
+                    center + IVec3::new(offset_x, offset_y, offset_z),
+                    BlockData::new(block, false, BlockDirection::Front),
+                );
+            }
         }
-    } else if temperature > 0.4 {
-        if humidity > 0.5 {
-            BiomeType::Plains
-        } else {
-            BiomeType::MediumMountain
+    }
+}
+
+/// How an ore is spread underground
+struct OreDistribution {
+    /// Name of the ore block
+    block: &'static str,
+    /// Number of veins tried per chunk
+    frequency: u32,
+    /// Maximum number of blocks in a vein
+    vein_size: u32,
+    /// Heights veins can start at, inclusive
+    min_y: i32,
+    max_y: i32,
+}
+
+const ORES: [OreDistribution; 4] = [
+    OreDistribution {
+        block: "coal_ore",
+        frequency: 20,
+        vein_size: 12,
+        min_y: 1,
+        max_y: 96,
+    },
+    OreDistribution {
+        block: "iron_ore",
+        frequency: 12,
+        vein_size: 8,
+        min_y: 1,
+        max_y: 64,
+    },
+    OreDistribution {
+        block: "gold_ore",
+        frequency: 3,
+        vein_size: 7,
+        min_y: 1,
+        max_y: 32,
+    },
+    OreDistribution {
+        block: "diamond_ore",
+        frequency: 1,
+        vein_size: 5,
+        min_y: 1,
+        max_y: 16,
+    },
+];
+
+/// Replaces stone with veins of ores. Veins are kept inside their chunk, as they are small
+/// and only replace stone, which neighbouring chunks may not have generated yet.
+/// The number of veins of each ore is multiplied by `ore_frequency`
+fn generate_ores(
+    chunk: &mut ServerChunk,
+    chunk_pos: IVec3,
+    rng: &mut StdRng,
+    ore_frequency: f32,
+    blocks: &TerrainBlocks,
+) {
+    let chunk_min_y = chunk_pos.y * CHUNK_SIZE;
+    for (ore, ore_block) in ORES.iter().zip(&blocks.ores) {
+        let frequency = (ore.frequency as f32 * ore_frequency).round() as u32;
+        for _ in 0..frequency {
+            // draw every value even for skipped veins, so that ores do not depend on each other
+            let mut pos = IVec3::new(
+                rng.gen_range(0..CHUNK_SIZE),
+                rng.gen_range(0..CHUNK_SIZE),
+                rng.gen_range(0..CHUNK_SIZE),
+            );
+            let size = rng.gen_range(1..=ore.vein_size);
+            let mut steps: Vec<IVec3> = (0..size)
+                .map(|_| match rng.gen_range(0..6) {
+                    0 => IVec3::X,
+                    1 => IVec3::NEG_X,
+                    2 => IVec3::Y,
+                    3 => IVec3::NEG_Y,
+                    4 => IVec3::Z,
+                    _ => IVec3::NEG_Z,
+                })
+                .collect();
+
+            let y = chunk_min_y + pos.y;
+            if y < ore.min_y || y > ore.max_y {
+                continue;
+            }
+
+            // random walk from the start of the vein
+            steps.insert(0, IVec3::ZERO);
+            for step in steps {
+                pos += step;
+                if chunk.map.get(&pos).map(|block| block.id) == Some(blocks.stone) {
+                    chunk.map.insert(
+                        pos,
+                        BlockData::new(*ore_block, false, BlockDirection::Front),
+                    );
+                }
+            }
         }
-    } else if temperature >= 0.0 {
-        if humidity > 0.5 {
-            BiomeType::IcePlain
-        } else {
-            BiomeType::HighMountain
+    }
+}
+
+/// Noises deciding the biome of each column
+struct BiomeNoises {
+    temperature: Perlin,
+    humidity: Perlin,
+    /// Low values are oceans, which are wider than the other biomes
+    continentalness: Perlin,
+    /// Rivers follow the middle line of this noise
+    river: Perlin,
+}
+
+impl BiomeNoises {
+    fn new(seed: u32) -> Self {
+        BiomeNoises {
+            temperature: Perlin::new(seed.wrapping_add(1)),
+            humidity: Perlin::new(seed.wrapping_add(2)),
+            continentalness: Perlin::new(seed.wrapping_add(7)),
+            river: Perlin::new(seed.wrapping_add(8)),
         }
-    } else {
-        panic!();
+    }
+
+    fn climate_at(&self, x: i32, z: i32, biome_scale: f64) -> Climate {
+        let sample = |perlin: &Perlin, scale: f64| {
+            ((perlin.get([x as f64 * scale, z as f64 * scale]) + 1.0) / 2.0).clamp(0.0, 1.0)
+        };
+        Climate {
+            temperature: sample(&self.temperature, biome_scale),
+            humidity: sample(&self.humidity, biome_scale),
+            continentalness: sample(&self.continentalness, biome_scale / 2.0),
+            river: (sample(&self.river, biome_scale / 4.0) - 0.5).abs(),
+        }
+    }
+
+    fn biome_at<'a>(
+        &self,
+        biomes: &'a BiomeRegistry,
+        x: i32,
+        z: i32,
+        biome_scale: f64,
+    ) -> &'a Biome {
+        biomes.find(&self.climate_at(x, z, biome_scale))
     }
 }
 
 fn interpolated_height(
     x: i32,
     z: i32,
-    biome_scale: f64,
     perlin: &Perlin,
-    temp_perlin: &Perlin,
-    humidity_perlin: &Perlin,
-    scale: f64,
-) -> i32 {
+    biomes: &BiomeRegistry,
+    biome_noises: &BiomeNoises,
+    settings: &GeneratorSettings,
+    amplification: f64,
+) -> (i32, f64) {
+    let biome_scale = settings.biome_scale;
+    let blend_distance = settings.blend_distance;
+
     // get the properties of the main biome at (x, z)
-    let temperature =
-        (temp_perlin.get([x as f64 * biome_scale, z as f64 * biome_scale]) + 1.0) / 2.0;
-    let humidity =
-        (humidity_perlin.get([x as f64 * biome_scale, z as f64 * biome_scale]) + 1.0) / 2.0;
-    let biome_type = determine_biome(temperature, humidity);
-    let biome = get_biome_data(biome_type);
+    let biome = biome_noises.biome_at(biomes, x, z, biome_scale);
 
     // initialize weighted values
     let mut weighted_base_height = biome.base_height as f64;
     let mut weighted_variation = biome.height_variation as f64;
+    let mut weighted_overhang = biome.overhang_height as f64;
     let mut total_weight = 1.0;
 
     // loop through neighboring blocks to get influences
-    for &offset_x in &[-4, 0, 4] {
-        for &offset_z in &[-4, 0, 4] {
+    for &offset_x in &[-blend_distance, 0, blend_distance] {
+        for &offset_z in &[-blend_distance, 0, blend_distance] {
             if offset_x == 0 && offset_z == 0 {
                 continue; // ignore the central position
             }
@@ -88,21 +331,8 @@ fn interpolated_height(
             let neighbor_x = x + offset_x;
             let neighbor_z = z + offset_z;
 
-            // calculate the temperature and humidity of the neighboring block
-            let neighbor_temp = (temp_perlin.get([
-                neighbor_x as f64 * biome_scale,
-                neighbor_z as f64 * biome_scale,
-            ]) + 1.0)
-                / 2.0;
-            let neighbor_humidity = (humidity_perlin.get([
-                neighbor_x as f64 * biome_scale,
-                neighbor_z as f64 * biome_scale,
-            ]) + 1.0)
-                / 2.0;
-
             // determine the biome of the neighboring block
-            let neighbor_biome_type = determine_biome(neighbor_temp, neighbor_humidity);
-            let neighbor_biome = get_biome_data(neighbor_biome_type);
+            let neighbor_biome = biome_noises.biome_at(biomes, neighbor_x, neighbor_z, biome_scale);
 
             // weight by distance (the farther a neighbor is, the less influence it has)
             let distance = ((offset_x.pow(2) + offset_z.pow(2)) as f64).sqrt();
@@ -111,6 +341,7 @@ fn interpolated_height(
             // update weighted values
             weighted_base_height += neighbor_biome.base_height as f64 * weight;
             weighted_variation += neighbor_biome.height_variation as f64 * weight;
+            weighted_overhang += neighbor_biome.overhang_height as f64 * weight;
             total_weight += weight;
         }
     }
@@ -118,160 +349,631 @@ fn interpolated_height(
     // normalize weighted values
     weighted_base_height /= total_weight;
     weighted_variation /= total_weight;
+    weighted_overhang /= total_weight;
 
     // final calculation of height with perlin noise
+    let scale = settings.terrain_scale;
     let terrain_noise = perlin.get([x as f64 * scale, z as f64 * scale]);
-    let interpolated_height = weighted_base_height + (weighted_variation * terrain_noise);
+    let amplified_base_height = weighted_base_height
+        + (weighted_base_height - settings.sea_level as f64) * (amplification - 1.0);
+    let interpolated_height =
+        amplified_base_height + (weighted_variation * amplification * terrain_noise);
 
-    interpolated_height.round() as i32
+    // overhangs are blended too, to avoid walls at the borders of mountains
+    (
+        interpolated_height.round() as i32,
+        weighted_overhang * amplification,
+    )
 }
 
-pub fn generate_chunk(chunk_pos: IVec3, seed: u32) -> ServerChunk {
-    let perlin = Perlin::new(seed);
-    let temp_perlin = Perlin::new(seed + 1);
-    let humidity_perlin = Perlin::new(seed + 2);
-
-    let scale = 0.1;
-    let biome_scale = 0.02;
-    let cx = chunk_pos.x;
-    let cy = chunk_pos.y;
-    let cz = chunk_pos.z;
-
-    let mut chunk = ServerChunk {
-        map: HashMap::new(),
-        ts: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64,
-    };
-
-    for dx in 0..CHUNK_SIZE {
-        for dz in 0..CHUNK_SIZE {
-            let x = CHUNK_SIZE * cx + dx;
-            let z = CHUNK_SIZE * cz + dz;
-
-            // calculate temperature and humidity
-            let temperature =
-                (temp_perlin.get([x as f64 * biome_scale, z as f64 * biome_scale]) + 1.0) / 2.0;
-            let humidity =
-                (humidity_perlin.get([x as f64 * biome_scale, z as f64 * biome_scale]) + 1.0) / 2.0;
-
-            // get biome regarding the two values
-            let biome_type = determine_biome(temperature, humidity);
-            let biome = get_biome_data(biome_type);
-
-            // get terrain height
-            let terrain_height = interpolated_height(
-                x,
-                z,
-                biome_scale,
-                &perlin,
-                &temp_perlin,
-                &humidity_perlin,
-                scale,
-            );
+/// 3D noises carving the terrain
+struct CaveNoises {
+    /// Large open caves
+    cheese: Perlin,
+    /// Tunnels follow the intersection of the zero surfaces of two noises
+    tunnel_a: Perlin,
+    tunnel_b: Perlin,
+    /// Shifts the surface up and down to form cliffs and overhangs
+    overhang: Perlin,
+}
 
-            // generate blocs
-            for dy in 0..CHUNK_SIZE {
-                let y = CHUNK_SIZE * cy + dy;
+impl CaveNoises {
+    fn new(seed: u32) -> Self {
+        CaveNoises {
+            cheese: Perlin::new(seed.wrapping_add(3)),
+            tunnel_a: Perlin::new(seed.wrapping_add(4)),
+            tunnel_b: Perlin::new(seed.wrapping_add(5)),
+            overhang: Perlin::new(seed.wrapping_add(6)),
+        }
+    }
+
+    /// Whether there is ground at `pos`, before caves are carved
+    fn is_terrain(&self, pos: IVec3, terrain_height: i32, overhang_height: f64) -> bool {
+        let depth = (terrain_height - pos.y) as f64;
+        if depth.abs() > overhang_height {
+            return depth >= 0.0;
+        }
+
+        let noise = self.overhang.get([
+            pos.x as f64 * 0.08,
+            pos.y as f64 * 0.12,
+            pos.z as f64 * 0.08,
+        ]);
+        depth + noise * overhang_height >= 0.0
+    }
 
-                if y > terrain_height {
-                    break;
+    /// Whether the ground at `pos` is carved by a cave
+    fn is_cave(
+        &self,
+        pos: IVec3,
+        terrain_height: i32,
+        cave_frequency: f64,
+        surface_openings: bool,
+    ) -> bool {
+        // bedrock is never carved
+        if pos.y <= 0 || cave_frequency <= 0.0 {
+            return false;
+        }
+        let (x, y, z) = (pos.x as f64, pos.y as f64, pos.z as f64);
+
+        // caves are kept away from the surface so that it is not riddled with holes,
+        // except for tunnels which give caves their entrances
+        let below_surface = pos.y < terrain_height - 8;
+        if !surface_openings && !below_surface {
+            return false;
+        }
+
+        let width = 0.06 * cave_frequency;
+        let tunnel = self.tunnel_a.get([x * 0.03, y * 0.05, z * 0.03]).abs() < width
+            && self.tunnel_b.get([x * 0.03, y * 0.05, z * 0.03]).abs() < width;
+        if tunnel {
+            return true;
+        }
+
+        // caves are flattened
+        below_surface
+            && self.cheese.get([x * 0.04, y * 0.06, z * 0.04]) > 1.0 - 0.55 * cave_frequency
+    }
+}
+
+/// Blocks placed by the generator itself rather than by biomes or structures
+pub struct TerrainBlocks {
+    stone: BlockId,
+    water: BlockId,
+    sand: BlockId,
+    bedrock: BlockId,
+    cobblestone: BlockId,
+    /// Same order as `ORES`
+    ores: Vec<BlockId>,
+}
+
+impl TerrainBlocks {
+    pub fn new(blocks: &BlockRegistry) -> Self {
+        TerrainBlocks {
+            stone: blocks.required("stone"),
+            water: blocks.required("water"),
+            sand: blocks.required("sand"),
+            bedrock: blocks.required("bedrock"),
+            cobblestone: blocks.required("cobblestone"),
+            ores: ORES.iter().map(|ore| blocks.required(ore.block)).collect(),
+        }
+    }
+}
+
+/// Terrain made of biomes, caves and features
+pub struct NoiseGenerator {
+    pub biomes: BiomeRegistry,
+    pub structures: StructureRegistry,
+    pub settings: GeneratorSettings,
+    pub blocks: TerrainBlocks,
+    /// How much heights stray from the sea level, 1 for the default terrain
+    pub amplification: f64,
+}
+
+impl NoiseGenerator {
+    pub fn new(
+        biomes: BiomeRegistry,
+        structures: StructureRegistry,
+        settings: GeneratorSettings,
+        blocks: &BlockRegistry,
+    ) -> Self {
+        NoiseGenerator {
+            biomes,
+            structures,
+            settings,
+            blocks: TerrainBlocks::new(blocks),
+            amplification: 1.0,
+        }
+    }
+
+    /// Places the parts of the structures crossing a chunk. Where a structure goes only depends
+    /// on the seed and its region, so each chunk it crosses places its own part of it
+    fn place_structures(
+        &self,
+        chunk: &mut ServerChunk,
+        chunk_pos: IVec3,
+        seed: u32,
+        perlin: &Perlin,
+        biome_noises: &BiomeNoises,
+    ) {
+        let chunk_min = chunk_pos * CHUNK_SIZE;
+        let chunk_max = chunk_min + IVec3::splat(CHUNK_SIZE - 1);
+
+        for structure in self.structures.iter() {
+            let region_size = structure.spacing * CHUNK_SIZE;
+            let reach = structure.size.x.max(structure.size.z);
+            // regions whose structure may reach this chunk
+            let min_region =
+                (chunk_min.xz() - IVec2::splat(reach)).div_euclid(IVec2::splat(region_size));
+            let max_region = chunk_max.xz().div_euclid(IVec2::splat(region_size));
+
+            for region_x in min_region.x..=max_region.x {
+                for region_z in min_region.y..=max_region.y {
+                    let region = IVec2::new(region_x, region_z);
+                    let mut rng =
+                        StdRng::seed_from_u64(structure_hash(seed, structure.salt, region));
+                    if rng.gen::<f32>() >= structure.chance * self.settings.structure_chance {
+                        continue;
+                    }
+                    let x = region.x * region_size + rng.gen_range(0..region_size);
+                    let z = region.y * region_size + rng.gen_range(0..region_size);
+                    let rotation = BlockDirection::from_quarter_turns(rng.gen_range(0..4));
+
+                    let size = structure.rotated_size(rotation);
+                    if x > chunk_max.x
+                        || z > chunk_max.z
+                        || x + size.x <= chunk_min.x
+                        || z + size.z <= chunk_min.z
+                    {
+                        continue;
+                    }
+
+                    let biome =
+                        biome_noises.biome_at(&self.biomes, x, z, self.settings.biome_scale);
+                    if !structure.allows_biome(&biome.name) {
+                        continue;
+                    }
+
+                    let (terrain_height, _) = interpolated_height(
+                        x,
+                        z,
+                        perlin,
+                        &self.biomes,
+                        biome_noises,
+                        &self.settings,
+                        self.amplification,
+                    );
+                    let y = match structure.placement {
+                        Placement::Surface { offset } => {
+                            if terrain_height < self.settings.sea_level {
+                                continue;
+                            }
+                            terrain_height + 1 + offset
+                        }
+                        Placement::Underground { min_y, max_y } => {
+                            let y = rng.gen_range(min_y..=max_y);
+                            // keep a few blocks of ground above, so that it stays hidden
+                            if y + size.y > terrain_height - 4 {
+                                continue;
+                            }
+                            y
+                        }
+                    };
+
+                    let origin = IVec3::new(x, y, z);
+                    if y > chunk_max.y || y + size.y <= chunk_min.y {
+                        continue;
+                    }
+                    trace!(
+                        "Placing {} at {:?} in chunk {:?}",
+                        structure.name,
+                        origin,
+                        chunk_pos
+                    );
+
+                    for (pos, block) in structure.rotated_blocks(rotation) {
+                        let local_pos = origin + pos - chunk_min;
+                        if local_pos.cmplt(IVec3::ZERO).any()
+                            || local_pos.cmpge(IVec3::splat(CHUNK_SIZE)).any()
+                        {
+                            continue;
+                        }
+                        match block {
+                            Some(block) => {
+                                chunk.map.insert(local_pos, block);
+                            }
+                            None => {
+                                chunk.map.remove(&local_pos);
+                            }
+                        }
+                    }
                 }
+            }
+        }
+    }
+}
 
-                let block = if y == 0 {
-                    BlockId::Bedrock
-                } else if y < terrain_height - 4 {
-                    BlockId::Stone
-                } else if y < terrain_height {
-                    biome.sub_surface_block
-                } else if y == terrain_height {
-                    biome.surface_block
-                } else {
-                    panic!();
-                };
-
-                let block_pos = IVec3::new(dx, dy, dz);
-
-                chunk.map.insert(
-                    block_pos,
-                    BlockData::new(block, false, BlockDirection::Front),
+impl WorldGenerator for NoiseGenerator {
+    fn generate_chunk(&self, chunk_pos: IVec3, seed: u32) -> GeneratedChunk {
+        let perlin = Perlin::new(seed);
+        let biome_noises = BiomeNoises::new(seed);
+        let cave_noises = CaveNoises::new(seed);
+
+        let settings = &self.settings;
+        let sea_level = settings.sea_level;
+        let cx = chunk_pos.x;
+        let cy = chunk_pos.y;
+        let cz = chunk_pos.z;
+
+        let mut generated = GeneratedChunk::default();
+
+        let mut vegetation_rng = feature_rng(seed, chunk_pos, Feature::Vegetation);
+        let mut tree_rng = feature_rng(seed, chunk_pos, Feature::TreeShape);
+        let mut boulder_rng = feature_rng(seed, chunk_pos, Feature::Boulders);
+        let mut ore_rng = feature_rng(seed, chunk_pos, Feature::Ores);
+
+        for dx in 0..CHUNK_SIZE {
+            for dz in 0..CHUNK_SIZE {
+                let x = CHUNK_SIZE * cx + dx;
+                let z = CHUNK_SIZE * cz + dz;
+
+                // get biome regarding temperature, humidity, and distance to oceans and rivers
+                let biome = biome_noises.biome_at(&self.biomes, x, z, settings.biome_scale);
+                generated.chunk.tints.push(biome.tint());
+
+                // get terrain height
+                let (terrain_height, overhang_height) = interpolated_height(
+                    x,
+                    z,
+                    &perlin,
+                    &self.biomes,
+                    &biome_noises,
+                    settings,
+                    self.amplification,
                 );
 
-                // Add flora in some biomes
-                if y == terrain_height && terrain_height >= 1 {
-                    match biome_type {
-                        BiomeType::Forest => {
-                            let tree_chance = rand::random::<f32>();
-                            if tree_chance < 0.05 {
+                // generate blocs
+                for dy in 0..CHUNK_SIZE {
+                    let y = CHUNK_SIZE * cy + dy;
+
+                    if y as f64 > terrain_height as f64 + overhang_height && y > sea_level {
+                        break;
+                    }
+
+                    let global_pos = IVec3::new(x, y, z);
+                    let is_terrain = |offset: i32| {
+                        cave_noises.is_terrain(
+                            global_pos.with_y(y + offset),
+                            terrain_height,
+                            overhang_height,
+                        )
+                    };
+                    let block_pos = IVec3::new(dx, dy, dz);
+
+                    if !is_terrain(0) {
+                        if y <= sea_level && !generated.chunk.map.contains_key(&block_pos) {
+                            generated.chunk.map.insert(
+                                block_pos,
+                                BlockData::new(self.blocks.water, false, BlockDirection::Front),
+                            );
+                        }
+                        continue;
+                    }
+
+                    // the surface is the top of the ground, even under an overhang
+                    let is_surface = !is_terrain(1);
+                    let underwater = y < sea_level;
+                    let block = if y == 0 {
+                        self.blocks.bedrock
+                    } else if is_surface && underwater {
+                        self.blocks.sand
+                    } else if is_surface {
+                        biome.surface_block
+                    } else if (2..=5).any(|offset| !is_terrain(offset)) {
+                        biome.sub_surface_block
+                    } else {
+                        self.blocks.stone
+                    };
+
+                    // caves do not open under water, as it would float above them
+                    if cave_noises.is_cave(
+                        global_pos,
+                        terrain_height,
+                        biome.cave_frequency * settings.cave_frequency,
+                        terrain_height >= sea_level,
+                    ) {
+                        continue;
+                    }
+
+                    generated.chunk.map.insert(
+                        block_pos,
+                        BlockData::new(block, false, BlockDirection::Front),
+                    );
+
+                    // Add flora in some biomes
+                    if is_surface && y >= 1 && !underwater {
+                        let above_surface_pos = block_pos.with_y(dy + 1);
+                        if !biome.flora.is_empty() && settings.flora_density > 0.0 {
+                            match biome
+                                .pick_flora(vegetation_rng.gen::<f32>() / settings.flora_density)
+                            {
                                 // ensure the area above is clear before generating the tree
-                                let above_surface_pos = IVec3::new(dx, terrain_height + 1, dz);
-                                if !chunk.map.contains_key(&above_surface_pos) {
+                                Some(Flora::Tree { log, leaves })
+                                    if !generated.chunk.map.contains_key(&above_surface_pos) =>
+                                {
                                     generate_tree(
-                                        &mut chunk,
-                                        dx,
-                                        dy + 1,
-                                        dz,
-                                        BlockId::OakLog,
-                                        BlockId::OakLeaves,
+                                        &mut generated,
+                                        chunk_pos,
+                                        &mut tree_rng,
+                                        above_surface_pos,
+                                        *log,
+                                        *leaves,
                                     );
                                 }
-                            } else if tree_chance < 0.075 {
-                                chunk.map.insert(
-                                    block_pos.with_y(block_pos.y + 1),
-                                    BlockData::new(
-                                        BlockId::Dandelion,
-                                        false,
-                                        BlockDirection::Front,
-                                    ),
-                                );
-                            } else if tree_chance < 0.1 {
-                                chunk.map.insert(
-                                    block_pos.with_y(block_pos.y + 1),
-                                    BlockData::new(BlockId::Poppy, false, BlockDirection::Front),
-                                );
-                            }
-                        }
-                        BiomeType::IcePlain => {
-                            let tree_chance = rand::random::<f32>();
-                            if tree_chance < 0.05 {
-                                // ensure the area above is clear before generating the tree
-                                let above_surface_pos = IVec3::new(dx, terrain_height + 1, dz);
-                                if !chunk.map.contains_key(&above_surface_pos) {
-                                    generate_tree(
-                                        &mut chunk,
-                                        dx,
-                                        dy + 1,
-                                        dz,
-                                        BlockId::SpruceLog,
-                                        BlockId::SpruceLeaves,
+                                Some(Flora::Plant(plant)) => {
+                                    generated.place_feature_block(
+                                        chunk_pos,
+                                        above_surface_pos,
+                                        BlockData::new(*plant, false, BlockDirection::Front),
                                     );
                                 }
+                                _ => {}
                             }
                         }
-                        BiomeType::Plains => {
-                            let flower_chance = rand::random::<f32>();
-                            if flower_chance < 0.075 {
-                                chunk.map.insert(
-                                    block_pos.with_y(block_pos.y + 1),
-                                    BlockData::new(
-                                        BlockId::Dandelion,
-                                        false,
-                                        BlockDirection::Front,
-                                    ),
-                                );
-                            } else if flower_chance < 0.1 {
-                                chunk.map.insert(
-                                    block_pos.with_y(block_pos.y + 1),
-                                    BlockData::new(BlockId::Poppy, false, BlockDirection::Front),
-                                );
-                            }
+                        let boulder_density = biome.boulder_density * settings.boulder_density;
+                        if boulder_density > 0.0 && boulder_rng.gen::<f32>() < boulder_density {
+                            generate_boulder(
+                                &mut generated,
+                                chunk_pos,
+                                &mut boulder_rng,
+                                block_pos,
+                                self.blocks.cobblestone,
+                            );
                         }
-                        _ => {}
                     }
                 }
             }
         }
+        generate_ores(
+            &mut generated.chunk,
+            chunk_pos,
+            &mut ore_rng,
+            settings.ore_frequency,
+            &self.blocks,
+        );
+        self.place_structures(
+            &mut generated.chunk,
+            chunk_pos,
+            seed,
+            &perlin,
+            &biome_noises,
+        );
+
+        generated
+    }
+
+    fn column_tints(&self, chunk_pos: IVec3, seed: u32) -> Vec<BiomeTint> {
+        self.column_biomes(chunk_pos, seed)
+            .into_iter()
+            .map(Biome::tint)
+            .collect()
+    }
+
+    fn column_biomes(&self, chunk_pos: IVec3, seed: u32) -> Vec<&Biome> {
+        let biome_noises = BiomeNoises::new(seed);
+        let mut biomes = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE) as usize);
+        for dx in 0..CHUNK_SIZE {
+            for dz in 0..CHUNK_SIZE {
+                let x = CHUNK_SIZE * chunk_pos.x + dx;
+                let z = CHUNK_SIZE * chunk_pos.z + dz;
+                biomes.push(biome_noises.biome_at(&self.biomes, x, z, self.settings.biome_scale));
+            }
+        }
+        biomes
+    }
+}
+
+/// Stores a generated chunk into the world, and places the blocks of features crossing its borders
+/// the first time it is generated. Those go into `pending_writes`, and into the neighbouring
+/// chunks that already exist. Returns `None` if the chunk is empty, in which case it is not stored
+pub fn insert_generated_chunk(
+    world_map: &mut ServerWorldMap,
+    chunk_pos: IVec3,
+    generated: GeneratedChunk,
+) -> Option<&ServerChunk> {
+    let GeneratedChunk {
+        mut chunk,
+        overflow,
+    } = generated;
+
+    if let Some(pending) = world_map.pending_writes.get(&chunk_pos) {
+        place_missing_blocks(&mut chunk.map, pending);
+    }
+
+    // When the chunk is generated again, players may have changed the features it placed
+    if let Entry::Vacant(entry) = world_map.features_placed.entry(chunk_pos) {
+        entry.insert(overflow.keys().copied().collect());
+        for (target_pos, blocks) in overflow {
+            if let Some(target) = world_map.map.get_mut(&target_pos) {
+                if place_missing_blocks(&mut target.map, &blocks) {
+                    world_map.chunks_to_update.push(target_pos);
+                }
+            }
+            let pending = world_map.pending_writes.entry(target_pos).or_default();
+            place_missing_blocks(pending, &blocks);
+        }
+    }
+
+    // Blocks may have been placed in the chunk while it was being generated
+    if let Some(existing) = world_map.map.get_mut(&chunk_pos) {
+        place_missing_blocks(&mut existing.map, &chunk.map);
+        existing.tints = chunk.tints;
+    } else if chunk.map.is_empty() {
+        return None;
+    } else {
+        world_map.map.insert(chunk_pos, chunk);
+    }
+    world_map.map.get(&chunk_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::data::{Save, WorldData};
+    use bincode::Options;
+
+    fn generator() -> NoiseGenerator {
+        let blocks = BlockRegistry::builtin();
+        NoiseGenerator::new(
+            BiomeRegistry::builtin(&blocks),
+            StructureRegistry::builtin(&blocks),
+            GeneratorSettings::default(),
+            &blocks,
+        )
+    }
+
+    fn serialized_chunk(chunk_pos: IVec3, seed: u32) -> Vec<u8> {
+        bincode::options()
+            .serialize(&generator().generate_chunk(chunk_pos, seed).chunk)
+            .unwrap()
+    }
+
+    #[test]
+    fn same_seed_generates_identical_chunks() {
+        let seed = 1234;
+        let generator = generator();
+        for x in -2..=2 {
+            // surface chunks, where the flora is
+            for y in 3..=5 {
+                for z in -2..=2 {
+                    let chunk_pos = IVec3::new(x, y, z);
+                    assert_eq!(
+                        serialized_chunk(chunk_pos, seed),
+                        serialized_chunk(chunk_pos, seed),
+                        "chunk {:?} differs between two generations",
+                        chunk_pos
+                    );
+                    assert_eq!(
+                        generator.generate_chunk(chunk_pos, seed).overflow,
+                        generator.generate_chunk(chunk_pos, seed).overflow,
+                        "features of chunk {:?} crossing its borders differ between two generations",
+                        chunk_pos
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_generate_different_chunks() {
+        let chunks = |seed| {
+            (3..=5)
+                .map(|y| serialized_chunk(IVec3::new(0, y, 0), seed))
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(chunks(1), chunks(2));
+    }
+
+    #[test]
+    fn features_crossing_borders_are_placed_once_without_marking_chunks_modified() {
+        let seed = 1234;
+        let generator = generator();
+        let (chunk_pos, generated) = (-8..8)
+            .flat_map(|x| (3..=5).map(move |y| IVec3::new(x, y, 0)))
+            .map(|chunk_pos| (chunk_pos, generator.generate_chunk(chunk_pos, seed)))
+            .find(|(_, generated)| !generated.overflow.is_empty())
+            .expect("no feature crosses a chunk border");
+        let (target_pos, local_pos) = generated
+            .overflow
+            .iter()
+            .find_map(|(target_pos, blocks)| Some((*target_pos, blocks.iter().next()?.0)))
+            .unwrap();
+        let global_pos = target_pos * CHUNK_SIZE + local_pos;
+
+        let mut world_map = ServerWorldMap::default();
+        insert_generated_chunk(&mut world_map, chunk_pos, generated);
+        insert_generated_chunk(
+            &mut world_map,
+            target_pos,
+            generator.generate_chunk(target_pos, seed),
+        );
+        assert!(world_map.get_block_by_coordinates(&global_pos).is_some());
+        assert!(world_map.map.values().all(|chunk| !chunk.modified));
+
+        // Unmodified chunks are generated again after a restart, with the features of their neighbours
+        world_map.map.remove(&target_pos);
+        insert_generated_chunk(
+            &mut world_map,
+            target_pos,
+            generator.generate_chunk(target_pos, seed),
+        );
+        assert!(world_map.get_block_by_coordinates(&global_pos).is_some());
+
+        // Features removed by players are not placed again when their chunk is generated again
+        world_map.remove_block_by_coordinates(&global_pos);
+        world_map.map.remove(&chunk_pos);
+        insert_generated_chunk(
+            &mut world_map,
+            chunk_pos,
+            generator.generate_chunk(chunk_pos, seed),
+        );
+        assert!(world_map.get_block_by_coordinates(&global_pos).is_none());
+    }
+
+    #[test]
+    fn exploring_without_editing_keeps_the_save_empty() {
+        let seed = 1234;
+        let generator = generator();
+        let mut world_map = ServerWorldMap::default();
+        for x in -3..=3 {
+            for y in 2..=5 {
+                for z in -3..=3 {
+                    let chunk_pos = IVec3::new(x, y, z);
+                    insert_generated_chunk(
+                        &mut world_map,
+                        chunk_pos,
+                        generator.generate_chunk(chunk_pos, seed),
+                    );
+                }
+            }
+        }
+        let (source_pos, target_pos, global_pos) = world_map
+            .features_placed
+            .iter()
+            .flat_map(|(source, targets)| targets.iter().map(move |target| (*source, *target)))
+            .filter(|(_, target)| world_map.map.contains_key(target))
+            .find_map(|(source, target)| {
+                let blocks = world_map.pending_writes.get(&target)?;
+                let local_pos = blocks.iter().next()?.0;
+                Some((source, target, target * CHUNK_SIZE + local_pos))
+            })
+            .expect("no feature crosses a chunk border");
+
+        let mut world = WorldData {
+            seed: WorldSeed(seed),
+            preset: WorldPreset::Default,
+            settings: GeneratorSettings::default(),
+            height: WorldHeight::default(),
+            terrain_data: None,
+            map: world_map,
+            held_items: HashMap::new(),
+            time: 0,
+        };
+        let blocks = BlockRegistry::builtin();
+        let save = Save::from_world(&world, &blocks);
+        assert!(save.chunks.is_empty());
+        assert!(save.pending_writes.is_empty());
+        assert!(save.features_placed.is_empty());
+
+        // Editing a chunk keeps the neighbours whose features went into it from placing them again
+        world.map.remove_block_by_coordinates(&global_pos);
+        let save = Save::from_world(&world, &blocks);
+        assert_eq!(save.chunks.len(), 1);
+        assert!(save.features_placed[&source_pos].contains(&target_pos));
+        assert!(!save.pending_writes.contains_key(&target_pos));
+        assert!(save
+            .pending_writes
+            .keys()
+            .all(|chunk_pos| !save.chunks.contains_key(chunk_pos)));
     }
-    chunk
 }
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use shared::world::{
+    Biome, BiomeRegistry, BiomeTint, BlockData, BlockDirection, BlockId, BlockRegistry, FlatLayer,
+    GeneratorSettings, WorldPreset,
+};
+use shared::CHUNK_SIZE;
+use std::sync::Arc;
+
+use super::generation::{GeneratedChunk, NoiseGenerator};
+use super::structures::StructureRegistry;
+
+/// Generates the terrain of a world, chunk by chunk. Implementations must only depend
+/// on their arguments : unmodified chunks are not saved, but generated again
+pub trait WorldGenerator: Send + Sync {
+    fn generate_chunk(&self, chunk_pos: IVec3, seed: u32) -> GeneratedChunk;
+
+    /// Biome colours of the columns of a chunk, as in `ServerChunk::tints`.
+    /// Generators without biomes leave them empty
+    fn column_tints(&self, _chunk_pos: IVec3, _seed: u32) -> Vec<BiomeTint> {
+        Vec::new()
+    }
+
+    /// Biomes of the columns of a chunk, in the same order as the tints.
+    /// Generators without biomes leave them empty
+    fn column_biomes(&self, _chunk_pos: IVec3, _seed: u32) -> Vec<&Biome> {
+        Vec::new()
+    }
+}
+
+/// Generator of the loaded world, built from its preset. Shared with generation tasks
+#[derive(Resource, Clone)]
+pub struct ActiveGenerator(pub Arc<dyn WorldGenerator>);
+
+/// Terrain heights are multiplied by this much in amplified worlds
+const AMPLIFICATION: f64 = 3.0;
+
+/// Builds the generator of a preset. Settings are used by the presets made of biomes
+pub fn create_generator(
+    preset: &WorldPreset,
+    settings: &GeneratorSettings,
+    biomes: &BiomeRegistry,
+    structures: &StructureRegistry,
+    blocks: &BlockRegistry,
+) -> Box<dyn WorldGenerator> {
+    let noise_generator = |biomes: BiomeRegistry| {
+        NoiseGenerator::new(biomes, structures.clone(), settings.clone(), blocks)
+    };
+    match preset {
+        WorldPreset::Default => Box::new(noise_generator(biomes.clone())),
+        WorldPreset::Superflat { layers } => Box::new(FlatGenerator::new(layers, blocks)),
+        WorldPreset::Void => Box::new(VoidGenerator {
+            platform: blocks.required("stone"),
+        }),
+        WorldPreset::SingleBiome { biome } => {
+            let biomes = biomes.single(biome).unwrap_or_else(|| {
+                warn!("Unknown biome '{}', using all of them", biome);
+                biomes.clone()
+            });
+            Box::new(noise_generator(biomes))
+        }
+        WorldPreset::Amplified => Box::new(NoiseGenerator {
+            amplification: AMPLIFICATION,
+            ..noise_generator(biomes.clone())
+        }),
+    }
+}
+
+/// Flat layers of blocks, without any feature
+pub struct FlatGenerator {
+    /// Block of each height, from 0 upwards
+    blocks: Vec<BlockId>,
+}
+
+impl FlatGenerator {
+    pub fn new(layers: &[FlatLayer], registry: &BlockRegistry) -> Self {
+        let mut blocks = Vec::new();
+        for layer in layers {
+            let Some(block) = registry.id(&layer.block) else {
+                warn!("Unknown block '{}' in superflat layers", layer.block);
+                continue;
+            };
+            blocks.resize(blocks.len() + layer.thickness as usize, block);
+        }
+        FlatGenerator { blocks }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate_chunk(&self, chunk_pos: IVec3, _seed: u32) -> GeneratedChunk {
+        let mut generated = GeneratedChunk::default();
+        for dy in 0..CHUNK_SIZE {
+            let y = chunk_pos.y * CHUNK_SIZE + dy;
+            let Some(block) = usize::try_from(y).ok().and_then(|y| self.blocks.get(y)) else {
+                continue;
+            };
+            for dx in 0..CHUNK_SIZE {
+                for dz in 0..CHUNK_SIZE {
+                    generated.chunk.map.insert(
+                        IVec3::new(dx, dy, dz),
+                        BlockData::new(*block, false, BlockDirection::Front),
+                    );
+                }
+            }
+        }
+        generated
+    }
+}
+
+/// Empty world, except for a platform below the spawn point
+pub struct VoidGenerator {
+    /// Block the platform is made of
+    platform: BlockId,
+}
+
+/// Platform of the void worlds, as (min, max) corners
+const VOID_PLATFORM: (IVec3, IVec3) = (IVec3::new(5, 63, 5), IVec3::new(9, 63, 9));
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(&self, chunk_pos: IVec3, _seed: u32) -> GeneratedChunk {
+        let mut generated = GeneratedChunk::default();
+        let (min, max) = VOID_PLATFORM;
+        for dx in 0..CHUNK_SIZE {
+            for dy in 0..CHUNK_SIZE {
+                for dz in 0..CHUNK_SIZE {
+                    let local_pos = IVec3::new(dx, dy, dz);
+                    let global_pos = chunk_pos * CHUNK_SIZE + local_pos;
+                    if global_pos.cmpge(min).all() && global_pos.cmple(max).all() {
+                        generated.chunk.map.insert(
+                            local_pos,
+                            BlockData::new(self.platform, false, BlockDirection::Front),
+                        );
+                    }
+                }
+            }
+        }
+        generated
+    }
+}
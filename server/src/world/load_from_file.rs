@@ -1,19 +1,37 @@
 use bevy::prelude::*;
-use ron::de::from_str;
-use shared::world::data::{ServerWorldMap, WorldSeed};
-use shared::world::get_game_folder;
-use shared::GameFolderPaths;
+use shared::world::data::{ServerWorldMap, WorldHeight, WorldSeed};
+use shared::world::{get_game_folder, BlockRegistry};
+use shared::{GameFolderPaths, GameServerConfig};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-use crate::world::data::SAVE_PATH;
+use crate::world::data::{Save, StorageFormat, TerrainDataHash, WorldData, SAVE_PATH};
+use crate::world::migration::{parse_binary_save, parse_save};
 use std::path::PathBuf;
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct WorldData {
-    pub seed: WorldSeed,
-    pub map: ServerWorldMap,
-    pub time: u64,
+pub fn get_saves_folder(game_folder_paths: &GameFolderPaths) -> PathBuf {
+    get_game_folder(Some(game_folder_paths)).join(SAVE_PATH)
+}
+
+pub fn save_path(saves_folder: &Path, world_name: &str, format: StorageFormat) -> PathBuf {
+    saves_folder.join(format!("{}.{}", world_name, format.extension()))
+}
+
+/// Finds the save file of a world, whatever format it is stored in
+pub fn find_save_file(saves_folder: &Path, world_name: &str) -> Option<(PathBuf, StorageFormat)> {
+    StorageFormat::ALL.iter().find_map(|format| {
+        let path = save_path(saves_folder, world_name, *format);
+        path.exists().then_some((path, *format))
+    })
+}
+
+/// Reads a save file, upgrading it to the current format if needed
+pub fn read_save(path: &Path, format: StorageFormat) -> Result<Save, Box<dyn std::error::Error>> {
+    match format {
+        StorageFormat::Ron => parse_save(&fs::read_to_string(path)?),
+        StorageFormat::Binary => parse_binary_save(&fs::read(path)?),
+    }
 }
 
 /// Charge les données combinées (carte et graine) d'un fichier
@@ -23,52 +41,45 @@ pub fn load_world_data(
 ) -> Result<WorldData, Box<dyn std::error::Error>> {
     // Obtenir le chemin du dossier de jeu
     let game_folder_path = app.world().get_resource::<GameFolderPaths>().unwrap();
-
-    // Construire le chemin complet du fichier
-    let file_path: PathBuf = get_game_folder(Some(game_folder_path))
-        .join(SAVE_PATH)
-        .join(format!("{file_name}.ron"));
-    let path: &Path = file_path.as_path();
+    let saves_folder = get_saves_folder(game_folder_path);
+    let assets_folder = Path::new(&game_folder_path.assets_folder_path);
 
     // Vérifier si le fichier existe
-    if !path.exists() {
+    let Some((file_path, format)) = find_save_file(&saves_folder, file_name) else {
         info!(
-            "World data file not found: {}. Generating default world and seed.",
-            file_path.display()
+            "World data file not found for {} in {}. Generating default world and seed.",
+            file_name,
+            saves_folder.display()
         );
+        let config = app.world().get_resource::<GameServerConfig>().unwrap();
+        let height = WorldHeight::default();
+        config.generator_settings.check_height(&height)?;
         return Ok(WorldData {
             map: ServerWorldMap {
                 name: file_name.to_string(),
                 ..Default::default()
             },
             seed: WorldSeed(rand::random::<u32>()),
+            preset: config.world_preset.clone(),
+            settings: config.generator_settings.clone(),
+            height,
+            terrain_data: Some(TerrainDataHash::of_folder(assets_folder)),
+            held_items: HashMap::new(),
             time: 0,
         });
-    }
-
-    // Lire le contenu du fichier
-    let contents: String = fs::read_to_string(path)?;
-    let world_data: WorldData = from_str(&contents)?; // Désérialiser les données combinées
-    Ok(world_data)
-}
+    };
 
-pub fn load_world_map(
-    file_name: &str,
-    app: &App,
-) -> Result<ServerWorldMap, Box<dyn std::error::Error>> {
-    let world_data = load_world_data(file_name, app)?;
-    Ok(world_data.map)
-}
-
-pub fn load_world_time(file_name: &str, app: &App) -> Result<u64, Box<dyn std::error::Error>> {
-    let world_data = load_world_data(file_name, app)?;
-    Ok(world_data.time)
-}
-
-pub fn load_world_seed(
-    file_name: &str,
-    app: &App,
-) -> Result<WorldSeed, Box<dyn std::error::Error>> {
-    let world_data = load_world_data(file_name, app)?;
-    Ok(world_data.seed)
+    // Older save formats are upgraded before being turned into the world
+    let save = read_save(&file_path, format)?;
+    if save
+        .terrain_data
+        .is_some_and(|terrain_data| terrain_data != TerrainDataHash::of_folder(assets_folder))
+    {
+        warn!(
+            "World {} was generated with other blocks, biomes or structures : the terrain of its unmodified chunks will change",
+            file_name
+        );
+    }
+    let blocks = app.world().resource::<BlockRegistry>();
+    Ok(save.into_world(blocks))
 }
@@ -0,0 +1,1137 @@
+use bevy::prelude::*;
+use bincode::Options;
+use ron::de::from_str;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::world::data::{Save, SAVE_FORMAT_VERSION};
+
+/// Only the version is read first, to know how the rest of the file must be parsed.
+/// Saves written before versioning existed have no `version` field and are version 0
+#[derive(Deserialize)]
+struct SaveHeader {
+    #[serde(default)]
+    version: u32,
+}
+
+/// Contents of a save file, in the serde format it was written with
+enum SaveContents<'a> {
+    Ron(&'a str),
+    Binary(&'a [u8]),
+}
+
+impl SaveContents<'_> {
+    fn read<T: DeserializeOwned>(&self) -> Result<T, Box<dyn std::error::Error>> {
+        match self {
+            SaveContents::Ron(contents) => Ok(from_str(contents)?),
+            SaveContents::Binary(bytes) => Ok(bincode::options().deserialize(bytes)?),
+        }
+    }
+}
+
+/// Save read with the layout of its format version, before being upgraded
+enum VersionedSave {
+    V0(v0::WorldData),
+    V1(v1::Save),
+    V2(v2::Save),
+    V3(v3::Save),
+    V4(v4::Save),
+    V5(v5::Save),
+    V6(v6::Save),
+    V7(v7::Save),
+    V8(v8::Save),
+    V9(v9::Save),
+    V10(v10::Save),
+    V11(v11::Save),
+    Current(Save),
+}
+
+impl VersionedSave {
+    fn read(version: u32, contents: &SaveContents) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match version {
+            0 => VersionedSave::V0(contents.read()?),
+            1 => VersionedSave::V1(contents.read()?),
+            2 => VersionedSave::V2(contents.read()?),
+            3 => VersionedSave::V3(contents.read()?),
+            4 => VersionedSave::V4(contents.read()?),
+            5 => VersionedSave::V5(contents.read()?),
+            6 => VersionedSave::V6(contents.read()?),
+            7 => VersionedSave::V7(contents.read()?),
+            8 => VersionedSave::V8(contents.read()?),
+            9 => VersionedSave::V9(contents.read()?),
+            10 => VersionedSave::V10(contents.read()?),
+            11 => VersionedSave::V11(contents.read()?),
+            _ => VersionedSave::Current(contents.read()?),
+        })
+    }
+
+    /// Upgrades the save one format version at a time, up to the current `Save` layout
+    fn migrate(self) -> Save {
+        let mut save = self;
+        loop {
+            save = match save {
+                VersionedSave::V0(old) => VersionedSave::V1(v0::migrate(old)),
+                VersionedSave::V1(old) => VersionedSave::V2(v1::migrate(old)),
+                VersionedSave::V2(old) => VersionedSave::V3(v2::migrate(old)),
+                VersionedSave::V3(old) => VersionedSave::V4(v3::migrate(old)),
+                VersionedSave::V4(old) => VersionedSave::V5(v4::migrate(old)),
+                VersionedSave::V5(old) => VersionedSave::V6(v5::migrate(old)),
+                VersionedSave::V6(old) => VersionedSave::V7(v6::migrate(old)),
+                VersionedSave::V7(old) => VersionedSave::V8(v7::migrate(old)),
+                VersionedSave::V8(old) => VersionedSave::V9(v8::migrate(old)),
+                VersionedSave::V9(old) => VersionedSave::V10(v9::migrate(old)),
+                VersionedSave::V10(old) => VersionedSave::V11(v10::migrate(old)),
+                VersionedSave::V11(old) => VersionedSave::Current(v11::migrate(old)),
+                VersionedSave::Current(save) => return save,
+            }
+        }
+    }
+}
+
+fn upgrade_save(version: u32, contents: SaveContents) -> Result<Save, Box<dyn std::error::Error>> {
+    if version > SAVE_FORMAT_VERSION {
+        return Err(format!(
+            "save format version {} is newer than the supported version {}",
+            version, SAVE_FORMAT_VERSION
+        )
+        .into());
+    }
+
+    let save = VersionedSave::read(version, &contents)?.migrate();
+
+    if version != SAVE_FORMAT_VERSION {
+        info!(
+            "Save migrated from format version {} to {}",
+            version, SAVE_FORMAT_VERSION
+        );
+    }
+
+    Ok(save)
+}
+
+/// Parses the contents of a save file of any known version,
+/// upgrading it step by step to the current `Save` layout
+pub fn parse_save(contents: &str) -> Result<Save, Box<dyn std::error::Error>> {
+    let header: SaveHeader = from_str(contents)?;
+    upgrade_save(header.version, SaveContents::Ron(contents))
+}
+
+/// Binary saves only exist since format version 1, and start with their version
+pub fn parse_binary_save(bytes: &[u8]) -> Result<Save, Box<dyn std::error::Error>> {
+    let version: u32 = bincode::options()
+        .allow_trailing_bytes()
+        .deserialize(bytes)?;
+    if version == 0 {
+        return Err("unsupported binary save format version 0".into());
+    }
+    upgrade_save(version, SaveContents::Binary(bytes))
+}
+
+/// Layout of saves written before format versioning : blocks were stored
+/// directly as `BlockId` variants. The enum is frozen here as it was back then,
+/// so that these saves stay readable whatever happens to the current one
+mod v0 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{BlockDirection, WorldSeed};
+    use std::collections::HashMap;
+
+    use super::v1::{Save, SavedBlock, SavedChunk};
+
+    #[derive(Deserialize)]
+    pub struct WorldData {
+        seed: Seed,
+        map: ServerWorldMap,
+        time: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct Seed(u32);
+
+    #[derive(Deserialize)]
+    struct ServerWorldMap {
+        name: String,
+        map: HashMap<IVec3, ServerChunk>,
+        player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    struct ServerChunk {
+        map: HashMap<IVec3, BlockData>,
+        ts: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct BlockData {
+        id: BlockId,
+        flipped: bool,
+        direction: Direction,
+    }
+
+    #[derive(Deserialize)]
+    enum Direction {
+        Front,
+        Right,
+        Back,
+        Left,
+    }
+
+    #[derive(Deserialize, Clone, Copy)]
+    enum BlockId {
+        Dirt,
+        Debug,
+        Grass,
+        Stone,
+        OakLog,
+        OakPlanks,
+        OakLeaves,
+        Sand,
+        Ice,
+        Glass,
+        Bedrock,
+        Dandelion,
+        Poppy,
+        Cobblestone,
+        Snow,
+        SpruceLeaves,
+        SpruceLog,
+    }
+
+    // In version 0, the palette is simply the order of the enum variants
+    const PALETTE: [&str; 17] = [
+        "dirt",
+        "debug",
+        "grass",
+        "stone",
+        "oak_log",
+        "oak_planks",
+        "oak_leaves",
+        "sand",
+        "ice",
+        "glass",
+        "bedrock",
+        "dandelion",
+        "poppy",
+        "cobblestone",
+        "snow",
+        "spruce_leaves",
+        "spruce_log",
+    ];
+
+    pub fn migrate(old: WorldData) -> Save {
+        let chunks = old
+            .map
+            .map
+            .into_iter()
+            .map(|(chunk_pos, chunk)| {
+                let blocks = chunk
+                    .map
+                    .into_iter()
+                    .map(|(pos, block)| SavedBlock {
+                        pos,
+                        id: block.id as u16,
+                        flipped: block.flipped,
+                        direction: match block.direction {
+                            Direction::Front => BlockDirection::Front,
+                            Direction::Right => BlockDirection::Right,
+                            Direction::Back => BlockDirection::Back,
+                            Direction::Left => BlockDirection::Left,
+                        },
+                    })
+                    .collect();
+                (
+                    chunk_pos,
+                    SavedChunk {
+                        blocks,
+                        ts: chunk.ts,
+                    },
+                )
+            })
+            .collect();
+
+        Save {
+            version: 1,
+            name: old.map.name,
+            seed: WorldSeed(old.seed.0),
+            time: old.time,
+            palette: PALETTE.iter().map(|name| name.to_string()).collect(),
+            chunks,
+            player_positions: old.map.player_positions,
+        }
+    }
+}
+
+/// Version 1 stored each block of a chunk with its position, instead of a paletted container
+mod v1 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{BlockDirection, PalettedContainer, WorldSeed};
+    use std::collections::HashMap;
+
+    use super::{v2, v3};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: Vec<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedBlock {
+        pub pos: IVec3,
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+    }
+
+    pub fn migrate(old: Save) -> v2::Save {
+        let chunks = old
+            .chunks
+            .into_iter()
+            .map(|(chunk_pos, chunk)| {
+                let mut blocks = PalettedContainer::default();
+                for block in chunk.blocks {
+                    blocks.insert(
+                        block.pos,
+                        v3::SavedBlock {
+                            id: block.id,
+                            flipped: block.flipped,
+                            direction: block.direction,
+                        },
+                    );
+                }
+                (
+                    chunk_pos,
+                    v3::SavedChunk {
+                        blocks,
+                        ts: chunk.ts,
+                    },
+                )
+            })
+            .collect();
+
+        v2::Save {
+            version: 2,
+            name: old.name,
+            seed: old.seed,
+            time: old.time,
+            palette: old.palette,
+            chunks,
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 2 had no pending writes of features crossing chunk borders
+mod v2 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::WorldSeed;
+    use std::collections::HashMap;
+
+    use super::v3::{self, SavedChunk};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    pub fn migrate(old: Save) -> v3::Save {
+        v3::Save {
+            version: 3,
+            name: old.name,
+            seed: old.seed,
+            time: old.time,
+            palette: old.palette,
+            chunks: old.chunks,
+            pending_writes: HashMap::new(),
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 3 had no fluid levels
+mod v3 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{BlockDirection, PalettedContainer, WorldSeed};
+    use std::collections::HashMap;
+
+    use super::{v4, v7};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: PalettedContainer<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize, Clone, Copy, PartialEq)]
+    pub struct SavedBlock {
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+    }
+
+    fn migrate_blocks(blocks: PalettedContainer<SavedBlock>) -> PalettedContainer<v7::SavedBlock> {
+        blocks.convert(|block| {
+            Some(v7::SavedBlock {
+                id: block.id,
+                flipped: block.flipped,
+                direction: block.direction,
+                level: 0,
+            })
+        })
+    }
+
+    pub fn migrate(old: Save) -> v4::Save {
+        v4::Save {
+            version: 4,
+            name: old.name,
+            seed: old.seed,
+            time: old.time,
+            palette: old.palette,
+            chunks: old
+                .chunks
+                .into_iter()
+                .map(|(chunk_pos, chunk)| {
+                    (
+                        chunk_pos,
+                        v7::SavedChunk {
+                            blocks: migrate_blocks(chunk.blocks),
+                            ts: chunk.ts,
+                        },
+                    )
+                })
+                .collect(),
+            pending_writes: old
+                .pending_writes
+                .into_iter()
+                .map(|(chunk_pos, blocks)| (chunk_pos, migrate_blocks(blocks)))
+                .collect(),
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 4 had no world presets, all worlds used the default terrain
+mod v4 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{PalettedContainer, WorldPreset, WorldSeed};
+    use std::collections::HashMap;
+
+    use super::v5;
+    use super::v7::{SavedBlock, SavedChunk};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    pub fn migrate(old: Save) -> v5::Save {
+        v5::Save {
+            version: 5,
+            name: old.name,
+            seed: old.seed,
+            preset: WorldPreset::Default,
+            time: old.time,
+            palette: old.palette,
+            chunks: old.chunks,
+            pending_writes: old.pending_writes,
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 5 had no height limits, the clients only loaded the chunk layers 0 to 8
+mod v5 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{PalettedContainer, WorldHeight, WorldPreset, WorldSeed};
+    use std::collections::HashMap;
+
+    use super::v6;
+    use super::v7::{SavedBlock, SavedChunk};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    pub fn migrate(old: Save) -> v6::Save {
+        v6::Save {
+            version: 6,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            height: WorldHeight::default(),
+            time: old.time,
+            palette: old.palette,
+            chunks: old.chunks,
+            pending_writes: old.pending_writes,
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 6 had no generator settings, all worlds used the default ones
+mod v6 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{
+        GeneratorSettings, PalettedContainer, WorldHeight, WorldPreset, WorldSeed,
+    };
+    use std::collections::HashMap;
+
+    use super::v7::{self, SavedBlock, SavedChunk};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub height: WorldHeight,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    pub fn migrate(old: Save) -> v7::Save {
+        v7::Save {
+            version: 7,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            settings: GeneratorSettings::default(),
+            height: old.height,
+            time: old.time,
+            palette: old.palette,
+            chunks: old.chunks,
+            pending_writes: old.pending_writes,
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 7 had no open state for blocks such as doors
+mod v7 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{
+        BlockDirection, GeneratorSettings, PalettedContainer, WorldHeight, WorldPreset, WorldSeed,
+    };
+    use std::collections::HashMap;
+
+    use super::v8;
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub settings: GeneratorSettings,
+        pub height: WorldHeight,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: PalettedContainer<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize, Clone, Copy, PartialEq)]
+    pub struct SavedBlock {
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+        pub level: u8,
+    }
+
+    fn migrate_blocks(blocks: PalettedContainer<SavedBlock>) -> PalettedContainer<v8::SavedBlock> {
+        blocks.convert(|block| {
+            Some(v8::SavedBlock {
+                id: block.id,
+                flipped: block.flipped,
+                direction: block.direction,
+                level: block.level,
+                open: false,
+            })
+        })
+    }
+
+    pub fn migrate(old: Save) -> v8::Save {
+        v8::Save {
+            version: 8,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            settings: old.settings,
+            height: old.height,
+            time: old.time,
+            palette: old.palette,
+            chunks: old
+                .chunks
+                .into_iter()
+                .map(|(chunk_pos, chunk)| {
+                    (
+                        chunk_pos,
+                        v8::SavedChunk {
+                            blocks: migrate_blocks(chunk.blocks),
+                            ts: chunk.ts,
+                        },
+                    )
+                })
+                .collect(),
+            pending_writes: old
+                .pending_writes
+                .into_iter()
+                .map(|(chunk_pos, blocks)| (chunk_pos, migrate_blocks(blocks)))
+                .collect(),
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 8 removed the features of neighbours from the pending writes once placed,
+/// and saved every chunk whose features crossed its borders
+mod v8 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{
+        BlockDirection, GeneratorSettings, PalettedContainer, WorldHeight, WorldPreset, WorldSeed,
+    };
+    use std::collections::HashMap;
+
+    use super::v9;
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub settings: GeneratorSettings,
+        pub height: WorldHeight,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: PalettedContainer<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize, Clone, Copy, PartialEq)]
+    pub struct SavedBlock {
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+        pub level: u8,
+        pub open: bool,
+    }
+
+    fn migrate_blocks(blocks: PalettedContainer<SavedBlock>) -> PalettedContainer<v9::SavedBlock> {
+        blocks.convert(|block| {
+            Some(v9::SavedBlock {
+                id: block.id,
+                flipped: block.flipped,
+                direction: block.direction,
+                level: block.level,
+                open: block.open,
+            })
+        })
+    }
+
+    pub fn migrate(old: Save) -> v9::Save {
+        v9::Save {
+            version: 9,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            settings: old.settings,
+            height: old.height,
+            time: old.time,
+            palette: old.palette,
+            // Chunks whose features crossed their borders were all saved
+            features_placed: old.chunks.keys().copied().collect(),
+            chunks: old
+                .chunks
+                .into_iter()
+                .map(|(chunk_pos, chunk)| {
+                    (
+                        chunk_pos,
+                        v9::SavedChunk {
+                            blocks: migrate_blocks(chunk.blocks),
+                            ts: chunk.ts,
+                        },
+                    )
+                })
+                .collect(),
+            pending_writes: old
+                .pending_writes
+                .into_iter()
+                .map(|(chunk_pos, blocks)| (chunk_pos, migrate_blocks(blocks)))
+                .collect(),
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 9 saved every chunk whose features crossing its borders were placed, and all the
+/// pending writes, without knowing which chunks these features went into
+mod v9 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{
+        BlockDirection, GeneratorSettings, PalettedContainer, WorldHeight, WorldPreset, WorldSeed,
+    };
+    use std::collections::{HashMap, HashSet};
+
+    use super::v10;
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub settings: GeneratorSettings,
+        pub height: WorldHeight,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub features_placed: HashSet<IVec3>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: PalettedContainer<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize, Clone, Copy, PartialEq)]
+    pub struct SavedBlock {
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+        pub level: u8,
+        pub open: bool,
+    }
+
+    fn migrate_blocks(blocks: PalettedContainer<SavedBlock>) -> PalettedContainer<v10::SavedBlock> {
+        blocks.convert(|block| {
+            Some(v10::SavedBlock {
+                id: block.id,
+                flipped: block.flipped,
+                direction: block.direction,
+                level: block.level,
+                open: block.open,
+            })
+        })
+    }
+
+    pub fn migrate(old: Save) -> v10::Save {
+        // The chunks features went into were not recorded. They are assumed to be the saved
+        // or pending neighbours, as the built-in features are smaller than a chunk
+        let stored: HashSet<IVec3> = old
+            .chunks
+            .keys()
+            .chain(old.pending_writes.keys())
+            .copied()
+            .collect();
+        let features_placed = old
+            .features_placed
+            .into_iter()
+            .map(|chunk_pos| {
+                let targets = stored
+                    .iter()
+                    .filter(|target| {
+                        **target != chunk_pos && (**target - chunk_pos).abs().max_element() <= 1
+                    })
+                    .copied()
+                    .collect();
+                (chunk_pos, targets)
+            })
+            .collect();
+
+        v10::Save {
+            version: 10,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            settings: old.settings,
+            height: old.height,
+            time: old.time,
+            palette: old.palette,
+            features_placed,
+            chunks: old
+                .chunks
+                .into_iter()
+                .map(|(chunk_pos, chunk)| {
+                    (
+                        chunk_pos,
+                        v10::SavedChunk {
+                            blocks: migrate_blocks(chunk.blocks),
+                            ts: chunk.ts,
+                        },
+                    )
+                })
+                .collect(),
+            pending_writes: old
+                .pending_writes
+                .into_iter()
+                .map(|(chunk_pos, blocks)| (chunk_pos, migrate_blocks(blocks)))
+                .collect(),
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 10 did not record the data files the terrain was generated with
+mod v10 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{
+        BlockDirection, GeneratorSettings, PalettedContainer, WorldHeight, WorldPreset, WorldSeed,
+    };
+    use std::collections::{HashMap, HashSet};
+
+    use super::v11;
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub settings: GeneratorSettings,
+        pub height: WorldHeight,
+        pub time: u64,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub features_placed: HashMap<IVec3, HashSet<IVec3>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: PalettedContainer<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize, Clone, Copy, PartialEq)]
+    pub struct SavedBlock {
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+        pub level: u8,
+        pub open: bool,
+    }
+
+    fn migrate_blocks(blocks: PalettedContainer<SavedBlock>) -> PalettedContainer<v11::SavedBlock> {
+        blocks.convert(|block| {
+            Some(v11::SavedBlock {
+                id: block.id,
+                flipped: block.flipped,
+                direction: block.direction,
+                level: block.level,
+                open: block.open,
+            })
+        })
+    }
+
+    pub fn migrate(old: Save) -> v11::Save {
+        v11::Save {
+            version: 11,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            settings: old.settings,
+            height: old.height,
+            time: old.time,
+            terrain_data: None,
+            palette: old.palette,
+            chunks: old
+                .chunks
+                .into_iter()
+                .map(|(chunk_pos, chunk)| {
+                    (
+                        chunk_pos,
+                        v11::SavedChunk {
+                            blocks: migrate_blocks(chunk.blocks),
+                            ts: chunk.ts,
+                        },
+                    )
+                })
+                .collect(),
+            pending_writes: old
+                .pending_writes
+                .into_iter()
+                .map(|(chunk_pos, blocks)| (chunk_pos, migrate_blocks(blocks)))
+                .collect(),
+            features_placed: old.features_placed,
+            player_positions: old.player_positions,
+        }
+    }
+}
+
+/// Version 11 did not record the items handed out to the players
+mod v11 {
+    use bevy::math::{IVec3, Vec3};
+    use serde::Deserialize;
+    use shared::world::{
+        BlockDirection, GeneratorSettings, PalettedContainer, WorldHeight, WorldPreset, WorldSeed,
+    };
+    use std::collections::{HashMap, HashSet};
+
+    use crate::world::data::{self, TerrainDataHash};
+
+    #[derive(Deserialize)]
+    pub struct Save {
+        // Only read by the header, but binary saves need every field in order
+        #[allow(dead_code)]
+        pub version: u32,
+        pub name: String,
+        pub seed: WorldSeed,
+        pub preset: WorldPreset,
+        pub settings: GeneratorSettings,
+        pub height: WorldHeight,
+        pub time: u64,
+        pub terrain_data: Option<TerrainDataHash>,
+        pub palette: Vec<String>,
+        pub chunks: HashMap<IVec3, SavedChunk>,
+        pub pending_writes: HashMap<IVec3, PalettedContainer<SavedBlock>>,
+        pub features_placed: HashMap<IVec3, HashSet<IVec3>>,
+        pub player_positions: HashMap<u64, Vec3>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SavedChunk {
+        pub blocks: PalettedContainer<SavedBlock>,
+        pub ts: u64,
+    }
+
+    #[derive(Deserialize, Clone, Copy, PartialEq)]
+    pub struct SavedBlock {
+        pub id: u16,
+        pub flipped: bool,
+        pub direction: BlockDirection,
+        pub level: u8,
+        pub open: bool,
+    }
+
+    fn migrate_blocks(
+        blocks: PalettedContainer<SavedBlock>,
+    ) -> PalettedContainer<data::SavedBlock> {
+        blocks.convert(|block| {
+            Some(data::SavedBlock {
+                id: block.id,
+                flipped: block.flipped,
+                direction: block.direction,
+                level: block.level,
+                open: block.open,
+            })
+        })
+    }
+
+    pub fn migrate(old: Save) -> data::Save {
+        data::Save {
+            version: 12,
+            name: old.name,
+            seed: old.seed,
+            preset: old.preset,
+            settings: old.settings,
+            height: old.height,
+            time: old.time,
+            terrain_data: old.terrain_data,
+            palette: old.palette,
+            chunks: old
+                .chunks
+                .into_iter()
+                .map(|(chunk_pos, chunk)| {
+                    (
+                        chunk_pos,
+                        data::SavedChunk {
+                            blocks: migrate_blocks(chunk.blocks),
+                            ts: chunk.ts,
+                        },
+                    )
+                })
+                .collect(),
+            pending_writes: old
+                .pending_writes
+                .into_iter()
+                .map(|(chunk_pos, blocks)| (chunk_pos, migrate_blocks(blocks)))
+                .collect(),
+            features_placed: old.features_placed,
+            player_positions: old.player_positions,
+            held_items: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::IVec3;
+    use shared::world::BlockDirection;
+    use std::collections::HashSet;
+
+    /// Name of the block saved at a position of a chunk
+    fn block_name(save: &Save, chunk_pos: IVec3, pos: IVec3) -> Option<&str> {
+        let block = save.chunks.get(&chunk_pos)?.blocks.get(&pos)?;
+        save.palette.get(block.id as usize).map(String::as_str)
+    }
+
+    #[test]
+    fn unversioned_saves_are_upgraded_to_the_current_format() {
+        let save = parse_save(include_str!("../../tests/fixtures/save_v0.ron")).unwrap();
+        assert_eq!(save.version, SAVE_FORMAT_VERSION);
+        assert_eq!(save.name, "old_world");
+        assert_eq!(save.seed.0, 1234);
+        assert_eq!(save.time, 4200);
+        assert_eq!(save.player_positions[&7], Vec3::new(1.5, 80., -2.5));
+        assert!(save.pending_writes.is_empty());
+        assert_eq!(save.terrain_data, None);
+        assert!(save.held_items.is_empty());
+        // Saved chunks placed their features, which could not reach the other one
+        assert_eq!(save.features_placed.len(), 2);
+        assert!(save.features_placed.values().all(HashSet::is_empty));
+
+        assert_eq!(save.chunks.len(), 2);
+        assert_eq!(block_name(&save, IVec3::ZERO, IVec3::ZERO), Some("bedrock"));
+        assert_eq!(
+            block_name(&save, IVec3::ZERO, IVec3::new(1, 2, 3)),
+            Some("grass")
+        );
+        assert_eq!(block_name(&save, IVec3::ZERO, IVec3::new(2, 2, 2)), None);
+        assert_eq!(
+            block_name(&save, IVec3::new(-1, 2, 3), IVec3::splat(15)),
+            Some("spruce_log")
+        );
+
+        let log = save.chunks[&IVec3::ZERO]
+            .blocks
+            .get(&IVec3::new(4, 5, 6))
+            .unwrap();
+        assert_eq!(save.palette[log.id as usize], "oak_log");
+        assert!(log.flipped);
+        assert_eq!(log.direction, BlockDirection::Right);
+        assert_eq!((log.level, log.open), (0, false));
+    }
+
+    #[test]
+    fn binary_saves_are_upgraded_to_the_current_format() {
+        // Binary saves only exist since format version 1
+        let save = parse_binary_save(include_bytes!("../../tests/fixtures/save_v1.bin")).unwrap();
+        assert_eq!(save.version, SAVE_FORMAT_VERSION);
+        assert_eq!(save.name, "binary_world");
+        assert_eq!(save.seed.0, 99);
+        assert_eq!(save.time, 123);
+        assert_eq!(save.palette, vec!["stone", "oak_planks"]);
+        assert_eq!(save.player_positions[&3], Vec3::new(0., 64., 0.));
+
+        let chunk_pos = IVec3::new(2, -1, 0);
+        assert_eq!(block_name(&save, chunk_pos, IVec3::ZERO), Some("stone"));
+        let planks = save.chunks[&chunk_pos]
+            .blocks
+            .get(&IVec3::new(3, 4, 5))
+            .unwrap();
+        assert_eq!(save.palette[planks.id as usize], "oak_planks");
+        assert!(planks.flipped);
+        assert_eq!(planks.direction, BlockDirection::Back);
+        assert_eq!(save.chunks[&chunk_pos].blocks.len(), 2);
+    }
+
+    #[test]
+    fn saves_newer_than_this_build_are_rejected() {
+        let contents = format!("(version: {})", SAVE_FORMAT_VERSION + 1);
+        assert!(parse_save(&contents).is_err());
+
+        let bytes = bincode::options()
+            .serialize(&(SAVE_FORMAT_VERSION + 1))
+            .unwrap();
+        let error = parse_binary_save(&bytes).err().unwrap();
+        assert!(error.to_string().contains("newer"));
+    }
+}
@@ -1,27 +1,190 @@
-mod data;
+pub mod data;
+pub mod dropped_items;
+pub mod fluids;
 pub mod generation;
+pub mod generators;
 pub mod load_from_file;
+pub mod migration;
+pub mod preview;
 pub mod save;
+pub mod structures;
+
+use std::collections::HashMap;
 
 use bevy::prelude::Event;
 use bevy::prelude::EventReader;
 use bevy::prelude::IVec3;
 use bevy::prelude::ResMut;
 use bevy::prelude::*;
+use shared::messages::PlayerId;
+use shared::world::global_block_to_chunk_pos;
 use shared::world::BlockData;
+use shared::world::BlockDefinition;
+use shared::world::BlockRegistry;
+use shared::world::ItemRegistry;
+use shared::world::ItemStack;
 use shared::world::ServerWorldMap;
+use shared::world::WorldHeight;
+
+use self::dropped_items::DroppedItems;
+use self::fluids::FluidUpdates;
+use crate::inventory::HeldItems;
+
+/// Breaking time a client may save, as its messages can reach the server closer to each other
+/// than they were sent
+const BREAKING_TIME_TOLERANCE: f32 = 0.25;
 
 #[derive(Event, Debug)]
 pub struct BlockInteractionEvent {
+    pub player: PlayerId,
     pub position: IVec3,
     pub block_type: Option<BlockData>, // None = suppression, Some = ajout
+    /// Block the player was breaking when the message arrived, checked when removing a block
+    pub breaking: Option<BlockBreaking>,
+}
+
+/// Block a player started breaking
+#[derive(Debug, Clone, Copy)]
+pub struct BlockBreaking {
+    pub position: IVec3,
+    /// Tool the player holds, which the server handed out to them. Its durability is the one
+    /// the client knows, telling apart the tools of a kind the player holds
+    pub tool: Option<ItemStack>,
+    /// Elapsed seconds of the server when breaking started
+    pub started: f32,
+}
+
+/// Blocks being broken by players, to check that breaking them took long enough
+#[derive(Resource, Debug, Default)]
+pub struct BlockBreakings(pub HashMap<PlayerId, BlockBreaking>);
+
+/// Whether a player may break a block now : they must have started breaking it
+/// for as long as their tool needs
+fn can_break(
+    breaking: Option<&BlockBreaking>,
+    position: IVec3,
+    block: &BlockDefinition,
+    items: &ItemRegistry,
+    now: f32,
+) -> bool {
+    let Some(breaking) = breaking.filter(|breaking| breaking.position == position) else {
+        return false;
+    };
+    let tool = match breaking.tool.map(|tool| tool.item_id) {
+        Some(tool) if items.contains(tool) => Some(items.get(tool)),
+        Some(_) => return false,
+        None => None,
+    };
+    block
+        .breaking_time(tool)
+        .is_some_and(|time| now - breaking.started + BREAKING_TIME_TOLERANCE >= time)
 }
 
 pub fn handle_block_interactions(
     mut world_map: ResMut<ServerWorldMap>,
+    mut fluids: ResMut<FluidUpdates>,
+    mut dropped_items: ResMut<DroppedItems>,
+    world_height: Res<WorldHeight>,
+    (blocks, items): (Res<BlockRegistry>, Res<ItemRegistry>),
+    mut held_items: ResMut<HeldItems>,
+    time: Res<Time>,
     mut events: EventReader<BlockInteractionEvent>,
 ) {
     for event in events.read() {
+        if !world_height.contains(event.position.y) {
+            warn!(
+                "Block interaction outside of the world at {:?}",
+                event.position
+            );
+            continue;
+        }
+        if let Some(block) = event.block_type.filter(|block| !blocks.contains(block.id)) {
+            warn!(
+                "Unknown block {:?} placed at {:?}",
+                block.id, event.position
+            );
+            continue;
+        }
+        if let Some(block) = event.block_type {
+            // Placing a block uses up its item. The upper half of doors comes with the lower
+            // one, which must have been placed just before
+            let door_top = blocks.get(block.id).other_half(&block, event.position)
+                == Some(event.position - IVec3::Y);
+            let allowed = if door_top {
+                world_map
+                    .get_block_by_coordinates(&(event.position - IVec3::Y))
+                    .is_some_and(|lower| lower.id == block.id)
+            } else {
+                items
+                    .placing(block.id)
+                    .is_some_and(|item| held_items.take_at_most(event.player, item, 1) > 0)
+            };
+            if !allowed {
+                warn!(
+                    "Player {} placed a block it does not hold at {:?}",
+                    event.player, event.position
+                );
+                // The client placed the block on its side : it gets removed with the chunk
+                world_map
+                    .chunks_to_update
+                    .push(global_block_to_chunk_pos(&event.position));
+                continue;
+            }
+        } else {
+            let breaking = event.breaking.as_ref();
+            let tool = breaking.and_then(|breaking| breaking.tool);
+            let allowed = world_map
+                .get_block_by_coordinates(&event.position)
+                .is_none_or(|block| {
+                    blocks.contains(block.id)
+                        && can_break(
+                            breaking,
+                            event.position,
+                            blocks.get(block.id),
+                            &items,
+                            time.elapsed_seconds(),
+                        )
+                });
+            if !allowed {
+                warn!(
+                    "Player {} broke the block at {:?} too early",
+                    event.player, event.position
+                );
+                // The client removed the block on its side : it gets it back with the chunk
+                world_map
+                    .chunks_to_update
+                    .push(global_block_to_chunk_pos(&event.position));
+                continue;
+            }
+
+            // Blocks needing a tool only drop items when mined with a good enough one
+            if let Some(block) = world_map.get_block_by_coordinates(&event.position) {
+                let definition = blocks.get(block.id);
+                let tool_definition = tool.map(|tool| items.get(tool.item_id));
+                // Blocks broken instantly do not wear tools out
+                if let Some(tool) = tool.filter(|_| {
+                    definition
+                        .breaking_time(tool_definition)
+                        .is_some_and(|time| time > 0.)
+                }) {
+                    held_items.wear(event.player, tool);
+                }
+                if definition.is_harvested_by(tool_definition) {
+                    for (item_id, nb) in definition.get_drops(1, &items) {
+                        let stack = ItemStack {
+                            item_id,
+                            item_type: items.get(item_id).default_type(),
+                            nb,
+                        };
+                        dropped_items.spawn_block_drop(stack, event.position);
+                    }
+                }
+            }
+        }
+
+        // Nearby fluids may flow into the broken block, or around the placed one
+        fluids.schedule_around(event.position);
+
         match &event.block_type {
             Some(block) => {
                 // Ajouter un bloc
@@ -29,6 +192,25 @@ pub fn handle_block_interactions(
                 debug!("Block added at {:?}: {:?}", event.position, block);
             }
             None => {
+                // Both halves of doors are broken together, the items only dropping once
+                let other_half = world_map
+                    .get_block_by_coordinates(&event.position)
+                    .filter(|block| blocks.contains(block.id))
+                    .and_then(|block| {
+                        blocks
+                            .get(block.id)
+                            .other_half(block, event.position)
+                            .filter(|other| {
+                                world_map
+                                    .get_block_by_coordinates(other)
+                                    .is_some_and(|other| other.id == block.id)
+                            })
+                    });
+                if let Some(other_half) = other_half {
+                    world_map.remove_block_by_coordinates(&other_half);
+                    fluids.schedule_around(other_half);
+                }
+
                 // Supprimer un bloc
                 world_map.remove_block_by_coordinates(&event.position);
                 info!("Block removed at {:?}", event.position);
@@ -0,0 +1,210 @@
+use bevy::math::{IVec2, IVec3};
+use shared::world::{
+    block_to_chunk_coord, BiomeTint, BlockId, BlockRegistry, ServerWorldMap, WorldHeight,
+};
+use shared::CHUNK_SIZE;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::generation::insert_generated_chunk;
+use super::generators::WorldGenerator;
+
+/// Colour of the columns without any block
+const EMPTY_COLOR: [u8; 3] = [0, 0, 0];
+
+/// Top of a column of the previewed region
+struct Column {
+    /// Highest block that is not a liquid
+    ground: Option<i32>,
+    /// Highest block, liquids included
+    surface: Option<BlockId>,
+    biome: Option<String>,
+    tint: Option<BiomeTint>,
+}
+
+/// Generates the square of chunk columns within `radius` of `center`, over the whole world
+/// height, then writes `height.png`, `biome.png` and `surface.png` into `output`.
+/// Features crossing chunk borders are placed as the server does.
+/// Returns a report of the timings, to be printed
+pub fn generate_preview(
+    generator: &dyn WorldGenerator,
+    seed: u32,
+    center: IVec2,
+    radius: i32,
+    height: WorldHeight,
+    blocks: &BlockRegistry,
+    output: &Path,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let min_chunk = center - IVec2::splat(radius);
+    let max_chunk = center + IVec2::splat(radius);
+    let chunk_layers = block_to_chunk_coord(height.min_y)..=block_to_chunk_coord(height.max_y);
+
+    let mut world_map = ServerWorldMap::default();
+    let mut chunk_times = Vec::new();
+    let start = Instant::now();
+    for cx in min_chunk.x..=max_chunk.x {
+        for cz in min_chunk.y..=max_chunk.y {
+            for cy in chunk_layers.clone() {
+                let chunk_pos = IVec3::new(cx, cy, cz);
+                let chunk_start = Instant::now();
+                let generated = generator.generate_chunk(chunk_pos, seed);
+                chunk_times.push(chunk_start.elapsed());
+                insert_generated_chunk(&mut world_map, chunk_pos, generated);
+            }
+        }
+    }
+    let generation_time = start.elapsed();
+
+    let biome_start = Instant::now();
+    let mut biomes: HashMap<IVec2, (String, BiomeTint)> = HashMap::new();
+    for cx in min_chunk.x..=max_chunk.x {
+        for cz in min_chunk.y..=max_chunk.y {
+            let chunk_pos = IVec3::new(cx, 0, cz);
+            let column_biomes = generator.column_biomes(chunk_pos, seed);
+            for (index, biome) in column_biomes.into_iter().enumerate() {
+                // same order as `ServerChunk::tints`
+                let local = IVec2::new(index as i32 / CHUNK_SIZE, index as i32 % CHUNK_SIZE);
+                biomes.insert(
+                    IVec2::new(cx, cz) * CHUNK_SIZE + local,
+                    (biome.name.clone(), biome.tint()),
+                );
+            }
+        }
+    }
+    let biome_time = biome_start.elapsed();
+
+    // columns are stored along x first, as image rows
+    let min_block = min_chunk * CHUNK_SIZE;
+    let size = (radius * 2 + 1) * CHUNK_SIZE;
+    let mut columns = Vec::with_capacity((size * size) as usize);
+    for z in 0..size {
+        for x in 0..size {
+            let pos = min_block + IVec2::new(x, z);
+            let mut column = Column {
+                ground: None,
+                surface: None,
+                biome: None,
+                tint: None,
+            };
+            for y in (height.min_y..=height.max_y).rev() {
+                let Some(block) = world_map.get_block_by_coordinates(&IVec3::new(pos.x, y, pos.y))
+                else {
+                    continue;
+                };
+                if column.surface.is_none() {
+                    column.surface = Some(block.id);
+                }
+                if !blocks.get(block.id).is_liquid() {
+                    column.ground = Some(y);
+                    break;
+                }
+            }
+            if let Some((name, tint)) = biomes.get(&pos) {
+                column.biome = Some(name.clone());
+                column.tint = Some(*tint);
+            }
+            columns.push(column);
+        }
+    }
+
+    fs::create_dir_all(output)?;
+    let range = (height.max_y - height.min_y).max(1) as f32;
+    write_png(
+        &output.join("height.png"),
+        size as u32,
+        &columns,
+        |column| {
+            column.ground.map_or(EMPTY_COLOR, |y| {
+                [((y - height.min_y) as f32 / range * 255.0).round() as u8; 3]
+            })
+        },
+    )?;
+    if !biomes.is_empty() {
+        write_png(&output.join("biome.png"), size as u32, &columns, |column| {
+            column.biome.as_deref().map_or(EMPTY_COLOR, biome_color)
+        })?;
+    }
+    write_png(
+        &output.join("surface.png"),
+        size as u32,
+        &columns,
+        |column| {
+            let Some(block) = column.surface else {
+                return EMPTY_COLOR;
+            };
+            let block = blocks.get(block);
+            match (block.biome_color(), column.tint) {
+                (Some(color), Some(tint)) => tint.get(color),
+                _ => block.map_color,
+            }
+        },
+    )?;
+
+    let mut report = format!(
+        "Generated {} chunks ({} columns) in {:.2?}\n",
+        chunk_times.len(),
+        columns.len(),
+        generation_time
+    );
+    report += &timing_stats("generate_chunk", &mut chunk_times);
+    if !biomes.is_empty() {
+        report += &format!(
+            "Biomes of {} columns determined in {:.2?}\n",
+            biomes.len(),
+            biome_time
+        );
+        let names: HashSet<&String> = biomes.values().map(|(name, _)| name).collect();
+        let mut names: Vec<&String> = names.into_iter().collect();
+        names.sort();
+        for name in names {
+            let [r, g, b] = biome_color(name);
+            report += &format!("  {} is #{:02x}{:02x}{:02x}\n", name, r, g, b);
+        }
+    }
+    Ok(report)
+}
+
+fn timing_stats(name: &str, times: &mut [Duration]) -> String {
+    if times.is_empty() {
+        return String::new();
+    }
+    times.sort();
+    let total: Duration = times.iter().sum();
+    let percentile = |p: usize| times[(times.len() - 1) * p / 100];
+    format!(
+        "{}: mean {:.2?}, median {:.2?}, p95 {:.2?}, max {:.2?}, {:.0} chunks/s\n",
+        name,
+        total / times.len() as u32,
+        percentile(50),
+        percentile(95),
+        percentile(100),
+        times.len() as f64 / total.as_secs_f64()
+    )
+}
+
+fn write_png(
+    path: &Path,
+    size: u32,
+    columns: &[Column],
+    color: impl Fn(&Column) -> [u8; 3],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size, size);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = columns.iter().flat_map(color).collect();
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
+
+/// Colour of a biome on the biome map, derived from its name so that it stays the same
+/// between previews
+fn biome_color(name: &str) -> [u8; 3] {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let hash = hasher.finish();
+    [hash, hash >> 16, hash >> 32].map(|bits| 64 + (bits % 192) as u8)
+}
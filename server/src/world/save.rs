@@ -1,8 +1,14 @@
 use crate::init::ServerTime;
+use crate::inventory::HeldItems;
 use bevy::prelude::*;
+use bincode::Options;
 use ron::ser::PrettyConfig;
-use shared::world::get_game_folder;
+use shared::world::BlockRegistry;
+use shared::world::GeneratorSettings;
+use shared::world::ItemRegistry;
 use shared::world::ServerWorldMap;
+use shared::world::WorldHeight;
+use shared::world::WorldPreset;
 use shared::world::WorldSeed;
 use shared::GameFolderPaths;
 use std::{fs::File, io::Write, path::Path};
@@ -10,21 +16,20 @@ use std::{fs::File, io::Write, path::Path};
 #[derive(Event)]
 pub struct SaveRequestEvent;
 
-use crate::world::data::SAVE_PATH;
-
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct WorldData {
-    pub seed: WorldSeed,
-    pub map: ServerWorldMap,
-    pub time: u64,
-}
+use crate::world::data::{Save, StorageFormat, TerrainDataHash, WorldData};
+use crate::world::load_from_file::{find_save_file, get_saves_folder, save_path};
 
 // System to save the world when "L" is pressed
 pub fn save_world_system(
     world_map: ResMut<ServerWorldMap>,
     world_seed: Res<WorldSeed>,
+    world_preset: Res<WorldPreset>,
+    generator_settings: Res<GeneratorSettings>,
+    world_height: Res<WorldHeight>,
     game_folder_path: Res<GameFolderPaths>,
     time: Res<ServerTime>,
+    (blocks, terrain_data): (Res<BlockRegistry>, Option<Res<TerrainDataHash>>),
+    (items, held_items): (Res<ItemRegistry>, Res<HeldItems>),
     mut event: EventReader<SaveRequestEvent>,
 ) {
     // Reads all events to prevent them from being queued forever and repeatedly request a save
@@ -38,20 +43,29 @@ pub fn save_world_system(
         let world_data = WorldData {
             map: world_map.clone(),
             seed: world_seed.clone(),
+            preset: world_preset.clone(),
+            settings: generator_settings.clone(),
+            height: *world_height,
+            terrain_data: terrain_data.map(|terrain_data| *terrain_data),
+            held_items: held_items.to_saved(&items),
             time: time.0,
         };
 
+        // Keep the storage format the world already uses, if it was saved before
+        let saves_folder = get_saves_folder(&game_folder_path);
+        let format = find_save_file(&saves_folder, &world_map.name)
+            .map(|(_, format)| format)
+            .unwrap_or(StorageFormat::Ron);
+
         // define save file path
-        let save_file_path = format!(
-            "{}{}.ron",
-            get_game_folder(Some(&game_folder_path))
-                .join(SAVE_PATH)
-                .display(),
-            world_map.name
-        );
+        let save_file_path = save_path(&saves_folder, &world_map.name, format);
 
         // save seed and world data
-        if let Err(e) = save_world_data(&world_data, &save_file_path) {
+        if let Err(e) = write_save(
+            &Save::from_world(&world_data, &blocks),
+            &save_file_path,
+            format,
+        ) {
             error!("Failed to save world data: {}", e);
         } else {
             info!("World data saved successfully! Name: {}", world_map.name);
@@ -59,21 +73,26 @@ pub fn save_world_system(
     }
 }
 
-pub fn save_world_data(
-    world_data: &WorldData,
-    file_path: &str,
+pub fn write_save(
+    save: &Save,
+    path: &Path,
+    format: StorageFormat,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // configure RON serialization
-    let pretty_config = PrettyConfig::new()
-        .with_depth_limit(3)
-        .with_separate_tuple_members(true)
-        .with_enumerate_arrays(true);
+    let serialized = match format {
+        StorageFormat::Ron => {
+            // configure RON serialization
+            let pretty_config = PrettyConfig::new()
+                .with_depth_limit(3)
+                .with_separate_tuple_members(true)
+                .with_enumerate_arrays(true);
+
+            ron::ser::to_string_pretty(save, pretty_config)?.into_bytes()
+        }
+        StorageFormat::Binary => bincode::options().serialize(save)?,
+    };
 
-    // serialize combined data (map + seed)
-    let serialized = ron::ser::to_string_pretty(world_data, pretty_config)?;
-    let path = Path::new(file_path);
     let mut file = File::create(path)?;
-    file.write_all(serialized.as_bytes())?;
-    info!("World data saved to {}", file_path);
+    file.write_all(&serialized)?;
+    info!("World data saved to {}", path.display());
     Ok(())
 }
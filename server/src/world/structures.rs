@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use shared::world::{BlockData, BlockDirection, BlockRegistry};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// File of the data folder where structures are defined
+pub const STRUCTURES_FILE: &str = "structures.ron";
+
+/// Structures shipped with the game, used when the data folder has no structure definitions
+const BUILTIN_STRUCTURES: &str = include_str!("../../../data/structures.ron");
+
+/// Characters of the templates that are not in palettes
+const KEEP_CHAR: char = ' ';
+const AIR_CHAR: char = '.';
+
+/// Height a structure is placed at
+#[derive(Debug, Clone, Deserialize)]
+pub enum Placement {
+    /// On the ground, its lowest layer `offset` blocks above the surface block.
+    /// Negative offsets bury the structure
+    Surface {
+        #[serde(default)]
+        offset: i32,
+    },
+    /// Between both heights, both included, and always below the surface
+    Underground { min_y: i32, max_y: i32 },
+}
+
+fn default_direction() -> BlockDirection {
+    BlockDirection::Front
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PaletteEntry {
+    block: String,
+    #[serde(default = "default_direction")]
+    direction: BlockDirection,
+}
+
+/// Structure as written in the structures file
+#[derive(Debug, Deserialize)]
+struct StructureDefinition {
+    name: String,
+    #[serde(default)]
+    biomes: Vec<String>,
+    spacing: i32,
+    chance: f32,
+    salt: u64,
+    placement: Placement,
+    palette: HashMap<char, PaletteEntry>,
+    /// Layers from the bottom, each one made of rows along z of blocks along x
+    layers: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Structure {
+    /// Unique name
+    pub name: String,
+    /// Biomes the structure is placed in, any if empty
+    pub biomes: Vec<String>,
+    /// Width in chunks of the square regions getting at most one of these structures
+    pub spacing: i32,
+    /// Chance for each region to get the structure
+    pub chance: f32,
+    /// Mixed with the seed, so that structures do not all end up at the same spots
+    pub salt: u64,
+    pub placement: Placement,
+    /// Size of the template, before rotation
+    pub size: IVec3,
+    /// Blocks of the template, `None` being air which clears the terrain.
+    /// Positions without block keep the terrain
+    blocks: Vec<(IVec3, Option<BlockData>)>,
+}
+
+impl Structure {
+    fn from_definition(
+        definition: StructureDefinition,
+        blocks: &BlockRegistry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let name = definition.name;
+        if definition.spacing < 1 {
+            return Err(format!("structure '{}' has a spacing below 1", name).into());
+        }
+        if let Placement::Underground { min_y, max_y } = definition.placement {
+            if min_y > max_y {
+                return Err(format!("structure '{}' has min_y above max_y", name).into());
+            }
+        }
+
+        let mut palette = HashMap::new();
+        for (symbol, entry) in definition.palette {
+            if symbol == KEEP_CHAR || symbol == AIR_CHAR {
+                return Err(
+                    format!("'{}' cannot be used in the palette of '{}'", symbol, name).into(),
+                );
+            }
+            let id = blocks
+                .id(&entry.block)
+                .ok_or_else(|| format!("unknown block '{}' in '{}'", entry.block, name))?;
+            palette.insert(symbol, BlockData::new(id, false, entry.direction));
+        }
+
+        let height = definition.layers.len() as i32;
+        let depth = definition.layers.first().map_or(0, |layer| layer.len()) as i32;
+        let width = definition
+            .layers
+            .first()
+            .and_then(|layer| layer.first())
+            .map_or(0, |row| row.chars().count()) as i32;
+        if height == 0 || depth == 0 || width == 0 {
+            return Err(format!("structure '{}' is empty", name).into());
+        }
+
+        let mut blocks = Vec::new();
+        for (y, layer) in definition.layers.iter().enumerate() {
+            if layer.len() as i32 != depth {
+                return Err(format!("layers of '{}' do not have the same size", name).into());
+            }
+            for (z, row) in layer.iter().enumerate() {
+                if row.chars().count() as i32 != width {
+                    return Err(format!("rows of '{}' do not have the same length", name).into());
+                }
+                for (x, symbol) in row.chars().enumerate() {
+                    let block = match symbol {
+                        KEEP_CHAR => continue,
+                        AIR_CHAR => None,
+                        _ => Some(*palette.get(&symbol).ok_or_else(|| {
+                            format!("'{}' is not in the palette of '{}'", symbol, name)
+                        })?),
+                    };
+                    blocks.push((IVec3::new(x as i32, y as i32, z as i32), block));
+                }
+            }
+        }
+
+        Ok(Structure {
+            name,
+            biomes: definition.biomes,
+            spacing: definition.spacing,
+            chance: definition.chance,
+            salt: definition.salt,
+            placement: definition.placement,
+            size: IVec3::new(width, height, depth),
+            blocks,
+        })
+    }
+
+    pub fn allows_biome(&self, biome: &str) -> bool {
+        self.biomes.is_empty() || self.biomes.iter().any(|name| name == biome)
+    }
+
+    /// Size of the structure once turned by `rotation`
+    pub fn rotated_size(&self, rotation: BlockDirection) -> IVec3 {
+        if rotation.quarter_turns() % 2 == 1 {
+            IVec3::new(self.size.z, self.size.y, self.size.x)
+        } else {
+            self.size
+        }
+    }
+
+    /// Blocks of the structure turned by `rotation`, relative to its lowest corner.
+    /// Turns are the same as the ones of `BlockDirection`, and blocks are turned along
+    pub fn rotated_blocks(
+        &self,
+        rotation: BlockDirection,
+    ) -> impl Iterator<Item = (IVec3, Option<BlockData>)> + '_ {
+        self.blocks.iter().map(move |(pos, block)| {
+            let mut pos = *pos;
+            let mut size = self.size;
+            for _ in 0..rotation.quarter_turns() {
+                pos = IVec3::new(size.z - 1 - pos.z, pos.y, pos.x);
+                size = IVec3::new(size.z, size.y, size.x);
+            }
+            let block = block.map(|block| BlockData {
+                direction: block.direction.rotate(rotation),
+                ..block
+            });
+            (pos, block)
+        })
+    }
+}
+
+/// All known structures
+#[derive(Debug, Clone, Default)]
+pub struct StructureRegistry {
+    structures: Vec<Structure>,
+}
+
+impl StructureRegistry {
+    pub fn from_ron(
+        contents: &str,
+        blocks: &BlockRegistry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let definitions: Vec<StructureDefinition> = ron::de::from_str(contents)?;
+        let structures = definitions
+            .into_iter()
+            .map(|definition| Structure::from_definition(definition, blocks))
+            .collect::<Result<_, _>>()?;
+        Ok(StructureRegistry { structures })
+    }
+
+    pub fn builtin(blocks: &BlockRegistry) -> Self {
+        Self::from_ron(BUILTIN_STRUCTURES, blocks).expect("built-in structures are invalid")
+    }
+
+    /// Loads the structures of the data folder, or the built-in ones if they cannot be read
+    pub fn load(assets_folder: &Path, blocks: &BlockRegistry) -> Self {
+        let path = assets_folder.join(STRUCTURES_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::from_ron(&contents, blocks).unwrap_or_else(|e| {
+                warn!(
+                    "Invalid structures in {}, using built-in ones: {}",
+                    path.display(),
+                    e
+                );
+                Self::builtin(blocks)
+            }),
+            Err(_) => Self::builtin(blocks),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Structure> {
+        self.structures.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_structures_stay_in_their_bounds() {
+        let blocks = BlockRegistry::builtin();
+        for structure in StructureRegistry::builtin(&blocks).iter() {
+            for turns in 0..4 {
+                let rotation = BlockDirection::from_quarter_turns(turns);
+                let size = structure.rotated_size(rotation);
+                let positions: Vec<IVec3> = structure
+                    .rotated_blocks(rotation)
+                    .map(|(pos, _)| pos)
+                    .collect();
+                assert!(positions
+                    .iter()
+                    .all(|pos| pos.cmpge(IVec3::ZERO).all() && pos.cmplt(size).all()));
+
+                // no two blocks end up at the same position
+                let mut unique = positions.clone();
+                unique.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+                unique.dedup();
+                assert_eq!(unique.len(), positions.len());
+            }
+        }
+    }
+}
@@ -16,10 +16,17 @@ pub struct GameFolderPaths {
 pub struct GameServerConfig {
     pub world_name: String,
     pub is_solo: bool,
+    /// Used if the world does not exist yet, existing worlds keep the preset they were created with
+    pub world_preset: world::WorldPreset,
+    /// Used if the world does not exist yet, like `world_preset`
+    pub generator_settings: world::GeneratorSettings,
+    pub chunk_compression: messages::ChunkCompression,
 }
 
 pub const PROTOCOL_ID: u64 = 0;
 pub const CHUNK_SIZE: i32 = 16;
+/// Terrain below this height is filled with water, unless the generator settings say otherwise
+pub const SEA_LEVEL: i32 = 62;
 
 fn get_customized_default_channels() -> Vec<ChannelConfig> {
     let memory = 128 * 1024 * 1024;
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use super::PlayerSpawnEvent;
+use crate::world::{BlockRegistry, ItemRegistry, ItemStack, RecipeRegistry, WorldHeight};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct AuthRegisterRequest {
@@ -12,4 +13,13 @@ pub struct AuthRegisterResponse {
     pub username: String,
     pub session_token: u128,
     pub spawn_event: PlayerSpawnEvent,
+    pub world_height: WorldHeight,
+    /// Blocks of the server, which the numeric block IDs it sends refer to
+    pub blocks: BlockRegistry,
+    /// Items of the server, which the numeric item IDs it sends refer to
+    pub items: ItemRegistry,
+    /// Crafting recipes of the server, checked by it whenever a client crafts
+    pub recipes: RecipeRegistry,
+    /// Items the server handed out to the player, the client puts them back in its inventory
+    pub inventory: Vec<ItemStack>,
 }
@@ -0,0 +1,25 @@
+use bevy::math::IVec3;
+use bevy::prelude::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::world::{ItemId, RecipeResult};
+
+/// Asks the server to craft the items of a square crafting grid once
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CraftRequest {
+    /// Side of the grid
+    pub size: usize,
+    /// Items of the grid, row by row
+    pub grid: Vec<Option<ItemId>>,
+    /// Crafting table used, needed by grids larger than the one of the inventory
+    pub table: Option<IVec3>,
+}
+
+/// Answer of the server to a `CraftRequest`
+#[derive(Event, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CraftResponse {
+    /// Grid of the request
+    pub grid: Vec<Option<ItemId>>,
+    /// Crafted items, `None` if the request was rejected
+    pub result: Option<RecipeResult>,
+}
@@ -0,0 +1,28 @@
+use bevy::math::Vec3;
+use bevy::prelude::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::world::ItemStack;
+
+/// Stack of items lying in the world
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct DroppedItem {
+    pub id: u64,
+    pub stack: ItemStack,
+    pub position: Vec3,
+    /// Thrown items cannot be picked up for a moment
+    pub pickable: bool,
+}
+
+/// All the items lying in the world, sent by the server whenever they change
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct DroppedItemsUpdate {
+    pub tick: u64,
+    pub items: Vec<DroppedItem>,
+}
+
+/// Items picked up by the player, after a `PickUpItem` request
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ItemPickedUp {
+    pub stack: ItemStack,
+}
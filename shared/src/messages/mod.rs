@@ -1,13 +1,17 @@
 mod auth;
 mod chat;
+mod crafting;
+mod items;
 pub mod player;
 mod system;
 mod world;
 
-use crate::world::BlockData;
+use crate::world::{BlockData, ItemStack};
 pub use auth::*;
 use bevy::math::{IVec3, Vec3};
 pub use chat::*;
+pub use crafting::*;
+pub use items::*;
 pub use player::*;
 use serde::{Deserialize, Serialize};
 pub use system::*;
@@ -24,6 +28,7 @@ pub enum ClientToServerMessage {
     WorldUpdateRequest {
         player_chunk_position: IVec3,
         render_distance: u32,
+        vertical_render_distance: u32,
         requested_chunks: Vec<IVec3>,
     },
     SaveWorldRequest(SaveWorldRequest),
@@ -31,10 +36,26 @@ pub enum ClientToServerMessage {
         position: IVec3,
         block_type: Option<BlockData>,
     },
+    /// The player started breaking a block, which it will break with a `BlockInteraction`
+    /// once the tool it holds had enough time
+    BlockBreakingStart {
+        position: IVec3,
+        tool: Option<ItemStack>,
+    },
     SetPlayerPosition {
         // should be deprecated in the long run
         position: Vec3,
     },
+    CraftRequest(CraftRequest),
+    /// Throws a stack of the inventory of the player in the direction it looks at
+    DropItem {
+        stack: ItemStack,
+        direction: Vec3,
+    },
+    /// Asks to take a dropped item close to the player, which has room for it
+    PickUpItem {
+        id: u64,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -43,4 +64,7 @@ pub enum ServerToClientMessage {
     ChatConversation(ChatConversation),
     WorldUpdate(WorldUpdate),
     PlayerSpawn(PlayerSpawnEvent),
+    CraftResponse(CraftResponse),
+    DroppedItems(DroppedItemsUpdate),
+    ItemPickedUp(ItemPickedUp),
 }
@@ -1,7 +1,13 @@
 use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::str::FromStr;
 
 use crate::world::ServerChunk;
 use bevy::math::{IVec3, Vec3};
+use bevy::prelude::Resource;
+use bincode::Options;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
 use serde::{Deserialize, Serialize};
 
 use super::PlayerId;
@@ -9,7 +15,162 @@ use super::PlayerId;
 #[derive(Default, Serialize, Deserialize, Debug, Clone)]
 pub struct WorldUpdate {
     pub tick: u64,
-    pub new_map: HashMap<IVec3, ServerChunk>,
+    pub new_map: ChunkPayload,
     pub player_positions: HashMap<PlayerId, Vec3>,
     pub time: u64,
 }
+
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    #[default]
+    None,
+    /// Best ratio, for servers with limited bandwidth
+    Deflate,
+    /// Fastest, a good default for local and LAN games
+    Lz4,
+}
+
+impl FromStr for CompressionAlgorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(CompressionAlgorithm::None),
+            "deflate" => Ok(CompressionAlgorithm::Deflate),
+            "lz4" => Ok(CompressionAlgorithm::Lz4),
+            _ => Err(format!(
+                "unknown compression '{}', expected none, deflate or lz4",
+                name
+            )),
+        }
+    }
+}
+
+/// How chunk payloads are compressed by the server before being sent to clients
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkCompression {
+    pub algorithm: CompressionAlgorithm,
+    /// Payloads smaller than this many bytes are not worth compressing
+    pub threshold: usize,
+}
+
+impl Default for ChunkCompression {
+    fn default() -> Self {
+        ChunkCompression {
+            algorithm: CompressionAlgorithm::Lz4,
+            threshold: 16 * 1024,
+        }
+    }
+}
+
+/// Serialized chunks of a `WorldUpdate`, compressed when they are large enough.
+/// Kept as bytes so that the client can decode them off the main thread
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkPayload {
+    pub compression: CompressionAlgorithm,
+    pub data: Vec<u8>,
+}
+
+impl ChunkPayload {
+    /// Chunks are only compressed if their serialized size reaches `threshold` bytes
+    pub fn new(
+        chunks: &HashMap<IVec3, ServerChunk>,
+        algorithm: CompressionAlgorithm,
+        threshold: usize,
+    ) -> Self {
+        let data = bincode::options().serialize(chunks).unwrap();
+        if data.len() < threshold {
+            return ChunkPayload {
+                compression: CompressionAlgorithm::None,
+                data,
+            };
+        }
+
+        let data = match algorithm {
+            CompressionAlgorithm::None => data,
+            CompressionAlgorithm::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(&data).unwrap();
+                encoder.finish().unwrap()
+            }
+            CompressionAlgorithm::Lz4 => lz4_flex::compress_prepend_size(&data),
+        };
+
+        ChunkPayload {
+            compression: algorithm,
+            data,
+        }
+    }
+
+    pub fn decode(
+        &self,
+    ) -> Result<HashMap<IVec3, ServerChunk>, Box<dyn std::error::Error + Send + Sync>> {
+        let decompressed;
+        let data = match self.compression {
+            CompressionAlgorithm::None => &self.data,
+            CompressionAlgorithm::Deflate => {
+                let mut buffer = Vec::new();
+                DeflateDecoder::new(self.data.as_slice()).read_to_end(&mut buffer)?;
+                decompressed = buffer;
+                &decompressed
+            }
+            CompressionAlgorithm::Lz4 => {
+                decompressed = lz4_flex::decompress_size_prepended(&self.data)?;
+                &decompressed
+            }
+        };
+
+        Ok(bincode::options().deserialize(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{BlockData, BlockDirection, BlockRegistry};
+
+    fn chunks(blocks: usize) -> HashMap<IVec3, ServerChunk> {
+        let registry = BlockRegistry::builtin();
+        let stone = BlockData::new(registry.id("stone").unwrap(), false, BlockDirection::Front);
+        let mut chunk = ServerChunk {
+            ts: 42,
+            ..Default::default()
+        };
+        for index in 0..blocks as i32 {
+            chunk
+                .map
+                .insert(IVec3::new(index % 16, index / 256, index / 16 % 16), stone);
+        }
+        HashMap::from([(IVec3::new(1, -2, 3), chunk)])
+    }
+
+    fn assert_same(decoded: &HashMap<IVec3, ServerChunk>, chunks: &HashMap<IVec3, ServerChunk>) {
+        assert_eq!(decoded.len(), chunks.len());
+        for (pos, chunk) in chunks {
+            assert_eq!(decoded[pos].ts, chunk.ts);
+            assert_eq!(decoded[pos].map, chunk.map);
+        }
+    }
+
+    #[test]
+    fn payloads_round_trip_with_every_compression() {
+        let chunks = chunks(4096);
+        for algorithm in [
+            CompressionAlgorithm::None,
+            CompressionAlgorithm::Deflate,
+            CompressionAlgorithm::Lz4,
+        ] {
+            let payload = ChunkPayload::new(&chunks, algorithm, 0);
+            assert_eq!(payload.compression, algorithm);
+            assert_same(&payload.decode().unwrap(), &chunks);
+        }
+    }
+
+    #[test]
+    fn payloads_under_the_threshold_are_not_compressed() {
+        let chunks = chunks(3);
+        let payload = ChunkPayload::new(&chunks, CompressionAlgorithm::Lz4, 16 * 1024);
+        assert_eq!(payload.compression, CompressionAlgorithm::None);
+        assert_same(&payload.decode().unwrap(), &chunks);
+    }
+}
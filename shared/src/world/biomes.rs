@@ -0,0 +1,285 @@
+use bevy::prelude::Resource;
+use log::warn;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::{BiomeTint, BlockId, BlockRegistry};
+
+/// File of the data folder where biomes are defined
+pub const BIOMES_FILE: &str = "biomes.ron";
+
+/// Biomes shipped with the game, used when the data folder has no biome definitions
+const BUILTIN_BIOMES: &str = include_str!("../../../data/biomes.ron");
+
+/// Climate of a column, each value going from 0 to 1
+#[derive(Debug, Clone, Copy)]
+pub struct Climate {
+    pub temperature: f64,
+    pub humidity: f64,
+    /// Low values are oceans
+    pub continentalness: f64,
+    /// Distance to the middle line of the closest river
+    pub river: f64,
+}
+
+/// Climate values a biome appears at, both ends included. Missing ranges accept any value
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClimateRanges {
+    pub temperature: (f64, f64),
+    pub humidity: (f64, f64),
+    pub continentalness: (f64, f64),
+    pub river: (f64, f64),
+}
+
+impl Default for ClimateRanges {
+    fn default() -> Self {
+        ClimateRanges {
+            temperature: (0.0, 1.0),
+            humidity: (0.0, 1.0),
+            continentalness: (0.0, 1.0),
+            river: (0.0, 1.0),
+        }
+    }
+}
+
+impl ClimateRanges {
+    pub fn contains(&self, climate: &Climate) -> bool {
+        let contains = |(min, max): (f64, f64), value: f64| min <= value && value <= max;
+        contains(self.temperature, climate.temperature)
+            && contains(self.humidity, climate.humidity)
+            && contains(self.continentalness, climate.continentalness)
+            && contains(self.river, climate.river)
+    }
+}
+
+/// Blocks are referenced by the same names as in saves, so biomes are first read
+/// with block names, then with the IDs of these blocks in the block registry
+fn block_by_name(blocks: &BlockRegistry, name: &str) -> Result<BlockId, String> {
+    blocks
+        .id(name)
+        .ok_or_else(|| format!("unknown block '{}'", name))
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Flora<B = BlockId> {
+    /// Single block on the surface, such as a flower
+    Plant(B),
+    Tree {
+        log: B,
+        leaves: B,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FloraEntry<B = BlockId> {
+    pub flora: Flora<B>,
+    /// Chance for each surface column to get this flora
+    pub density: f32,
+}
+
+fn default_cave_frequency() -> f64 {
+    1.0
+}
+
+fn default_color() -> [f32; 3] {
+    [0.1, 1.0, 0.3]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Biome<B = BlockId> {
+    /// Unique name, used by presets
+    pub name: String,
+    #[serde(default)]
+    pub climate: ClimateRanges,
+    pub base_height: i32,
+    pub height_variation: i32,
+    pub surface_block: B,
+    pub sub_surface_block: B,
+    /// How much of the underground is carved by caves, 0 disables them
+    #[serde(default = "default_cave_frequency")]
+    pub cave_frequency: f64,
+    /// How far cliffs and overhangs can stray from the surface height, 0 disables them
+    #[serde(default)]
+    pub overhang_height: i32,
+    /// At most one of them grows on each surface column, their densities must not add up to more than 1
+    #[serde(default)]
+    pub flora: Vec<FloraEntry<B>>,
+    /// Chance for each surface column to get a boulder
+    #[serde(default)]
+    pub boulder_density: f32,
+    /// Colour of the top of grass blocks, as RGB
+    #[serde(default = "default_color")]
+    pub grass_color: [f32; 3],
+    /// Colour of leaves, as RGB
+    #[serde(default = "default_color")]
+    pub foliage_color: [f32; 3],
+}
+
+impl Biome<String> {
+    fn resolve(self, blocks: &BlockRegistry) -> Result<Biome, String> {
+        if let Some(entry) = self
+            .flora
+            .iter()
+            .find(|entry| !entry.density.is_finite() || entry.density < 0.0)
+        {
+            return Err(format!(
+                "flora of biome '{}' has an invalid density of {}",
+                self.name, entry.density
+            ));
+        }
+        let total_density: f32 = self.flora.iter().map(|entry| entry.density).sum();
+        if total_density > 1.0 {
+            return Err(format!(
+                "flora of biome '{}' has a total density of {} above 1",
+                self.name, total_density
+            ));
+        }
+        let flora = self
+            .flora
+            .into_iter()
+            .map(|entry| {
+                let flora = match entry.flora {
+                    Flora::Plant(plant) => Flora::Plant(block_by_name(blocks, &plant)?),
+                    Flora::Tree { log, leaves } => Flora::Tree {
+                        log: block_by_name(blocks, &log)?,
+                        leaves: block_by_name(blocks, &leaves)?,
+                    },
+                };
+                Ok(FloraEntry {
+                    flora,
+                    density: entry.density,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Biome {
+            surface_block: block_by_name(blocks, &self.surface_block)?,
+            sub_surface_block: block_by_name(blocks, &self.sub_surface_block)?,
+            flora,
+            name: self.name,
+            climate: self.climate,
+            base_height: self.base_height,
+            height_variation: self.height_variation,
+            cave_frequency: self.cave_frequency,
+            overhang_height: self.overhang_height,
+            boulder_density: self.boulder_density,
+            grass_color: self.grass_color,
+            foliage_color: self.foliage_color,
+        })
+    }
+}
+
+impl Biome {
+    pub fn tint(&self) -> BiomeTint {
+        let to_bytes = |color: [f32; 3]| color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        BiomeTint {
+            grass: to_bytes(self.grass_color),
+            foliage: to_bytes(self.foliage_color),
+        }
+    }
+
+    /// Flora growing on a column, from a random value between 0 and 1
+    pub fn pick_flora(&self, chance: f32) -> Option<&Flora> {
+        let mut threshold = 0.0;
+        self.flora.iter().find_map(|entry| {
+            threshold += entry.density;
+            (chance < threshold).then_some(&entry.flora)
+        })
+    }
+}
+
+/// All known biomes. When several of them match the climate of a column, the first one is used
+#[derive(Resource, Debug, Clone)]
+pub struct BiomeRegistry {
+    biomes: Vec<Biome>,
+}
+
+impl BiomeRegistry {
+    pub fn from_ron(
+        contents: &str,
+        blocks: &BlockRegistry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let biomes: Vec<Biome<String>> = ron::de::from_str(contents)?;
+        if biomes.is_empty() {
+            return Err("no biome is defined".into());
+        }
+        let biomes = biomes
+            .into_iter()
+            .map(|biome| biome.resolve(blocks))
+            .collect::<Result<_, _>>()?;
+        Ok(BiomeRegistry { biomes })
+    }
+
+    pub fn builtin(blocks: &BlockRegistry) -> Self {
+        Self::from_ron(BUILTIN_BIOMES, blocks).expect("built-in biomes are invalid")
+    }
+
+    /// Loads the biomes of the data folder, or the built-in ones if they cannot be read
+    pub fn load(assets_folder: &Path, blocks: &BlockRegistry) -> Self {
+        let path = assets_folder.join(BIOMES_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::from_ron(&contents, blocks).unwrap_or_else(|e| {
+                warn!(
+                    "Invalid biomes in {}, using built-in ones: {}",
+                    path.display(),
+                    e
+                );
+                Self::builtin(blocks)
+            }),
+            Err(_) => Self::builtin(blocks),
+        }
+    }
+
+    /// Registry containing a single biome, used everywhere
+    pub fn single(&self, name: &str) -> Option<Self> {
+        let biome = self.get(name)?;
+        Some(BiomeRegistry {
+            biomes: vec![biome.clone()],
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Biome> {
+        self.biomes.iter().find(|biome| biome.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Biome> {
+        self.biomes.iter()
+    }
+
+    /// Biome of a column, the last one is used if none matches its climate
+    pub fn find(&self, climate: &Climate) -> &Biome {
+        self.biomes
+            .iter()
+            .find(|biome| biome.climate.contains(climate))
+            .unwrap_or_else(|| self.biomes.last().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn biome_with_flora(densities: &[f32]) -> String {
+        let flora = densities
+            .iter()
+            .map(|density| format!("(flora: Plant(\"poppy\"), density: {:?})", density))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "[(name: \"meadow\", base_height: 64, height_variation: 4, surface_block: \"grass\", \
+             sub_surface_block: \"dirt\", flora: [{}])]",
+            flora
+        )
+    }
+
+    #[test]
+    fn flora_densities_must_not_exceed_one() {
+        let blocks = BlockRegistry::builtin();
+        assert!(BiomeRegistry::from_ron(&biome_with_flora(&[0.5, 0.5]), &blocks).is_ok());
+        assert!(BiomeRegistry::from_ron(&biome_with_flora(&[0.75, 0.5]), &blocks).is_err());
+        assert!(BiomeRegistry::from_ron(&biome_with_flora(&[-0.1]), &blocks).is_err());
+        assert!(BiomeRegistry::from_ron(&biome_with_flora(&[f32::NAN]), &blocks).is_err());
+    }
+}
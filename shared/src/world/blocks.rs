@@ -1,9 +1,35 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 
-use super::{GameElementId, ItemId};
+use super::{GameElementId, ItemDefinition, ItemId, ItemRegistry, ToolTier};
+use bevy::math::{IVec3, Vec3};
+use bevy::prelude::Resource;
+use log::warn;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+/// File of the data folder where blocks are defined
+pub const BLOCKS_FILE: &str = "blocks.ron";
+
+/// Blocks shipped with the game, used when the data folder has no block definitions
+const BUILTIN_BLOCKS: &str = include_str!("../../../data/blocks.ron");
+
+/// Blocks placed by the game itself, which every block registry must define
+pub const REQUIRED_BLOCKS: [&str; 9] = [
+    "stone",
+    "water",
+    "sand",
+    "bedrock",
+    "cobblestone",
+    "coal_ore",
+    "iron_ore",
+    "gold_ore",
+    "diamond_ore",
+];
+
+/// Numeric ID of a block : its position in the `BlockRegistry` it comes from.
+/// IDs can differ between versions, saves store block names instead
 #[derive(
     Debug,
     Clone,
@@ -17,25 +43,12 @@ use serde::{Deserialize, Serialize};
     Hash,
     Default,
 )]
-pub enum BlockId {
-    #[default]
-    Dirt,
-    Debug,
-    Grass,
-    Stone,
-    OakLog,
-    OakPlanks,
-    OakLeaves,
-    Sand,
-    Ice,
-    Glass,
-    Bedrock,
-    Dandelion,
-    Poppy,
-    Cobblestone,
-    Snow,
-    SpruceLeaves,
-    SpruceLog,
+pub struct BlockId(u16);
+
+impl BlockId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -46,12 +59,78 @@ pub enum BlockDirection {
     Left,
 }
 
+impl BlockDirection {
+    /// Clockwise quarter turns from `Front`
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            BlockDirection::Front => 0,
+            BlockDirection::Right => 1,
+            BlockDirection::Back => 2,
+            BlockDirection::Left => 3,
+        }
+    }
+
+    pub fn from_quarter_turns(turns: u8) -> Self {
+        match turns % 4 {
+            0 => BlockDirection::Front,
+            1 => BlockDirection::Right,
+            2 => BlockDirection::Back,
+            _ => BlockDirection::Left,
+        }
+    }
+
+    /// Turns this direction as much as `Front` is turned to get `rotation`
+    pub fn rotate(self, rotation: BlockDirection) -> Self {
+        Self::from_quarter_turns(self.quarter_turns() + rotation.quarter_turns())
+    }
+
+    /// Direction of a block placed by a player looking along `forward` : its front faces the player
+    pub fn facing(forward: Vec3) -> Self {
+        if forward.x.abs() > forward.z.abs() {
+            if forward.x > 0. {
+                BlockDirection::Left
+            } else {
+                BlockDirection::Right
+            }
+        } else if forward.z > 0. {
+            BlockDirection::Front
+        } else {
+            BlockDirection::Back
+        }
+    }
+
+    /// Turns a vector of a block facing `Front` around the vertical axis, so that it faces this direction.
+    /// The front of blocks facing `Right` is towards +X
+    pub fn rotate_vector(self, vector: Vec3) -> Vec3 {
+        let (x, z) = match self {
+            BlockDirection::Front => (vector.x, vector.z),
+            BlockDirection::Right => (-vector.z, vector.x),
+            BlockDirection::Back => (-vector.x, -vector.z),
+            BlockDirection::Left => (vector.z, -vector.x),
+        };
+        Vec3::new(x, vector.y, z)
+    }
+
+    /// Turns a point of a block facing `Front` around the center of the block, so that it faces this direction
+    pub fn rotate_point(self, point: Vec3) -> Vec3 {
+        let center = Vec3::new(0.5, 0., 0.5);
+        self.rotate_vector(point - center) + center
+    }
+}
+
 /// Data associated with a given `BlockId`
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BlockData {
     pub id: BlockId,
+    /// Upside down, such as slabs in the upper half of a block.
+    /// Marks the upper half of doors, which are never upside down
     pub flipped: bool,
     pub direction: BlockDirection,
+    /// Level of a fluid : 0 for a source, then increasing with the distance to the source.
+    /// Always 0 for other blocks
+    pub level: u8,
+    /// Whether a door is open. Always false for other blocks
+    pub open: bool,
 }
 
 impl BlockData {
@@ -60,110 +139,482 @@ impl BlockData {
             id,
             flipped,
             direction,
+            level: 0,
+            open: false,
+        }
+    }
+
+    /// Fluid flowing away from its source
+    pub fn flowing(id: BlockId, level: u8) -> Self {
+        BlockData {
+            level,
+            ..BlockData::new(id, false, BlockDirection::Front)
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BlockTags {
     Solid,
+    /// Mined faster with pickaxes
     Stone,
+    /// Mined faster with axes
+    Wood,
+    /// Mined faster with shovels
+    Soil,
+    /// Opens a 3×3 crafting grid when used
+    CraftingTable,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum BlockTransparency {
     Transparent,
     Liquid,
+    #[default]
     Solid,
     Decoration,
 }
 
-impl BlockId {
-    pub fn has_hitbox(&self) -> bool {
-        match *self {
-            BlockId::Dandelion | BlockId::Poppy => false,
-            _ => true,
+/// Colour of the biome a block is tinted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BiomeColor {
+    Grass,
+    Foliage,
+}
+
+/// Biome colour applied to the faces of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockTint {
+    pub color: BiomeColor,
+    /// Only the top face is tinted, like the grass on grass blocks
+    #[serde(default)]
+    pub top_only: bool,
+}
+
+/// Shape a block is rendered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BlockModel {
+    #[default]
+    Cube,
+    /// Two crossed planes, for plants
+    Cross,
+    /// Cube getting lower as the fluid level increases
+    Liquid,
+    /// Lower half of a cube, or upper half when flipped
+    Slab,
+    /// Slab with a step on its back half
+    Stairs,
+    /// Thin panel on the front of the block, turning to its left side when open.
+    /// Doors are two blocks high : the lower half uses the `bottom` texture, the upper one the `top` texture
+    Door,
+}
+
+/// Thickness of door panels
+const DOOR_THICKNESS: f32 = 3. / 16.;
+
+/// Box of the shape of a block, relative to the block : coordinates go from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ShapeBox {
+    pub const FULL: ShapeBox = ShapeBox::new([0., 0., 0.], [1., 1., 1.]);
+
+    pub const fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        ShapeBox {
+            min: Vec3::from_array(min),
+            max: Vec3::from_array(max),
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmplt(self.max).all()
+    }
+}
+
+impl BlockModel {
+    /// Boxes making the shape of a block of this model facing `Front`, before it is flipped
+    pub fn boxes(self, block: &BlockData) -> Vec<ShapeBox> {
+        match self {
+            BlockModel::Cube | BlockModel::Cross | BlockModel::Liquid => vec![ShapeBox::FULL],
+            BlockModel::Slab => vec![ShapeBox::new([0., 0., 0.], [1., 0.5, 1.])],
+            BlockModel::Stairs => vec![
+                ShapeBox::new([0., 0., 0.], [1., 0.5, 1.]),
+                ShapeBox::new([0., 0.5, 0.5], [1., 1., 1.]),
+            ],
+            BlockModel::Door if block.open => {
+                vec![ShapeBox::new([0., 0., 0.], [DOOR_THICKNESS, 1., 1.])]
+            }
+            BlockModel::Door => vec![ShapeBox::new([0., 0., 0.], [1., 1., DOOR_THICKNESS])],
         }
     }
+}
+
+/// Textures of the faces of a block, as file names in the block textures folder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockTextures {
+    /// Used by the faces without a texture of their own
+    pub all: String,
+    #[serde(default)]
+    pub top: Option<String>,
+    #[serde(default)]
+    pub bottom: Option<String>,
+    #[serde(default)]
+    pub front: Option<String>,
+    #[serde(default)]
+    pub back: Option<String>,
+    #[serde(default)]
+    pub left: Option<String>,
+    #[serde(default)]
+    pub right: Option<String>,
+}
+
+/// Item a block may drop when broken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockDrop {
+    /// Relative to the chances of the other drops of the block
+    pub chance: u32,
+    /// Name of the dropped item
+    pub item: String,
+    pub count: u32,
+}
+
+fn default_hitbox() -> bool {
+    true
+}
+
+fn default_break_time() -> f32 {
+    5.
+}
+
+fn default_color() -> [f32; 4] {
+    [1., 1., 1., 1.]
+}
+
+fn default_map_color() -> [u8; 3] {
+    [125, 125, 125]
+}
+
+/// Properties of a kind of block, as defined in the blocks file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockDefinition {
+    /// Stable name of the block, used to identify it in save files.
+    /// Must never change once a block has been released
+    pub name: String,
+    pub textures: BlockTextures,
+    #[serde(default)]
+    pub model: BlockModel,
+    #[serde(default)]
+    pub visibility: BlockTransparency,
+    #[serde(default = "default_hitbox")]
+    pub hitbox: bool,
+    /// Seconds to break the block by hand, negative if it cannot be broken
+    #[serde(default = "default_break_time")]
+    pub break_time: f32,
+    /// Lowest tier of tool the block drops items with, if tools are needed
+    #[serde(default)]
+    pub harvest_tier: Option<ToolTier>,
+    #[serde(default)]
+    pub drops: Vec<BlockDrop>,
+    #[serde(default)]
+    pub tags: Vec<BlockTags>,
+    #[serde(default)]
+    pub tint: Option<BlockTint>,
+    /// Colour the faces are multiplied with, as RGBA
+    #[serde(default = "default_color")]
+    pub color: [f32; 4],
+    /// Colour of the block seen from above on generated maps
+    #[serde(default = "default_map_color")]
+    pub map_color: [u8; 3],
+}
+
+impl BlockDefinition {
+    pub fn is_liquid(&self) -> bool {
+        self.visibility == BlockTransparency::Liquid
+    }
+
+    pub fn biome_color(&self) -> Option<BiomeColor> {
+        self.tint.map(|tint| tint.color)
+    }
 
-    pub fn is_biome_colored() -> bool {
-        false
+    pub fn has_tag(&self, tag: BlockTags) -> bool {
+        self.tags.contains(&tag)
     }
 
-    pub fn get_break_time(&self) -> f32 {
-        match *self {
-            Self::Bedrock => -1.,
-            _ => 5.,
+    /// Whether the shape of a block is upside down. The upper halves of doors are flipped
+    /// blocks, but keep their shape
+    pub fn is_mirrored(&self, block: &BlockData) -> bool {
+        block.flipped && self.model != BlockModel::Door
+    }
+
+    /// Moves a point of the shape of a block facing `Front` to where it is once the block is
+    /// flipped and turned
+    pub fn orient(&self, block: &BlockData, point: Vec3) -> Vec3 {
+        let point = if self.is_mirrored(block) {
+            Vec3::new(point.x, 1. - point.y, point.z)
+        } else {
+            point
+        };
+        block.direction.rotate_point(point)
+    }
+
+    /// Boxes players and items collide with, relative to the block
+    pub fn collision_boxes(&self, block: &BlockData) -> Vec<ShapeBox> {
+        if !self.hitbox {
+            return Vec::new();
         }
+        self.model
+            .boxes(block)
+            .into_iter()
+            .map(|shape_box| {
+                let a = self.orient(block, shape_box.min);
+                let b = self.orient(block, shape_box.max);
+                ShapeBox {
+                    min: a.min(b),
+                    max: a.max(b),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether a point, relative to the block, is inside its collision boxes
+    pub fn collides(&self, block: &BlockData, point: Vec3) -> bool {
+        self.collision_boxes(block)
+            .iter()
+            .any(|shape_box| shape_box.contains(point))
     }
 
-    pub fn get_color(&self) -> [f32; 4] {
-        match *self {
-            Self::Grass => [0.1, 1.0, 0.25, 1.],
-            _ => [1., 1., 1., 1.],
+    /// Position of the other half of a door, which is broken and opened along with it
+    pub fn other_half(&self, block: &BlockData, position: IVec3) -> Option<IVec3> {
+        (self.model == BlockModel::Door).then(|| {
+            if block.flipped {
+                position - IVec3::Y
+            } else {
+                position + IVec3::Y
+            }
+        })
+    }
+
+    /// Seconds to break the block with a tool, or by hand. `None` if it cannot be broken
+    pub fn breaking_time(&self, tool: Option<&ItemDefinition>) -> Option<f32> {
+        if self.break_time < 0. {
+            return None;
+        }
+        match tool.and_then(|tool| tool.tool()) {
+            Some((kind, tier)) if self.has_tag(kind.mined_tag()) => {
+                Some(self.break_time / tier.speed())
+            }
+            _ => Some(self.break_time),
         }
     }
 
-    pub fn get_drops(&self, nb_drops: u32) -> HashMap<ItemId, u32> {
+    /// Whether the block drops its items when broken with a tool, or by hand
+    pub fn is_harvested_by(&self, tool: Option<&ItemDefinition>) -> bool {
+        let Some(needed) = self.harvest_tier else {
+            return true;
+        };
+        tool.and_then(|tool| tool.tool())
+            .is_some_and(|(kind, tier)| {
+                self.has_tag(kind.mined_tag()) && tier.level() >= needed.level()
+            })
+    }
+
+    /// Items dropped when the block is broken `nb_drops` times
+    pub fn get_drops(&self, nb_drops: u32, items: &ItemRegistry) -> HashMap<ItemId, u32> {
         let mut drops = HashMap::new();
-        let table = self.get_drop_table();
+        let total: u32 = self.drops.iter().map(|drop| drop.chance).sum();
 
-        if table.is_empty() {
+        if total == 0 {
             return drops;
         }
 
-        let total = table
-            .clone()
-            .into_iter()
-            .reduce(|a, b| (a.0 + b.0, a.1, a.2))
-            .unwrap()
-            .0;
-
         // Choose drop items
         for _ in 0..nb_drops {
             let mut nb = rand::thread_rng().gen_range(0..total);
-            for item in table.iter() {
-                if nb < item.0 {
-                    drops.insert(item.1, *drops.get(&item.1).unwrap_or(&0) + item.2);
+            for drop in self.drops.iter() {
+                if nb < drop.chance {
+                    if let Some(item) = items.id(&drop.item) {
+                        *drops.entry(item).or_insert(0) += drop.count;
+                    }
                 } else {
-                    nb -= item.0;
+                    nb -= drop.chance;
                 }
             }
         }
         drops
     }
+}
+
+/// All known blocks. Servers send theirs to the clients joining them
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<BlockDefinition>", into = "Vec<BlockDefinition>")]
+pub struct BlockRegistry {
+    blocks: Vec<BlockDefinition>,
+    ids: HashMap<String, BlockId>,
+}
 
-    /// Specifies the drop table of a given block
-    /// Drops are specified this way : `(relative_chance, corresponding_item, base_number)`
-    pub fn get_drop_table(&self) -> Vec<(u32, ItemId, u32)> {
-        match *self {
-            BlockId::Dirt | BlockId::Grass => vec![(1, ItemId::Dirt, 1)],
-            BlockId::Stone => vec![(1, ItemId::Cobblestone, 1)],
-            BlockId::Sand => vec![(1, ItemId::Sand, 1)],
-            BlockId::OakLog => vec![(1, ItemId::OakLog, 1)],
-            BlockId::OakPlanks => vec![(1, ItemId::OakPlanks, 1)],
-            BlockId::Ice => vec![(1, ItemId::Ice, 1)],
-            BlockId::Dandelion => vec![(1, ItemId::Dandelion, 1)],
-            BlockId::Poppy => vec![(1, ItemId::Dandelion, 1)],
-            BlockId::SpruceLog => vec![(1, ItemId::SpruceLog, 1)],
-            BlockId::Snow => vec![(1, ItemId::Snowball, 4)],
-            _ => vec![],
+impl TryFrom<Vec<BlockDefinition>> for BlockRegistry {
+    type Error = String;
+
+    fn try_from(blocks: Vec<BlockDefinition>) -> Result<Self, Self::Error> {
+        if blocks.len() > u16::MAX as usize {
+            return Err("too many blocks are defined".into());
+        }
+        let mut ids = HashMap::new();
+        for (index, block) in blocks.iter().enumerate() {
+            if ids
+                .insert(block.name.clone(), BlockId(index as u16))
+                .is_some()
+            {
+                return Err(format!("block '{}' is defined twice", block.name));
+            }
+        }
+        if let Some(name) = REQUIRED_BLOCKS
+            .iter()
+            .find(|name| !ids.contains_key(**name))
+        {
+            return Err(format!("block '{}' is required but not defined", name));
         }
+        Ok(BlockRegistry { blocks, ids })
     }
+}
 
-    pub fn get_tags(&self) -> Vec<BlockTags> {
-        match *self {
-            BlockId::Stone => vec![BlockTags::Stone, BlockTags::Solid],
-            _ => vec![BlockTags::Solid],
-        }
+impl From<BlockRegistry> for Vec<BlockDefinition> {
+    fn from(registry: BlockRegistry) -> Self {
+        registry.blocks
+    }
+}
+
+impl BlockRegistry {
+    pub fn from_ron(contents: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(ron::de::from_str(contents)?)
+    }
+
+    pub fn builtin() -> Self {
+        Self::from_ron(BUILTIN_BLOCKS).expect("built-in blocks are invalid")
     }
 
-    pub fn get_visibility(&self) -> BlockTransparency {
-        match *self {
-            Self::Dandelion | Self::Poppy => BlockTransparency::Decoration,
-            Self::Glass | Self::OakLeaves | Self::SpruceLeaves => BlockTransparency::Transparent,
-            _ => BlockTransparency::Solid,
+    /// Loads the blocks of the data folder, or the built-in ones if they cannot be read
+    pub fn load(assets_folder: &Path) -> Self {
+        let path = assets_folder.join(BLOCKS_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::from_ron(&contents).unwrap_or_else(|e| {
+                warn!(
+                    "Invalid blocks in {}, using built-in ones: {}",
+                    path.display(),
+                    e
+                );
+                Self::builtin()
+            }),
+            Err(_) => Self::builtin(),
         }
     }
+
+    /// Properties of a block. IDs must come from this registry
+    pub fn get(&self, id: BlockId) -> &BlockDefinition {
+        &self.blocks[id.index()]
+    }
+
+    /// Whether an ID, such as one received from a client, belongs to this registry
+    pub fn contains(&self, id: BlockId) -> bool {
+        id.index() < self.blocks.len()
+    }
+
+    /// ID of a block from its stable name
+    pub fn id(&self, name: &str) -> Option<BlockId> {
+        self.ids.get(name).copied()
+    }
+
+    /// ID of a block of `REQUIRED_BLOCKS`, which are always defined
+    pub fn required(&self, name: &str) -> BlockId {
+        self.id(name)
+            .unwrap_or_else(|| panic!("required block '{}' is not defined", name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BlockId, &BlockDefinition)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (BlockId(index as u16), block))
+    }
 }
 
 impl GameElementId for BlockId {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::utils::through_handshake;
+
+    #[test]
+    fn registry_survives_the_handshake() {
+        let blocks = BlockRegistry::builtin();
+        let received = through_handshake(&blocks);
+        assert_eq!(received, blocks);
+        for name in REQUIRED_BLOCKS {
+            assert_eq!(received.id(name), blocks.id(name));
+        }
+    }
+
+    #[test]
+    fn tools_speed_up_matching_blocks_and_harvest_by_tier() {
+        let blocks = BlockRegistry::builtin();
+        let items = ItemRegistry::builtin(&blocks);
+        let block = |name| blocks.get(blocks.id(name).unwrap());
+        let tool = |name| Some(items.get(items.id(name).unwrap()));
+
+        let stone = block("stone");
+        let hand = stone.breaking_time(None).unwrap();
+        assert!(stone.breaking_time(tool("stone_pickaxe")).unwrap() < hand);
+        assert_eq!(stone.breaking_time(tool("diamond_shovel")), Some(hand));
+        assert!(!stone.is_harvested_by(None));
+        assert!(stone.is_harvested_by(tool("wooden_pickaxe")));
+
+        let iron_ore = block("iron_ore");
+        assert!(!iron_ore.is_harvested_by(tool("wooden_pickaxe")));
+        assert!(!iron_ore.is_harvested_by(tool("iron_axe")));
+        assert!(iron_ore.is_harvested_by(tool("stone_pickaxe")));
+
+        assert!(block("dirt").is_harvested_by(None));
+        assert_eq!(
+            block("bedrock").breaking_time(tool("diamond_pickaxe")),
+            None
+        );
+    }
+
+    #[test]
+    fn collision_boxes_follow_the_orientation_of_blocks() {
+        let blocks = BlockRegistry::builtin();
+        let block = |name: &str, flipped: bool, direction: BlockDirection| {
+            BlockData::new(blocks.id(name).unwrap(), flipped, direction)
+        };
+        let collides = |block: BlockData, point: [f32; 3]| {
+            blocks
+                .get(block.id)
+                .collides(&block, Vec3::from_array(point))
+        };
+
+        // Slabs fill the lower half of the block, or the upper one when flipped
+        let slab = block("oak_slab", false, BlockDirection::Front);
+        assert!(collides(slab, [0.5, 0.25, 0.5]));
+        assert!(!collides(slab, [0.5, 0.75, 0.5]));
+        let slab = block("oak_slab", true, BlockDirection::Front);
+        assert!(collides(slab, [0.5, 0.75, 0.5]));
+        assert!(!collides(slab, [0.5, 0.25, 0.5]));
+
+        // The step of stairs is away from the player who placed them
+        let stairs = block("oak_stairs", false, BlockDirection::facing(Vec3::X));
+        assert!(collides(stairs, [0.75, 0.75, 0.5]));
+        assert!(!collides(stairs, [0.25, 0.75, 0.5]));
+        assert!(collides(stairs, [0.25, 0.25, 0.5]));
+
+        // Open doors turn towards their left side
+        let mut door = block("oak_door", false, BlockDirection::Front);
+        assert!(collides(door, [0.5, 0.5, 0.1]));
+        door.open = true;
+        assert!(!collides(door, [0.5, 0.5, 0.1]));
+        assert!(collides(door, [0.1, 0.5, 0.5]));
+    }
+}
@@ -2,24 +2,52 @@ use crate::messages::PlayerId;
 use crate::world::block_to_chunk_coord;
 use crate::world::global_block_to_chunk_pos;
 use crate::world::to_local_pos;
-use crate::world::BlockId;
 use crate::CHUNK_SIZE;
 use bevy::math::IVec3;
 use bevy::math::Vec3;
 use bevy::prelude::Resource;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 
+use super::BiomeColor;
 use super::BlockData;
 use super::ItemId;
 use super::ItemType;
+use super::PalettedContainer;
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct ServerChunk {
-    pub map: HashMap<IVec3, BlockData>,
+    pub map: PalettedContainer,
     /// Timestamp marking the last update this chunk has received
     pub ts: u64,
+    /// Whether a block was changed since the chunk was generated.
+    /// Pristine chunks are not saved, as they can be generated again from the seed
+    #[serde(skip)]
+    pub modified: bool,
+    /// Tint of each column, see `column_index`. Empty if the generator has no biomes
+    pub tints: Vec<BiomeTint>,
+}
+
+/// Biome colours of a column, as RGB
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BiomeTint {
+    pub grass: [u8; 3],
+    pub foliage: [u8; 3],
+}
+
+impl BiomeTint {
+    pub fn get(&self, color: BiomeColor) -> [u8; 3] {
+        match color {
+            BiomeColor::Grass => self.grass,
+            BiomeColor::Foliage => self.foliage,
+        }
+    }
+}
+
+/// Index of the column of a local block position in the tints of its chunk
+pub fn column_index(local_pos: IVec3) -> usize {
+    (local_pos.x * CHUNK_SIZE + local_pos.z) as usize
 }
 
 #[derive(Resource, Default, Clone, Serialize, Deserialize, Debug)]
@@ -27,6 +55,13 @@ pub struct ServerWorldMap {
     pub name: String,
     pub map: HashMap<IVec3, ServerChunk>,
     pub chunks_to_update: Vec<IVec3>,
+    /// Blocks of generated features that belong to other chunks, placed where those chunks
+    /// are empty whenever they are generated. They are kept afterwards, since unmodified
+    /// chunks are not saved but generated again
+    pub pending_writes: HashMap<IVec3, PalettedContainer>,
+    /// Chunks whose features crossing their borders are already in `pending_writes`, with the
+    /// chunks these features went into, so that generating them again does not place them a second time
+    pub features_placed: HashMap<IVec3, HashSet<IVec3>>,
     pub player_positions: HashMap<PlayerId, Vec3>,
     pub time: u64,
 }
@@ -34,79 +69,41 @@ pub struct ServerWorldMap {
 #[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct WorldSeed(pub u32);
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy)]
-pub struct ItemStack {
-    pub item_id: ItemId,
-    pub item_type: ItemType,
-    pub nb: u32,
+/// Vertical limits of a world, in blocks and both included.
+/// Chunks outside of them are never generated, and blocks cannot be placed there
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldHeight {
+    pub min_y: i32,
+    pub max_y: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum BiomeType {
-    Plains,
-    Forest,
-    MediumMountain,
-    HighMountain,
-    Desert,
-    IcePlain,
+impl Default for WorldHeight {
+    fn default() -> Self {
+        WorldHeight {
+            min_y: 0,
+            max_y: 16 * CHUNK_SIZE - 1,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Biome {
-    pub biome_type: BiomeType,
-    pub base_height: i32,
-    pub height_variation: i32,
-    pub surface_block: BlockId,
-    pub sub_surface_block: BlockId,
-}
+impl WorldHeight {
+    pub fn contains(&self, y: i32) -> bool {
+        self.min_y <= y && y <= self.max_y
+    }
 
-pub fn get_biome_data(biome_type: BiomeType) -> Biome {
-    match biome_type {
-        BiomeType::Plains => Biome {
-            biome_type: BiomeType::Plains,
-            base_height: 64,
-            height_variation: 1,
-            surface_block: BlockId::Grass,
-            sub_surface_block: BlockId::Dirt,
-        },
-        BiomeType::Forest => Biome {
-            biome_type: BiomeType::Forest,
-            base_height: 64,
-            height_variation: 2,
-            surface_block: BlockId::Grass,
-            sub_surface_block: BlockId::Dirt,
-        },
-        BiomeType::MediumMountain => Biome {
-            biome_type: BiomeType::MediumMountain,
-            base_height: 70,
-            height_variation: 4,
-            surface_block: BlockId::Grass,
-            sub_surface_block: BlockId::Dirt,
-        },
-        BiomeType::HighMountain => Biome {
-            biome_type: BiomeType::HighMountain,
-            base_height: 80,
-            height_variation: 7,
-            surface_block: BlockId::Grass,
-            sub_surface_block: BlockId::Dirt,
-        },
-        BiomeType::Desert => Biome {
-            biome_type: BiomeType::Desert,
-            base_height: 64,
-            height_variation: 1,
-            surface_block: BlockId::Sand,
-            sub_surface_block: BlockId::Sand,
-        },
-        BiomeType::IcePlain => Biome {
-            biome_type: BiomeType::IcePlain,
-            base_height: 64,
-            height_variation: 1,
-            surface_block: BlockId::Snow,
-            sub_surface_block: BlockId::Ice,
-        },
+    /// Whether a chunk layer holds blocks inside of the limits
+    pub fn contains_chunk(&self, chunk_y: i32) -> bool {
+        block_to_chunk_coord(self.min_y) <= chunk_y && chunk_y <= block_to_chunk_coord(self.max_y)
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq)]
+pub struct ItemStack {
+    pub item_id: ItemId,
+    pub item_type: ItemType,
+    pub nb: u32,
+}
+
 impl ServerWorldMap {
     pub fn get_block_by_coordinates(&self, position: &IVec3) -> Option<&BlockData> {
         let x: i32 = position.x;
@@ -143,6 +140,7 @@ impl ServerWorldMap {
         let local_block_pos: IVec3 = to_local_pos(global_block_pos);
 
         chunk_map.map.remove(&local_block_pos);
+        chunk_map.modified = true;
         self.chunks_to_update.push(IVec3::new(cx, cy, cz));
 
         Some(kind)
@@ -161,6 +159,7 @@ impl ServerWorldMap {
         let sub_z: i32 = ((z % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE;
 
         chunk.map.insert(IVec3::new(sub_x, sub_y, sub_z), block);
+        chunk.modified = true;
         self.chunks_to_update.push(IVec3::new(cx, cy, cz));
     }
 }
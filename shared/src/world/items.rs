@@ -1,9 +1,21 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
 
+use bevy::prelude::Resource;
+use log::warn;
 use serde::{Deserialize, Serialize};
 
-use super::{BlockId, GameElementId};
+use super::{BlockId, BlockRegistry, BlockTags, GameElementId};
 
+/// File of the data folder where items are defined
+pub const ITEMS_FILE: &str = "items.ron";
+
+/// Items shipped with the game, used when the data folder has no item definitions
+const BUILTIN_ITEMS: &str = include_str!("../../../data/items.ron");
+
+/// Numeric ID of an item : its position in the `ItemRegistry` it comes from
 #[derive(
     Debug,
     Clone,
@@ -17,65 +29,16 @@ use super::{BlockId, GameElementId};
     Hash,
     Default,
 )]
-pub enum ItemId {
-    #[default]
-    Dirt,
-    Grass,
-    Stone,
-    OakLog,
-    OakPlanks,
-    OakLeaves,
-    Sand,
-    Ice,
-    Glass,
-    Bedrock,
-    Dandelion,
-    Poppy,
-    Cobblestone,
-    Snow,
-    Snowball,
-    SpruceLog,
-}
+pub struct ItemId(u16);
 
 impl ItemId {
-    pub fn get_max_stack(&self) -> u32 {
-        64
-    }
-
-    pub fn get_default_type(&self) -> ItemType {
-        match *self {
-            Self::Dirt => ItemType::Block(BlockId::Dirt),
-            Self::Bedrock => ItemType::Block(BlockId::Bedrock),
-            Self::Grass => ItemType::Block(BlockId::Grass),
-            Self::Stone => ItemType::Block(BlockId::Stone),
-            Self::OakLog => ItemType::Block(BlockId::OakLog),
-            Self::OakPlanks => ItemType::Block(BlockId::OakPlanks),
-            Self::Sand => ItemType::Block(BlockId::Sand),
-            Self::Ice => ItemType::Block(BlockId::Ice),
-            Self::OakLeaves => ItemType::Block(BlockId::OakLeaves),
-            Self::Glass => ItemType::Block(BlockId::Glass),
-            Self::Dandelion => ItemType::Block(BlockId::Dandelion),
-            Self::Poppy => ItemType::Block(BlockId::Poppy),
-            Self::Cobblestone => ItemType::Block(BlockId::Cobblestone),
-            Self::Snow => ItemType::Block(BlockId::Snow),
-            Self::SpruceLog => ItemType::Block(BlockId::SpruceLog),
-
-            Self::Snowball => ItemType::Generic,
-        }
+    pub fn index(self) -> usize {
+        self.0 as usize
     }
 }
 
 impl GameElementId for ItemId {}
 
-/// Temporary struct for deserialization purposes
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TempBlock {
-    pub id: String,
-    pub drops: Vec<(u16, String)>,
-    pub break_time: f32,
-    pub uvs: [f32; 4],
-}
-
 /// Type of armor piece
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 pub enum ArmorType {
@@ -85,11 +48,339 @@ pub enum ArmorType {
     Boots,
 }
 
-/// Type of item
+/// Material of a tool, from the weakest to the strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ToolTier {
+    Wood,
+    Stone,
+    Iron,
+    Gold,
+    Diamond,
+}
+
+impl ToolTier {
+    /// How many times faster than bare hands the blocks matching the tool are mined
+    pub fn speed(self) -> f32 {
+        match self {
+            ToolTier::Wood => 2.,
+            ToolTier::Stone => 4.,
+            ToolTier::Iron => 6.,
+            ToolTier::Diamond => 8.,
+            ToolTier::Gold => 12.,
+        }
+    }
+
+    /// Harvest level : blocks needing a tier drop items when mined with a tool of at least its level.
+    /// Gold tools are fast but as weak as wooden ones
+    pub fn level(self) -> u8 {
+        match self {
+            ToolTier::Wood | ToolTier::Gold => 0,
+            ToolTier::Stone => 1,
+            ToolTier::Iron => 2,
+            ToolTier::Diamond => 3,
+        }
+    }
+}
+
+/// Kind of tool, each mining faster the blocks of one tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
+}
+
+impl ToolKind {
+    /// Tag of the blocks this tool mines faster
+    pub fn mined_tag(self) -> BlockTags {
+        match self {
+            ToolKind::Pickaxe => BlockTags::Stone,
+            ToolKind::Axe => BlockTags::Wood,
+            ToolKind::Shovel => BlockTags::Soil,
+        }
+    }
+}
+
+/// Tab of the creative inventory an item is listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ItemCategory {
+    Building,
+    Nature,
+    Materials,
+    Tools,
+    Combat,
+    #[default]
+    Miscellaneous,
+}
+
+/// What an item is, as defined in the items file. Blocks are first read by name,
+/// then by their ID in the block registry
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ItemKind<B = BlockId> {
+    #[default]
+    Generic,
+    /// Item placing a block
+    Block(B),
+    Tool {
+        kind: ToolKind,
+        tier: ToolTier,
+        /// Number of uses before the tool breaks
+        durability: u16,
+    },
+    Armor(ArmorType),
+}
+
+/// Type of item of a stack, with the state of this stack such as the remaining durability
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 pub enum ItemType {
     Generic,
     Block(BlockId),
-    Tool { durability: i16 },
+    Tool { durability: u16 },
     Armor(ArmorType),
 }
+
+fn default_max_stack() -> u32 {
+    64
+}
+
+/// Properties of a kind of item, as defined in the items file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDefinition<B = BlockId> {
+    /// Stable name of the item. Must never change once an item has been released
+    pub name: String,
+    /// Name shown to players
+    pub display_name: String,
+    pub texture: String,
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
+    #[serde(default)]
+    pub kind: ItemKind<B>,
+    #[serde(default)]
+    pub category: ItemCategory,
+}
+
+impl ItemDefinition<String> {
+    fn resolve(self, blocks: &BlockRegistry) -> Result<ItemDefinition, String> {
+        let kind =
+            match self.kind {
+                ItemKind::Generic => ItemKind::Generic,
+                ItemKind::Block(name) => ItemKind::Block(blocks.id(&name).ok_or_else(|| {
+                    format!("unknown block '{}' placed by '{}'", name, self.name)
+                })?),
+                ItemKind::Tool {
+                    kind,
+                    tier,
+                    durability,
+                } => ItemKind::Tool {
+                    kind,
+                    tier,
+                    durability,
+                },
+                ItemKind::Armor(armor) => ItemKind::Armor(armor),
+            };
+        Ok(ItemDefinition {
+            name: self.name,
+            display_name: self.display_name,
+            texture: self.texture,
+            max_stack: self.max_stack,
+            kind,
+            category: self.category,
+        })
+    }
+}
+
+impl ItemDefinition {
+    /// Block placed by this item, if any
+    pub fn block(&self) -> Option<BlockId> {
+        match self.kind {
+            ItemKind::Block(block) => Some(block),
+            _ => None,
+        }
+    }
+
+    /// Kind and tier of this item if it is a tool
+    pub fn tool(&self) -> Option<(ToolKind, ToolTier)> {
+        match self.kind {
+            ItemKind::Tool { kind, tier, .. } => Some((kind, tier)),
+            _ => None,
+        }
+    }
+
+    /// Type of the new stacks of this item, such as tools that were never used
+    pub fn default_type(&self) -> ItemType {
+        match self.kind {
+            ItemKind::Generic => ItemType::Generic,
+            ItemKind::Block(block) => ItemType::Block(block),
+            ItemKind::Tool { durability, .. } => ItemType::Tool { durability },
+            ItemKind::Armor(armor) => ItemType::Armor(armor),
+        }
+    }
+}
+
+/// All known items. Servers send theirs to the clients joining them, along with their blocks
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<ItemDefinition>", into = "Vec<ItemDefinition>")]
+pub struct ItemRegistry {
+    items: Vec<ItemDefinition>,
+    ids: HashMap<String, ItemId>,
+}
+
+impl TryFrom<Vec<ItemDefinition>> for ItemRegistry {
+    type Error = String;
+
+    fn try_from(items: Vec<ItemDefinition>) -> Result<Self, Self::Error> {
+        if items.len() > u16::MAX as usize {
+            return Err("too many items are defined".into());
+        }
+        let mut ids = HashMap::new();
+        for (index, item) in items.iter().enumerate() {
+            if item.max_stack == 0 {
+                return Err(format!("item '{}' has a max_stack of 0", item.name));
+            }
+            // The durability of a tool belongs to its whole stack
+            if item.tool().is_some() && item.max_stack != 1 {
+                return Err(format!("tool '{}' must have a max_stack of 1", item.name));
+            }
+            if ids
+                .insert(item.name.clone(), ItemId(index as u16))
+                .is_some()
+            {
+                return Err(format!("item '{}' is defined twice", item.name));
+            }
+        }
+        Ok(ItemRegistry { items, ids })
+    }
+}
+
+impl From<ItemRegistry> for Vec<ItemDefinition> {
+    fn from(registry: ItemRegistry) -> Self {
+        registry.items
+    }
+}
+
+impl ItemRegistry {
+    pub fn from_ron(
+        contents: &str,
+        blocks: &BlockRegistry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let items: Vec<ItemDefinition<String>> = ron::de::from_str(contents)?;
+        let items = items
+            .into_iter()
+            .map(|item| item.resolve(blocks))
+            .collect::<Result<Vec<_>, _>>()?;
+        let registry = ItemRegistry::try_from(items)?;
+
+        // Drops are only known by name in the block registry
+        for (_, block) in blocks.iter() {
+            if let Some(drop) = block
+                .drops
+                .iter()
+                .find(|drop| registry.id(&drop.item).is_none())
+            {
+                return Err(
+                    format!("block '{}' drops unknown item '{}'", block.name, drop.item).into(),
+                );
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn builtin(blocks: &BlockRegistry) -> Self {
+        Self::from_ron(BUILTIN_ITEMS, blocks).expect("built-in items are invalid")
+    }
+
+    /// Loads the items of the data folder. The built-in ones are used if they cannot be read,
+    /// but only along the built-in blocks : they place blocks custom ones may not have
+    pub fn load(
+        assets_folder: &Path,
+        blocks: &BlockRegistry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = assets_folder.join(ITEMS_FILE);
+        let error = match fs::read_to_string(&path) {
+            Ok(contents) => match Self::from_ron(&contents, blocks) {
+                Ok(items) => return Ok(items),
+                Err(e) => format!("invalid items in {}: {}", path.display(), e),
+            },
+            Err(e) => format!("cannot read {}: {}", path.display(), e),
+        };
+        if *blocks != BlockRegistry::builtin() {
+            return Err(format!("{}, and the blocks are not the built-in ones", error).into());
+        }
+        warn!("{}, using built-in items", error);
+        Ok(Self::builtin(blocks))
+    }
+
+    /// Properties of an item. IDs must come from this registry
+    pub fn get(&self, id: ItemId) -> &ItemDefinition {
+        &self.items[id.index()]
+    }
+
+    /// Whether an ID, such as one received from a client, belongs to this registry
+    pub fn contains(&self, id: ItemId) -> bool {
+        id.index() < self.items.len()
+    }
+
+    /// ID of an item from its stable name
+    pub fn id(&self, name: &str) -> Option<ItemId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, &ItemDefinition)> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (ItemId(index as u16), item))
+    }
+
+    /// Items of a tab of the creative inventory, in the order of the items file
+    pub fn in_category(
+        &self,
+        category: ItemCategory,
+    ) -> impl Iterator<Item = (ItemId, &ItemDefinition)> {
+        self.iter()
+            .filter(move |(_, item)| item.category == category)
+    }
+
+    /// Item placing a block, if any
+    pub fn placing(&self, block: BlockId) -> Option<ItemId> {
+        self.iter()
+            .find(|(_, item)| item.block() == Some(block))
+            .map(|(id, _)| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::utils::through_handshake;
+    use crate::world::REQUIRED_BLOCKS;
+
+    #[test]
+    fn registry_survives_the_handshake() {
+        let blocks = BlockRegistry::builtin();
+        let items = ItemRegistry::builtin(&blocks);
+        let received = through_handshake(&items);
+        assert_eq!(received, items);
+
+        let dirt = blocks.id("dirt").unwrap();
+        let item = received.placing(dirt).unwrap();
+        assert_eq!(received.get(item).default_type(), ItemType::Block(dirt));
+    }
+
+    #[test]
+    fn built_in_items_are_only_used_with_built_in_blocks() {
+        let missing = Path::new("rustcraft_missing_data_folder");
+        let blocks = BlockRegistry::builtin();
+        assert_eq!(
+            ItemRegistry::load(missing, &blocks).unwrap(),
+            ItemRegistry::builtin(&blocks)
+        );
+
+        let custom: Vec<String> = REQUIRED_BLOCKS
+            .iter()
+            .map(|name| format!("(name: \"{}\", textures: (all: \"Stone\"))", name))
+            .collect();
+        let custom = BlockRegistry::from_ron(&format!("[{}]", custom.join(", "))).unwrap();
+        assert!(ItemRegistry::load(missing, &custom).is_err());
+    }
+}
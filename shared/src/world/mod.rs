@@ -1,9 +1,19 @@
+pub mod biomes;
 pub mod blocks;
 pub mod data;
 pub mod items;
+pub mod palette;
+pub mod presets;
+pub mod recipes;
+pub mod settings;
 mod utils;
 
+pub use biomes::*;
 pub use blocks::*;
 pub use data::*;
 pub use items::*;
+pub use palette::*;
+pub use presets::*;
+pub use recipes::*;
+pub use settings::*;
 pub use utils::*;
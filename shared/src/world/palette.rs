@@ -0,0 +1,392 @@
+use bevy::math::IVec3;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::BlockData;
+use crate::CHUNK_SIZE;
+
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Blocks of a chunk, stored as a flat 16³ array of indices into a palette of the
+/// distinct blocks it contains. Indices are bit-packed with as few bits as the palette
+/// size allows, and index 0 means there is no block.
+///
+/// The API mirrors the `HashMap<IVec3, BlockData>` it replaces, with positions local to the chunk.
+/// Saves use it with their own block representation, hence the generic palette entry type
+#[derive(Clone, Debug)]
+pub struct PalettedContainer<T = BlockData> {
+    palette: Vec<T>,
+    bits: u8,
+    data: Vec<u64>,
+    len: usize,
+}
+
+/// Compact form of a `PalettedContainer`, used for serialization
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackedBlocks<T = BlockData> {
+    pub palette: Vec<T>,
+    pub bits: u8,
+    pub data: Vec<u64>,
+}
+
+/// Number of bits needed to store indices from 0 to `palette_len`
+fn bits_for(palette_len: usize) -> u8 {
+    (usize::BITS - palette_len.leading_zeros()) as u8
+}
+
+/// Indices never span two words, to keep reads simple
+fn words_for(bits: u8) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        CHUNK_VOLUME.div_ceil(64 / bits as usize)
+    }
+}
+
+fn read_packed(data: &[u64], bits: u8, index: usize) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) * bits as usize;
+    ((data[index / per_word] >> shift) & ((1 << bits) - 1)) as usize
+}
+
+fn write_packed(data: &mut [u64], bits: u8, index: usize, value: usize) {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) * bits as usize;
+    let mask = ((1u64 << bits) - 1) << shift;
+    let word = &mut data[index / per_word];
+    *word = (*word & !mask) | ((value as u64) << shift);
+}
+
+fn index_of(pos: &IVec3) -> Option<usize> {
+    let range = 0..CHUNK_SIZE;
+    if range.contains(&pos.x) && range.contains(&pos.y) && range.contains(&pos.z) {
+        Some(((pos.y * CHUNK_SIZE + pos.z) * CHUNK_SIZE + pos.x) as usize)
+    } else {
+        None
+    }
+}
+
+fn local_pos(index: usize) -> IVec3 {
+    let index = index as i32;
+    IVec3::new(
+        index % CHUNK_SIZE,
+        index / (CHUNK_SIZE * CHUNK_SIZE),
+        (index / CHUNK_SIZE) % CHUNK_SIZE,
+    )
+}
+
+impl<T> Default for PalettedContainer<T> {
+    fn default() -> Self {
+        PalettedContainer {
+            palette: Vec::new(),
+            bits: 0,
+            data: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> PalettedContainer<T> {
+    pub fn get(&self, pos: &IVec3) -> Option<&T> {
+        let value = read_packed(&self.data, self.bits, index_of(pos)?);
+        if value == 0 {
+            None
+        } else {
+            Some(&self.palette[value - 1])
+        }
+    }
+
+    pub fn contains_key(&self, pos: &IVec3) -> bool {
+        self.get(pos).is_some()
+    }
+
+    /// Positions outside of the chunk are ignored
+    pub fn insert(&mut self, pos: IVec3, block: T) -> Option<T> {
+        let index = index_of(&pos)?;
+        let value = self.palette_value(block);
+        self.set_value(index, value)
+    }
+
+    pub fn remove(&mut self, pos: &IVec3) -> Option<T> {
+        let index = index_of(pos)?;
+        self.set_value(index, 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, &T)> {
+        (0..CHUNK_VOLUME).filter_map(
+            move |index| match read_packed(&self.data, self.bits, index) {
+                0 => None,
+                value => Some((local_pos(index), &self.palette[value - 1])),
+            },
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Converts every block with `f`, blocks converted to `None` are removed
+    pub fn convert<U: Copy + PartialEq>(
+        &self,
+        mut f: impl FnMut(&T) -> Option<U>,
+    ) -> PalettedContainer<U> {
+        let palette: Vec<Option<U>> = self.palette.iter().map(&mut f).collect();
+        let mut container = PalettedContainer::default();
+        for index in 0..CHUNK_VOLUME {
+            let value = read_packed(&self.data, self.bits, index);
+            if let Some(Some(block)) = palette.get(value.wrapping_sub(1)) {
+                let value = container.palette_value(*block);
+                container.set_value(index, value);
+            }
+        }
+        container
+    }
+
+    pub fn to_packed(&self) -> PackedBlocks<T> {
+        let mut compacted = self.clone();
+        compacted.compact();
+        PackedBlocks {
+            palette: compacted.palette,
+            bits: compacted.bits,
+            data: compacted.data,
+        }
+    }
+
+    fn set_value(&mut self, index: usize, value: usize) -> Option<T> {
+        let old = read_packed(&self.data, self.bits, index);
+        if old == value {
+            return self.palette.get(old.wrapping_sub(1)).copied();
+        }
+        write_packed(&mut self.data, self.bits, index, value);
+
+        if old == 0 {
+            self.len += 1;
+            None
+        } else {
+            if value == 0 {
+                self.len -= 1;
+            }
+            Some(self.palette[old - 1])
+        }
+    }
+
+    /// Value stored in the data array for the given block, adding it to the palette if needed
+    fn palette_value(&mut self, block: T) -> usize {
+        if let Some(i) = self.palette.iter().position(|b| *b == block) {
+            return i + 1;
+        }
+
+        if bits_for(self.palette.len() + 1) > self.bits {
+            // Make room by dropping unused entries before using more bits per block
+            self.compact();
+            let bits = bits_for(self.palette.len() + 1);
+            if bits > self.bits {
+                self.repack(bits, |value| value);
+            }
+        }
+
+        self.palette.push(block);
+        self.palette.len()
+    }
+
+    /// Removes palette entries that are no longer used, and uses as few bits as possible
+    fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len() + 1];
+        for index in 0..CHUNK_VOLUME {
+            used[read_packed(&self.data, self.bits, index)] = true;
+        }
+
+        let mut remap = vec![0; self.palette.len() + 1];
+        let mut palette = Vec::new();
+        for (i, block) in self.palette.iter().enumerate() {
+            if used[i + 1] {
+                palette.push(*block);
+                remap[i + 1] = palette.len();
+            }
+        }
+
+        self.palette = palette;
+        self.repack(bits_for(self.palette.len()), |value| remap[value]);
+    }
+
+    fn repack(&mut self, bits: u8, remap: impl Fn(usize) -> usize) {
+        let mut data = vec![0; words_for(bits)];
+        if bits > 0 {
+            for index in 0..CHUNK_VOLUME {
+                let value = remap(read_packed(&self.data, self.bits, index));
+                write_packed(&mut data, bits, index, value);
+            }
+        }
+        self.bits = bits;
+        self.data = data;
+    }
+}
+
+/// Two containers are equal if they hold the same blocks, whatever their palette order
+impl<T: Copy + PartialEq> PartialEq for PalettedContainer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && (0..CHUNK_VOLUME).all(|index| {
+                let pos = local_pos(index);
+                self.get(&pos) == other.get(&pos)
+            })
+    }
+}
+
+impl<T> TryFrom<PackedBlocks<T>> for PalettedContainer<T> {
+    type Error = String;
+
+    fn try_from(packed: PackedBlocks<T>) -> Result<Self, Self::Error> {
+        if packed.bits > 16 || packed.data.len() != words_for(packed.bits) {
+            return Err(format!(
+                "invalid packed blocks: {} bits for {} words",
+                packed.bits,
+                packed.data.len()
+            ));
+        }
+
+        let mut len = 0;
+        for index in 0..CHUNK_VOLUME {
+            match read_packed(&packed.data, packed.bits, index) {
+                0 => {}
+                value if value <= packed.palette.len() => len += 1,
+                value => {
+                    return Err(format!(
+                        "invalid packed blocks: palette index {} out of range",
+                        value
+                    ))
+                }
+            }
+        }
+
+        Ok(PalettedContainer {
+            palette: packed.palette,
+            bits: packed.bits,
+            data: packed.data,
+            len,
+        })
+    }
+}
+
+impl<T: Copy + PartialEq + Serialize> Serialize for PalettedContainer<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_packed().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for PalettedContainer<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PackedBlocks::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions spread over the whole chunk
+    fn position(i: usize) -> IVec3 {
+        local_pos(i * 13 % CHUNK_VOLUME)
+    }
+
+    #[test]
+    fn bits_per_block_grow_with_the_palette() {
+        let mut container = PalettedContainer::<u32>::default();
+        let expected_bits = [
+            (1, 1),
+            (2, 2),
+            (3, 2),
+            (4, 3),
+            (15, 4),
+            (16, 5),
+            (255, 8),
+            (256, 9),
+        ];
+        for i in 0..300 {
+            container.insert(position(i), i as u32);
+            if let Some((_, bits)) = expected_bits.iter().find(|(count, _)| *count == i + 1) {
+                assert_eq!(container.bits, *bits, "{} distinct blocks", i + 1);
+            }
+        }
+
+        assert_eq!(container.len(), 300);
+        for i in 0..300 {
+            assert_eq!(container.get(&position(i)), Some(&(i as u32)));
+        }
+        assert_eq!(container.get(&position(300)), None);
+        assert_eq!(container.insert(IVec3::new(CHUNK_SIZE, 0, 0), 1), None);
+        assert_eq!(container.len(), 300);
+    }
+
+    #[test]
+    fn unused_palette_entries_are_dropped() {
+        // Replacing the same block keeps the palette small instead of growing it
+        let mut container = PalettedContainer::<u32>::default();
+        for value in 0..100 {
+            container.insert(IVec3::ZERO, value);
+        }
+        assert_eq!(container.len(), 1);
+        assert!(container.bits <= 2);
+        assert_eq!(container.get(&IVec3::ZERO), Some(&99));
+
+        for i in 0..50 {
+            container.insert(position(i), i as u32 % 5);
+        }
+        for i in 0..50 {
+            assert_eq!(container.remove(&position(i)), Some(i as u32 % 5));
+        }
+        assert_eq!(container.remove(&position(0)), None);
+        assert!(container.is_empty());
+        assert_eq!(container.iter().count(), 0);
+
+        let packed = container.to_packed();
+        assert!(packed.palette.is_empty());
+        assert_eq!(packed.bits, 0);
+        assert!(packed.data.is_empty());
+    }
+
+    #[test]
+    fn packed_blocks_round_trip_and_malformed_ones_are_rejected() {
+        let mut container = PalettedContainer::<u32>::default();
+        for i in 0..40 {
+            container.insert(position(i), i as u32 % 7);
+        }
+        container.remove(&position(3));
+
+        let packed = container.to_packed();
+        assert_eq!(packed.palette.len(), 7);
+        assert_eq!(packed.bits, 3);
+        let unpacked = PalettedContainer::try_from(packed.clone()).unwrap();
+        assert_eq!(unpacked, container);
+        assert_eq!(unpacked.len(), 39);
+
+        let too_many_bits = PackedBlocks {
+            bits: 17,
+            data: vec![0; words_for(17)],
+            ..packed.clone()
+        };
+        assert!(PalettedContainer::try_from(too_many_bits).is_err());
+
+        let truncated = PackedBlocks {
+            data: packed.data[1..].to_vec(),
+            ..packed.clone()
+        };
+        assert!(PalettedContainer::try_from(truncated).is_err());
+
+        let mut out_of_range = PackedBlocks {
+            palette: vec![1u32],
+            bits: 2,
+            data: vec![0; words_for(2)],
+        };
+        write_packed(&mut out_of_range.data, 2, 10, 3);
+        assert!(PalettedContainer::try_from(out_of_range).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use super::BiomeRegistry;
+
+/// How the terrain of a world is generated. Chosen when the world is created, then stored in its save
+#[derive(Resource, Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum WorldPreset {
+    /// Biomes, caves and features
+    #[default]
+    Default,
+    /// Flat layers of blocks
+    Superflat { layers: Vec<FlatLayer> },
+    /// Nothing but a small platform to spawn on
+    Void,
+    /// Default terrain, with the same biome everywhere
+    SingleBiome { biome: String },
+    /// Default terrain, with much higher mountains and deeper oceans
+    Amplified,
+}
+
+/// Layer of a superflat world. Layers are listed from the bottom of the world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlatLayer {
+    /// Name of the block, as in saves
+    pub block: String,
+    pub thickness: u32,
+}
+
+impl FlatLayer {
+    pub fn new(block: &str, thickness: u32) -> Self {
+        FlatLayer {
+            block: block.to_string(),
+            thickness,
+        }
+    }
+}
+
+impl WorldPreset {
+    /// Layers of a superflat world unless configured otherwise
+    pub fn default_flat_layers() -> Vec<FlatLayer> {
+        vec![
+            FlatLayer::new("bedrock", 1),
+            FlatLayer::new("dirt", 2),
+            FlatLayer::new("grass", 1),
+        ]
+    }
+
+    /// Presets offered when creating a world
+    pub fn all(biomes: &BiomeRegistry) -> Vec<WorldPreset> {
+        let mut presets = vec![
+            WorldPreset::Default,
+            WorldPreset::Superflat {
+                layers: Self::default_flat_layers(),
+            },
+            WorldPreset::Void,
+            WorldPreset::Amplified,
+        ];
+        presets.extend(biomes.iter().map(|biome| WorldPreset::SingleBiome {
+            biome: biome.name.clone(),
+        }));
+        presets
+    }
+
+    pub fn get_name(&self) -> String {
+        match self {
+            WorldPreset::Default => "Default".into(),
+            WorldPreset::Superflat { .. } => "Superflat".into(),
+            WorldPreset::Void => "Void".into(),
+            WorldPreset::SingleBiome { biome } => format!("Single biome ({})", biome),
+            WorldPreset::Amplified => "Amplified".into(),
+        }
+    }
+}
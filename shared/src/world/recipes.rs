@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::Resource;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::{ItemId, ItemRegistry};
+
+/// File of the data folder where crafting recipes are defined
+pub const RECIPES_FILE: &str = "recipes.ron";
+
+/// Recipes shipped with the game, used when the data folder has no recipe definitions
+const BUILTIN_RECIPES: &str = include_str!("../../../data/recipes.ron");
+
+/// Side of the crafting grid of the inventory
+pub const INVENTORY_CRAFTING_SIZE: usize = 2;
+
+/// Side of the crafting grid of crafting tables, the largest one
+pub const TABLE_CRAFTING_SIZE: usize = 3;
+
+/// Stack produced by a recipe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeResult<I = ItemId> {
+    pub item: I,
+    pub count: u32,
+}
+
+/// Way of crafting an item, as defined in the recipes file. Items are first read by name,
+/// then by their ID in the item registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recipe<I = ItemId> {
+    /// Items placed in a given arrangement, anywhere in the grid.
+    /// Rows of `pattern` use the characters of `key`, spaces being empty slots
+    Shaped {
+        pattern: Vec<String>,
+        key: HashMap<char, I>,
+        result: RecipeResult<I>,
+    },
+    /// Items placed anywhere in the grid
+    Shapeless {
+        ingredients: Vec<I>,
+        result: RecipeResult<I>,
+    },
+}
+
+impl<I> Recipe<I> {
+    pub fn result(&self) -> &RecipeResult<I> {
+        match self {
+            Recipe::Shaped { result, .. } | Recipe::Shapeless { result, .. } => result,
+        }
+    }
+
+    /// Side of the smallest crafting grid this recipe can be made in
+    pub fn grid_size(&self) -> usize {
+        match self {
+            Recipe::Shaped { pattern, .. } => pattern
+                .iter()
+                .map(|row| row.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(pattern.len()),
+            Recipe::Shapeless { ingredients, .. } => (1..=TABLE_CRAFTING_SIZE)
+                .find(|size| size * size >= ingredients.len())
+                .unwrap_or(usize::MAX),
+        }
+    }
+}
+
+impl Recipe<String> {
+    fn resolve(self, items: &ItemRegistry) -> Result<Recipe, String> {
+        let id = |name: String| {
+            items
+                .id(&name)
+                .ok_or_else(|| format!("unknown item '{}' in a recipe", name))
+        };
+        let resolve_result = |result: RecipeResult<String>| -> Result<RecipeResult, String> {
+            Ok(RecipeResult {
+                item: id(result.item)?,
+                count: result.count,
+            })
+        };
+        Ok(match self {
+            Recipe::Shaped {
+                pattern,
+                key,
+                result,
+            } => Recipe::Shaped {
+                pattern,
+                key: key
+                    .into_iter()
+                    .map(|(c, name)| Ok((c, id(name)?)))
+                    .collect::<Result<_, String>>()?,
+                result: resolve_result(result)?,
+            },
+            Recipe::Shapeless {
+                ingredients,
+                result,
+            } => Recipe::Shapeless {
+                ingredients: ingredients.into_iter().map(id).collect::<Result<_, _>>()?,
+                result: resolve_result(result)?,
+            },
+        })
+    }
+}
+
+impl Recipe {
+    fn validate(&self) -> Result<(), String> {
+        if self.result().count == 0 {
+            return Err("a recipe produces no items".into());
+        }
+        match self {
+            Recipe::Shaped { pattern, key, .. } => {
+                let width = pattern.first().map_or(0, |row| row.chars().count());
+                if width == 0 || pattern.iter().any(|row| row.chars().count() != width) {
+                    return Err(format!("recipe pattern {:?} is not a rectangle", pattern));
+                }
+                if let Some(c) = pattern
+                    .iter()
+                    .flat_map(|row| row.chars())
+                    .find(|c| *c != ' ' && !key.contains_key(c))
+                {
+                    return Err(format!(
+                        "recipe pattern {:?} uses unknown key '{}'",
+                        pattern, c
+                    ));
+                }
+            }
+            Recipe::Shapeless { ingredients, .. } => {
+                if ingredients.is_empty() {
+                    return Err("a shapeless recipe has no ingredients".into());
+                }
+            }
+        }
+        if self.grid_size() > TABLE_CRAFTING_SIZE {
+            return Err("a recipe does not fit in a crafting table".into());
+        }
+        Ok(())
+    }
+
+    /// Whether the items of a square crafting grid, given row by row, make this recipe
+    pub fn matches(&self, size: usize, grid: &[Option<ItemId>]) -> bool {
+        match self {
+            Recipe::Shaped { pattern, key, .. } => {
+                // The pattern can be anywhere : only the part of the grid holding items is compared
+                let filled = |x: usize, y: usize| grid[y * size + x].is_some();
+                let columns: Vec<usize> = (0..size)
+                    .filter(|x| (0..size).any(|y| filled(*x, y)))
+                    .collect();
+                let rows: Vec<usize> = (0..size)
+                    .filter(|y| (0..size).any(|x| filled(x, *y)))
+                    .collect();
+                let (Some(left), Some(top)) = (columns.first(), rows.first()) else {
+                    return false;
+                };
+                let width = columns.last().unwrap() - left + 1;
+                let height = rows.last().unwrap() - top + 1;
+                if height != pattern.len() || pattern.iter().any(|row| row.chars().count() != width)
+                {
+                    return false;
+                }
+                pattern.iter().enumerate().all(|(y, row)| {
+                    row.chars()
+                        .enumerate()
+                        .all(|(x, c)| grid[(top + y) * size + left + x] == key.get(&c).copied())
+                })
+            }
+            Recipe::Shapeless { ingredients, .. } => {
+                let mut placed: Vec<ItemId> = grid.iter().flatten().copied().collect();
+                let mut ingredients = ingredients.clone();
+                placed.sort();
+                ingredients.sort();
+                placed == ingredients
+            }
+        }
+    }
+}
+
+/// All known crafting recipes. Servers send theirs to the clients joining them
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Recipe>", into = "Vec<Recipe>")]
+pub struct RecipeRegistry {
+    recipes: Vec<Recipe>,
+}
+
+impl TryFrom<Vec<Recipe>> for RecipeRegistry {
+    type Error = String;
+
+    fn try_from(recipes: Vec<Recipe>) -> Result<Self, Self::Error> {
+        for recipe in recipes.iter() {
+            recipe.validate()?;
+        }
+        Ok(RecipeRegistry { recipes })
+    }
+}
+
+impl From<RecipeRegistry> for Vec<Recipe> {
+    fn from(registry: RecipeRegistry) -> Self {
+        registry.recipes
+    }
+}
+
+impl RecipeRegistry {
+    pub fn from_ron(
+        contents: &str,
+        items: &ItemRegistry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let recipes: Vec<Recipe<String>> = ron::de::from_str(contents)?;
+        let recipes = recipes
+            .into_iter()
+            .map(|recipe| recipe.resolve(items))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RecipeRegistry::try_from(recipes)?)
+    }
+
+    pub fn builtin(items: &ItemRegistry) -> Self {
+        Self::from_ron(BUILTIN_RECIPES, items).expect("built-in recipes are invalid")
+    }
+
+    /// Loads the recipes of the data folder, or the built-in ones if they cannot be read
+    pub fn load(assets_folder: &Path, items: &ItemRegistry) -> Self {
+        let path = assets_folder.join(RECIPES_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::from_ron(&contents, items).unwrap_or_else(|e| {
+                warn!(
+                    "Invalid recipes in {}, using built-in ones: {}",
+                    path.display(),
+                    e
+                );
+                Self::builtin(items)
+            }),
+            Err(_) => Self::builtin(items),
+        }
+    }
+
+    /// Recipe made by the items of a square crafting grid of side `size`, given row by row
+    pub fn find(&self, size: usize, grid: &[Option<ItemId>]) -> Option<&Recipe> {
+        if grid.len() != size * size {
+            return None;
+        }
+        self.recipes
+            .iter()
+            .find(|recipe| recipe.grid_size() <= size && recipe.matches(size, grid))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BlockRegistry;
+
+    fn registries() -> (ItemRegistry, RecipeRegistry) {
+        let items = ItemRegistry::builtin(&BlockRegistry::builtin());
+        let recipes = RecipeRegistry::builtin(&items);
+        (items, recipes)
+    }
+
+    #[test]
+    fn shaped_recipes_match_anywhere_in_the_grid() {
+        let (items, recipes) = registries();
+        let planks = items.id("oak_planks");
+
+        let mut grid = vec![None; 9];
+        for slot in [4, 5, 7, 8] {
+            grid[slot] = planks;
+        }
+        let recipe = recipes.find(TABLE_CRAFTING_SIZE, &grid).unwrap();
+        assert_eq!(Some(recipe.result().item), items.id("crafting_table"));
+
+        // Same items in another arrangement
+        grid[8] = None;
+        grid[3] = planks;
+        assert!(recipes.find(TABLE_CRAFTING_SIZE, &grid).is_none());
+    }
+
+    #[test]
+    fn shapeless_recipes_ignore_positions_but_not_grid_sizes() {
+        let (items, recipes) = registries();
+        let log = items.id("oak_log");
+
+        let grid = [None, None, log, None];
+        let recipe = recipes.find(INVENTORY_CRAFTING_SIZE, &grid).unwrap();
+        assert_eq!(Some(recipe.result().item), items.id("oak_planks"));
+        assert_eq!(recipe.result().count, 4);
+
+        let grid = [log, log, None, None];
+        assert!(recipes.find(INVENTORY_CRAFTING_SIZE, &grid).is_none());
+
+        // Shaped patterns larger than the grid are never made
+        let too_large = Recipe::Shaped {
+            pattern: vec!["###".into()],
+            key: HashMap::from([('#', log.unwrap())]),
+            result: RecipeResult {
+                item: log.unwrap(),
+                count: 1,
+            },
+        };
+        let recipes = RecipeRegistry::try_from(vec![too_large]).unwrap();
+        assert!(recipes
+            .find(INVENTORY_CRAFTING_SIZE, &[log, log, None, None])
+            .is_none());
+        assert!(recipes
+            .find(
+                TABLE_CRAFTING_SIZE,
+                &[log, log, log, None, None, None, None, None, None]
+            )
+            .is_some());
+    }
+}
@@ -0,0 +1,125 @@
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::world::WorldHeight;
+use crate::SEA_LEVEL;
+
+/// Largest blend distance, farther biomes would barely weigh on the height of a column anyway
+pub const MAX_BLEND_DISTANCE: i32 = 64;
+
+/// Parameters of the terrain generation. Chosen when the world is created, then stored in its save.
+/// Missing fields keep their default value, which gives the default terrain
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    /// Scale of the terrain height noise, higher values give bumpier terrain
+    pub terrain_scale: f64,
+    /// Scale of the climate noises, lower values give wider biomes
+    pub biome_scale: f64,
+    /// Distance in blocks of the columns whose biome is blended into the height of a column,
+    /// at most `MAX_BLEND_DISTANCE`
+    pub blend_distance: i32,
+    /// Terrain below this height is filled with water
+    pub sea_level: i32,
+    /// Multiplies the densities of the flora of all biomes, 0 disables flora
+    pub flora_density: f32,
+    /// Multiplies the densities of the boulders of all biomes
+    pub boulder_density: f32,
+    /// Multiplies the cave frequencies of all biomes
+    pub cave_frequency: f64,
+    /// Multiplies the number of ore veins
+    pub ore_frequency: f32,
+    /// Multiplies the chances of structures to be placed in their regions
+    pub structure_chance: f32,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            terrain_scale: 0.1,
+            biome_scale: 0.02,
+            blend_distance: 4,
+            sea_level: SEA_LEVEL,
+            flora_density: 1.0,
+            boulder_density: 1.0,
+            cave_frequency: 1.0,
+            ore_frequency: 1.0,
+            structure_chance: 1.0,
+        }
+    }
+}
+
+impl GeneratorSettings {
+    pub fn from_ron(contents: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let settings: GeneratorSettings = ron::de::from_str(contents)?;
+        for (name, value) in [
+            ("terrain_scale", settings.terrain_scale),
+            ("biome_scale", settings.biome_scale),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("{} must be a finite number above 0", name).into());
+            }
+        }
+        if !(0..=MAX_BLEND_DISTANCE).contains(&settings.blend_distance) {
+            return Err(format!(
+                "the blend distance must be between 0 and {}",
+                MAX_BLEND_DISTANCE
+            )
+            .into());
+        }
+        let multipliers = [
+            ("flora_density", settings.flora_density as f64),
+            ("boulder_density", settings.boulder_density as f64),
+            ("cave_frequency", settings.cave_frequency),
+            ("ore_frequency", settings.ore_frequency as f64),
+            ("structure_chance", settings.structure_chance as f64),
+        ];
+        for (name, value) in multipliers {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("{} must be a finite number of at least 0", name).into());
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Checks that the settings fit a world of the given height, when it is created
+    pub fn check_height(&self, height: &WorldHeight) -> Result<(), String> {
+        if !height.contains(self.sea_level) {
+            return Err(format!(
+                "the sea level {} is outside of the world height ({} to {})",
+                self.sea_level, height.min_y, height.max_y
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_multipliers_and_sea_levels_are_rejected() {
+        assert!(GeneratorSettings::from_ron("(flora_density: 0.0)").is_ok());
+        assert!(GeneratorSettings::from_ron("(ore_frequency: -1.0)").is_err());
+        assert!(GeneratorSettings::from_ron("(cave_frequency: inf)").is_err());
+        assert!(GeneratorSettings::from_ron("(structure_chance: NaN)").is_err());
+
+        assert!(GeneratorSettings::from_ron("(terrain_scale: 0.0)").is_err());
+        assert!(GeneratorSettings::from_ron("(terrain_scale: NaN)").is_err());
+        assert!(GeneratorSettings::from_ron("(biome_scale: inf)").is_err());
+        assert!(GeneratorSettings::from_ron("(biome_scale: -0.5)").is_err());
+
+        let max_blend = format!("(blend_distance: {})", MAX_BLEND_DISTANCE);
+        assert!(GeneratorSettings::from_ron(&max_blend).is_ok());
+        let too_far = format!("(blend_distance: {})", MAX_BLEND_DISTANCE + 1);
+        assert!(GeneratorSettings::from_ron(&too_far).is_err());
+        assert!(GeneratorSettings::from_ron("(blend_distance: 2147483647)").is_err());
+        assert!(GeneratorSettings::from_ron("(blend_distance: -1)").is_err());
+
+        let height = WorldHeight::default();
+        assert!(GeneratorSettings::default().check_height(&height).is_ok());
+        let settings = GeneratorSettings::from_ron("(sea_level: 1000)").unwrap();
+        assert!(settings.check_height(&height).is_err());
+    }
+}
@@ -60,6 +60,26 @@ pub const SIX_OFFSETS: [IVec3; 6] = [
     IVec3::new(0, 0, -1),
 ];
 
-pub fn chunk_in_radius(player_pos: &IVec3, chunk_pos: &IVec3, radius: i32) -> bool {
-    (player_pos.x - chunk_pos.x).abs() <= radius && (player_pos.z - chunk_pos.z).abs() <= radius
+/// Whether a chunk is in the view volume around a player, `radius` chunks away
+/// horizontally and `vertical_radius` chunks away vertically
+pub fn chunk_in_radius(
+    player_pos: &IVec3,
+    chunk_pos: &IVec3,
+    radius: i32,
+    vertical_radius: i32,
+) -> bool {
+    (player_pos.x - chunk_pos.x).abs() <= radius
+        && (player_pos.z - chunk_pos.z).abs() <= radius
+        && (player_pos.y - chunk_pos.y).abs() <= vertical_radius
+}
+
+/// Copy of a value sent to the clients when they join, as they receive it
+#[cfg(test)]
+pub(crate) fn through_handshake<T>(value: &T) -> T
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    use bincode::Options;
+    let bytes = bincode::options().serialize(value).unwrap();
+    bincode::options().deserialize(&bytes).unwrap()
 }